@@ -0,0 +1,5 @@
+// Generated by `sqlx migrate build-script`
+fn main() {
+    // Trigger recompilation when a new migration is added
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,14 @@
+-- Sampled values rejected by the MeterValues validation (unknown measurand or unit).
+-- Kept verbatim so charger firmware bugs can be debugged later.
+CREATE TABLE IF NOT EXISTS meter_readings_raw (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    connector_id INTEGER,
+    transaction_id INTEGER,
+    measurand TEXT,
+    unit TEXT,
+    sampled_value JSONB NOT NULL,
+    received_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS meter_readings_raw_station_id_idx ON meter_readings_raw (station_id);
@@ -0,0 +1,27 @@
+-- Chargers as last reported by their BootNotification
+CREATE TABLE IF NOT EXISTS chargers (
+    station_id TEXT PRIMARY KEY,
+    charge_point_vendor TEXT NOT NULL,
+    charge_point_model TEXT NOT NULL,
+    charge_point_serial_number TEXT,
+    charge_box_serial_number TEXT,
+    firmware_version TEXT,
+    iccid TEXT,
+    imsi TEXT,
+    meter_type TEXT,
+    meter_serial_number TEXT,
+    first_boot_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    last_boot_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Firmware versions reported by a charger that differ from the previously stored one
+CREATE TABLE IF NOT EXISTS firmware_change_events (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL REFERENCES chargers (station_id),
+    old_version TEXT,
+    new_version TEXT,
+    changed_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS firmware_change_events_station_id_idx
+    ON firmware_change_events (station_id, changed_at);
@@ -0,0 +1,12 @@
+-- One row per WebSocket connection of a charger
+CREATE TABLE IF NOT EXISTS charger_sessions (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    remote_addr TEXT NOT NULL,
+    protocol_version TEXT,
+    connected_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    disconnected_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS charger_sessions_station_id_idx
+    ON charger_sessions (station_id, connected_at);
@@ -0,0 +1,2 @@
+-- Last measured difference between the charger clock and the server clock, in seconds
+ALTER TABLE charger_sessions ADD COLUMN IF NOT EXISTS clock_skew_secs BIGINT;
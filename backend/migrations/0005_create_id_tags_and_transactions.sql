@@ -0,0 +1,27 @@
+-- Authorization of the RFID cards, mirrored by the in-memory authorization cache
+CREATE TABLE IF NOT EXISTS id_tags (
+    id_tag TEXT PRIMARY KEY,
+    status TEXT NOT NULL DEFAULT 'Accepted'
+        CHECK (status IN ('Accepted', 'Blocked', 'Expired', 'Invalid', 'ConcurrentTx')),
+    expiry_date TIMESTAMPTZ,
+    parent_id_tag TEXT
+);
+
+-- Charge sessions. Transactions whose idTag was not accepted are kept as 'blocked' for audit
+CREATE TABLE IF NOT EXISTS transactions (
+    id SERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    connector_id INTEGER NOT NULL,
+    id_tag TEXT NOT NULL,
+    status TEXT NOT NULL CHECK (status IN ('active', 'blocked', 'completed')),
+    meter_start INTEGER NOT NULL,
+    start_time TIMESTAMPTZ NOT NULL,
+    meter_stop INTEGER,
+    stop_time TIMESTAMPTZ,
+    stop_reason TEXT,
+    energy_wh BIGINT,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE INDEX IF NOT EXISTS transactions_station_id_idx
+    ON transactions (station_id, connector_id, start_time);
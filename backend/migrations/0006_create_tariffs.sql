@@ -0,0 +1,20 @@
+-- Energy prices of the stations. Tariffs with a time of day only apply between time_of_day_start
+-- and time_of_day_end (UTC), which wraps around midnight when the start is after the end
+CREATE TABLE IF NOT EXISTS tariffs (
+    id SERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    price_per_kwh DOUBLE PRECISION NOT NULL CHECK (price_per_kwh >= 0),
+    currency TEXT NOT NULL CHECK (length(currency) = 3),
+    valid_from TIMESTAMPTZ NOT NULL,
+    valid_to TIMESTAMPTZ CHECK (valid_to > valid_from),
+    time_of_day_start TIME,
+    time_of_day_end TIME,
+    CHECK ((time_of_day_start IS NULL) = (time_of_day_end IS NULL))
+);
+
+CREATE INDEX IF NOT EXISTS tariffs_station_id_idx ON tariffs (station_id, valid_from);
+
+-- Estimated cost of the completed transactions, from the tariff applicable at their start
+ALTER TABLE transactions
+    ADD COLUMN IF NOT EXISTS cost DOUBLE PRECISION,
+    ADD COLUMN IF NOT EXISTS currency TEXT;
@@ -0,0 +1,15 @@
+-- Every connector state reported by a StatusNotification, for fault analysis and maintenance
+-- planning
+CREATE TABLE IF NOT EXISTS status_notification_history (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    connector_id INTEGER NOT NULL,
+    status TEXT NOT NULL,
+    error_code TEXT NOT NULL,
+    vendor_error_code TEXT,
+    info TEXT,
+    timestamp TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS status_notification_history_connector_idx
+    ON status_notification_history (station_id, connector_id, timestamp);
@@ -0,0 +1,11 @@
+-- Owners of the RFID cards, so sessions can be reported by name instead of raw idTag
+CREATE TABLE IF NOT EXISTS users (
+    id SERIAL PRIMARY KEY,
+    id_tag TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    email TEXT,
+    external_id TEXT UNIQUE
+);
+
+ALTER TABLE transactions
+    ADD COLUMN IF NOT EXISTS user_id INTEGER REFERENCES users (id) ON DELETE SET NULL;
@@ -0,0 +1,15 @@
+-- Numeric sampled values of the MeterValues requests, for metering statistics
+CREATE TABLE IF NOT EXISTS meter_readings (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    connector_id INTEGER NOT NULL,
+    transaction_id INTEGER,
+    measurand TEXT NOT NULL,
+    unit TEXT NOT NULL,
+    phase TEXT,
+    value DOUBLE PRECISION NOT NULL,
+    timestamp TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS meter_readings_transaction_id_idx
+    ON meter_readings (transaction_id, measurand);
@@ -0,0 +1,2 @@
+-- Identifies the WebSocket connection of the session, to find its log lines
+ALTER TABLE charger_sessions ADD COLUMN IF NOT EXISTS connection_id UUID;
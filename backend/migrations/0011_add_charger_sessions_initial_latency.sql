@@ -0,0 +1,2 @@
+-- Time between the connection of the charger and its first BootNotification, in milliseconds
+ALTER TABLE charger_sessions ADD COLUMN IF NOT EXISTS initial_latency_ms INTEGER;
@@ -0,0 +1,9 @@
+-- Availability of the connectors set from the management UI, applied again whenever the charger
+-- reboots as ChangeAvailability does not survive a reboot
+CREATE TABLE IF NOT EXISTS availability_overrides (
+    station_id TEXT NOT NULL,
+    connector_id INTEGER NOT NULL CHECK (connector_id >= 0),
+    availability_type TEXT NOT NULL CHECK (availability_type IN ('Inoperative', 'Operative')),
+    set_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (station_id, connector_id)
+);
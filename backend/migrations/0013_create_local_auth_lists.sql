@@ -0,0 +1,16 @@
+-- Local Authorization Lists sent to the stations with SendLocalList, see OCPP 1.6 section 3.5
+CREATE TABLE IF NOT EXISTS local_auth_lists (
+    station_id TEXT PRIMARY KEY,
+    list_version INTEGER NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS local_auth_list_entries (
+    station_id TEXT NOT NULL REFERENCES local_auth_lists (station_id) ON DELETE CASCADE,
+    id_tag TEXT NOT NULL,
+    status TEXT NOT NULL
+        CHECK (status IN ('Accepted', 'Blocked', 'Expired', 'Invalid', 'ConcurrentTx')),
+    expiry_date TIMESTAMPTZ,
+    parent_id_tag TEXT,
+    PRIMARY KEY (station_id, id_tag)
+);
@@ -0,0 +1,3 @@
+-- Firmware version of the BootNotification normalized to a semantic version, NULL when it could
+-- not be parsed. firmware_version keeps the raw string reported by the charger
+ALTER TABLE chargers ADD COLUMN IF NOT EXISTS firmware_version_semver TEXT;
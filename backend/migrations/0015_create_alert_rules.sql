@@ -0,0 +1,28 @@
+-- Conditions on the OCPP events that notify the operators through a webhook. threshold_value is
+-- the maximum session length in hours for session_too_long, and the number of consecutive
+-- rejected authorizations for auth_failure_threshold
+CREATE TABLE IF NOT EXISTS alert_rules (
+    id SERIAL PRIMARY KEY,
+    rule_type TEXT NOT NULL CHECK (
+        rule_type IN ('charger_faulted', 'session_too_long', 'charger_offline', 'auth_failure_threshold')
+    ),
+    threshold_value DOUBLE PRECISION CHECK (threshold_value > 0),
+    webhook_url TEXT NOT NULL,
+    enabled BOOLEAN NOT NULL DEFAULT true,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+-- Alerts fired by the rules, kept for audit even when their rule is deleted
+CREATE TABLE IF NOT EXISTS alert_events (
+    id BIGSERIAL PRIMARY KEY,
+    rule_id INTEGER REFERENCES alert_rules (id) ON DELETE SET NULL,
+    rule_type TEXT NOT NULL,
+    station_id TEXT NOT NULL,
+    event JSONB NOT NULL,
+    fired_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    -- HTTP status of the webhook, NULL when it could not be reached
+    webhook_status INTEGER,
+    webhook_error TEXT
+);
+
+CREATE INDEX IF NOT EXISTS alert_events_fired_at_idx ON alert_events (fired_at);
@@ -0,0 +1,8 @@
+-- AuthorizationKey of the chargers for HTTP Basic Auth (OCPP 1.6 security profile 1). Only a
+-- salted SHA-256 hash of the key is stored
+CREATE TABLE IF NOT EXISTS charger_credentials (
+    station_id TEXT PRIMARY KEY,
+    auth_key_salt TEXT NOT NULL,
+    auth_key_hash TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
@@ -0,0 +1,8 @@
+-- Energy drawn from the grid and energy fed back to it by bi-directional (V2G) chargers, in Wh.
+-- energy_wh stays the imported energy, which is what is billed
+ALTER TABLE transactions
+    ADD COLUMN IF NOT EXISTS import_energy_wh BIGINT,
+    ADD COLUMN IF NOT EXISTS export_energy_wh BIGINT;
+
+UPDATE transactions SET import_energy_wh = energy_wh, export_energy_wh = 0
+    WHERE energy_wh IS NOT NULL AND import_energy_wh IS NULL;
@@ -0,0 +1,20 @@
+-- Clusters of chargers managed as a unit, e.g. all the chargers of a parking lot. A group contains
+-- chargers and other groups, never itself through any chain of subgroups
+CREATE TABLE IF NOT EXISTS charger_groups (
+    id SERIAL PRIMARY KEY,
+    name TEXT NOT NULL UNIQUE,
+    created_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE TABLE IF NOT EXISTS charger_group_chargers (
+    group_id INTEGER NOT NULL REFERENCES charger_groups (id) ON DELETE CASCADE,
+    station_id TEXT NOT NULL,
+    PRIMARY KEY (group_id, station_id)
+);
+
+CREATE TABLE IF NOT EXISTS charger_group_subgroups (
+    group_id INTEGER NOT NULL REFERENCES charger_groups (id) ON DELETE CASCADE,
+    subgroup_id INTEGER NOT NULL REFERENCES charger_groups (id) ON DELETE CASCADE,
+    PRIMARY KEY (group_id, subgroup_id),
+    CHECK (group_id <> subgroup_id)
+);
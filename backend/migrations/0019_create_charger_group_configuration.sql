@@ -0,0 +1,9 @@
+-- Configuration intended for the chargers of a group, pushed again to each of them when it boots,
+-- so that chargers that were offline or joined the group later get it too
+CREATE TABLE IF NOT EXISTS charger_group_configuration (
+    group_id INTEGER NOT NULL REFERENCES charger_groups (id) ON DELETE CASCADE,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMPTZ NOT NULL DEFAULT now(),
+    PRIMARY KEY (group_id, key)
+);
@@ -0,0 +1,3 @@
+-- Per-charger statistics over a period, see GET /api/stats/top-chargers
+CREATE INDEX IF NOT EXISTS transactions_station_id_start_time_idx
+    ON transactions (station_id, start_time);
@@ -0,0 +1,3 @@
+-- IANA timezone of the charger, e.g. America/Sao_Paulo. The currentTime of the BootNotification
+-- response is expressed in its offset, NULL keeps it in UTC
+ALTER TABLE chargers ADD COLUMN IF NOT EXISTS timezone TEXT;
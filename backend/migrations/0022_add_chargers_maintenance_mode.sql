@@ -0,0 +1,2 @@
+-- Chargers in maintenance reject new sessions, the running ones complete normally
+ALTER TABLE chargers ADD COLUMN IF NOT EXISTS maintenance_mode BOOLEAN NOT NULL DEFAULT false;
@@ -0,0 +1,29 @@
+-- Transactions of a connector that overlap, which would bill the same charge twice. Each pair is
+-- recorded once, whichever of the two transactions stopped first
+CREATE TABLE IF NOT EXISTS billing_anomalies (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    connector_id INTEGER NOT NULL,
+    transaction_id INTEGER NOT NULL REFERENCES transactions (id) ON DELETE CASCADE,
+    overlapping_transaction_id INTEGER NOT NULL REFERENCES transactions (id) ON DELETE CASCADE,
+    detected_at TIMESTAMPTZ NOT NULL DEFAULT now()
+);
+
+CREATE UNIQUE INDEX IF NOT EXISTS billing_anomalies_transactions_idx ON billing_anomalies (
+    LEAST(transaction_id, overlapping_transaction_id),
+    GREATEST(transaction_id, overlapping_transaction_id)
+);
+
+CREATE INDEX IF NOT EXISTS billing_anomalies_station_id_idx
+    ON billing_anomalies (station_id, detected_at);
+
+ALTER TABLE alert_rules DROP CONSTRAINT IF EXISTS alert_rules_rule_type_check;
+ALTER TABLE alert_rules ADD CONSTRAINT alert_rules_rule_type_check CHECK (
+    rule_type IN (
+        'charger_faulted',
+        'session_too_long',
+        'charger_offline',
+        'auth_failure_threshold',
+        'session_overlap'
+    )
+);
@@ -0,0 +1,13 @@
+-- Certificates issued to the chargers from their SignCertificate CSRs, see OCPP 1.6 Security
+-- Profile 3. serial_number is the hexadecimal serial of the certificate
+CREATE TABLE IF NOT EXISTS charger_certificates (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    serial_number TEXT NOT NULL UNIQUE,
+    certificate TEXT NOT NULL,
+    issued_at TIMESTAMPTZ NOT NULL,
+    expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS charger_certificates_station_id_idx
+    ON charger_certificates (station_id, issued_at);
@@ -0,0 +1,2 @@
+-- Transactions grouped by stop reason, see GET /api/reports/stop-reasons
+CREATE INDEX IF NOT EXISTS transactions_stop_reason_idx ON transactions (stop_reason);
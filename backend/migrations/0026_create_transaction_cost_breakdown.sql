@@ -0,0 +1,15 @@
+-- Time of use pricing: a tariff can be named after its period, e.g. peak, and restricted to a day
+-- of the week (ISO 8601, 1 is Monday), in UTC like its time of day
+ALTER TABLE tariffs
+    ADD COLUMN IF NOT EXISTS period TEXT,
+    ADD COLUMN IF NOT EXISTS day_of_week SMALLINT CHECK (day_of_week BETWEEN 1 AND 7);
+
+-- Energy and cost of the completed transactions in each tariff period they went through
+CREATE TABLE IF NOT EXISTS transaction_cost_breakdown (
+    transaction_id INTEGER NOT NULL REFERENCES transactions (id) ON DELETE CASCADE,
+    period TEXT NOT NULL,
+    currency TEXT NOT NULL,
+    energy_wh BIGINT NOT NULL,
+    cost DOUBLE PRECISION NOT NULL,
+    PRIMARY KEY (transaction_id, period, currency)
+);
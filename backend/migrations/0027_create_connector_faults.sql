@@ -0,0 +1,20 @@
+-- Faults reported by StatusNotifications with the Faulted status, open until resolved after the
+-- maintenance of the connector
+CREATE TABLE IF NOT EXISTS connector_faults (
+    id BIGSERIAL PRIMARY KEY,
+    station_id TEXT NOT NULL,
+    connector_id INTEGER NOT NULL,
+    error_code TEXT NOT NULL,
+    vendor_error_code TEXT,
+    info TEXT,
+    faulted_at TIMESTAMPTZ NOT NULL,
+    resolved_at TIMESTAMPTZ
+);
+
+CREATE INDEX IF NOT EXISTS connector_faults_station_id_idx
+    ON connector_faults (station_id, faulted_at);
+
+-- A connector has at most one open fault of an error code, repeated notifications reuse it
+CREATE UNIQUE INDEX IF NOT EXISTS connector_faults_open_idx
+    ON connector_faults (station_id, connector_id, error_code)
+    WHERE resolved_at IS NULL;
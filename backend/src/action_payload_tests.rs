@@ -0,0 +1,114 @@
+//! Consistency of the action of a Call with its payload, see `action_matches_payload`
+
+use serde_json::json;
+
+use crate::{action_matches_payload, OcppActionEnum, OcppPayload};
+
+/// A request of each action
+fn requests() -> Vec<(OcppActionEnum, serde_json::Value)> {
+    use OcppActionEnum::*;
+    vec![
+        (Authorize, json!({ "idTag": "B4F62CEF" })),
+        (
+            BootNotification,
+            json!({ "chargePointVendor": "VendorX", "chargePointModel": "SingleSocketCharger" }),
+        ),
+        (
+            ChangeAvailability,
+            json!({ "connectorId": 1, "type": "Inoperative" }),
+        ),
+        (
+            ChangeConfiguration,
+            json!({ "key": "HeartbeatInterval", "value": "300" }),
+        ),
+        (ClearCache, json!({})),
+        (DataTransfer, json!({ "vendorId": "com.vendorx" })),
+        (GetConfiguration, json!({ "key": ["HeartbeatInterval"] })),
+        (Heartbeat, json!({})),
+        (
+            MeterValues,
+            json!({
+                "connectorId": 1,
+                "meterValue": [{
+                    "timestamp": "2024-01-01T10:00:00Z",
+                    "sampledValue": [{ "value": "1500" }],
+                }],
+            }),
+        ),
+        (RemoteStartTransaction, json!({ "idTag": "B4F62CEF" })),
+        (RemoteStopTransaction, json!({ "transactionId": 1 })),
+        (Reset, json!({ "type": "Soft" })),
+        (
+            StatusNotification,
+            json!({ "connectorId": 1, "errorCode": "NoError", "status": "Available" }),
+        ),
+        (
+            StartTransaction,
+            json!({
+                "connectorId": 1,
+                "idTag": "B4F62CEF",
+                "meterStart": 0,
+                "timestamp": "2024-01-01T10:00:00Z",
+            }),
+        ),
+        (
+            StopTransaction,
+            json!({ "transactionId": 1, "meterStop": 2050, "timestamp": "2024-01-01T11:00:00Z" }),
+        ),
+        (UnlockConnector, json!({ "connectorId": 1 })),
+        (ClearChargingProfile, json!({ "id": 1 })),
+        (GetLocalListVersion, json!({})),
+        (
+            SendLocalList,
+            json!({ "listVersion": 1, "updateType": "Full" }),
+        ),
+        (
+            GetDiagnostics,
+            json!({ "location": "ftp://diagnostics.example.com/" }),
+        ),
+        (
+            UpdateFirmware,
+            json!({
+                "location": "https://firmware.example.com/1.2.4.bin",
+                "retrieveDate": "2024-01-01T10:00:00Z",
+            }),
+        ),
+        (
+            SignCertificate,
+            json!({ "csr": "-----BEGIN CERTIFICATE REQUEST-----" }),
+        ),
+        (
+            CertificateSigned,
+            json!({ "certificateChain": "-----BEGIN CERTIFICATE-----" }),
+        ),
+    ]
+}
+
+#[test]
+fn payload_of_the_action() {
+    for (action, payload) in requests() {
+        let parsed = OcppPayload::deserialize_request(&action, payload)
+            .unwrap_or_else(|err| panic!("Failed to parse {action:?} payload: {err}"));
+        assert!(
+            action_matches_payload(&action, &parsed),
+            "{action:?} payload parsed as {parsed:?}"
+        );
+    }
+}
+
+#[test]
+fn payload_of_another_action() {
+    let requests = requests();
+    for (payload_action, payload) in &requests {
+        let parsed = OcppPayload::deserialize_request(payload_action, payload.clone()).unwrap();
+        for (action, _) in requests
+            .iter()
+            .filter(|(action, _)| action != payload_action)
+        {
+            assert!(
+                !action_matches_payload(action, &parsed),
+                "{action:?} Call accepted a {payload_action:?} payload"
+            );
+        }
+    }
+}
@@ -0,0 +1,57 @@
+use axum::{
+    extract::Request,
+    http::{header, HeaderValue},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::TypedHeader;
+use dotenvy_macro::dotenv;
+use headers::{authorization::Bearer, Authorization};
+use subtle::ConstantTimeEq;
+use tracing::warn;
+
+use crate::api::ApiError;
+
+/// Token of the administrators of the REST API. The admin routes are refused to everyone while it
+/// is empty
+const ADMIN_API_TOKEN: &str = dotenv!("ADMIN_API_TOKEN");
+
+fn unauthorized(detail: &str) -> Response {
+    let mut response = ApiError::Unauthorized(detail.to_string()).into_response();
+    response
+        .headers_mut()
+        .insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+    response
+}
+
+/// Bearer token authentication of the admin routes of the REST API, those that change the
+/// credentials of the chargers or the server itself. Runs as a route layer, so a rejected request
+/// never reaches the handler
+pub async fn require_admin_token(
+    authorization: Option<TypedHeader<Authorization<Bearer>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if ADMIN_API_TOKEN.is_empty() {
+        return ApiError::Forbidden(
+            "Admin routes are disabled, ADMIN_API_TOKEN is not set".to_string(),
+        )
+        .into_response();
+    }
+    let Some(TypedHeader(Authorization(bearer))) = authorization else {
+        return unauthorized("Admin routes require a Bearer token");
+    };
+    if !bool::from(
+        bearer
+            .token()
+            .as_bytes()
+            .ct_eq(ADMIN_API_TOKEN.as_bytes()),
+    ) {
+        warn!(
+            "Rejected admin request to {}: wrong token",
+            request.uri().path()
+        );
+        return unauthorized("Wrong admin token");
+    }
+    next.run(request).await
+}
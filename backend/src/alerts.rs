@@ -0,0 +1,364 @@
+use std::{
+    collections::HashMap,
+    net::IpAddr,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, Utc};
+use rust_ocpp::v1_6::types::AuthorizationStatus;
+use tracing::{error, info, warn, Instrument, Span};
+
+use crate::{connectors::ConnectorId, db, StationId};
+
+/// Time given to a webhook to answer
+const WEBHOOK_TIMEOUT: Duration = Duration::from_secs(10);
+
+static WEBHOOK_CLIENT: LazyLock<reqwest::Client> = LazyLock::new(|| {
+    reqwest::Client::builder()
+        .timeout(WEBHOOK_TIMEOUT)
+        // A redirect could lead to a host `validate_webhook_url` would reject
+        .redirect(reqwest::redirect::Policy::none())
+        .build()
+        .expect("Failed to build the webhook client")
+});
+
+/// Authorizations rejected in a row per charger, reset by an accepted one
+static CONSECUTIVE_REJECTIONS: LazyLock<Mutex<HashMap<StationId, u32>>> =
+    LazyLock::new(Default::default);
+
+/// OCPP event an alert rule can fire on, sent along with the webhook
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum AlertEvent {
+    ConnectorFaulted {
+        connector_id: ConnectorId,
+        error_code: String,
+        vendor_error_code: Option<String>,
+        info: Option<String>,
+        faulted_at: DateTime<Utc>,
+        /// Open fault of the connector, `None` when it could not be stored
+        fault_id: Option<i64>,
+    },
+    TransactionStopped {
+        transaction_id: i32,
+        duration_secs: i64,
+    },
+    ChargerDisconnected,
+    AuthorizationRejected {
+        consecutive_rejections: u32,
+    },
+    SessionOverlap {
+        connector_id: i32,
+        transaction_id: i32,
+        overlapping_transaction_id: i32,
+    },
+}
+
+/// Condition of a rule type. A new rule type only needs an evaluator, registered in `evaluator`
+pub trait AlertEvaluator: Sync {
+    /// Whether the event fires a rule of this type with the given threshold
+    fn matches(&self, event: &AlertEvent, threshold_value: Option<f64>) -> bool;
+}
+
+struct ChargerFaulted;
+
+impl AlertEvaluator for ChargerFaulted {
+    fn matches(&self, event: &AlertEvent, _: Option<f64>) -> bool {
+        matches!(event, AlertEvent::ConnectorFaulted { .. })
+    }
+}
+
+/// Fires when a transaction stops after more than the threshold, in hours
+struct SessionTooLong;
+
+impl AlertEvaluator for SessionTooLong {
+    fn matches(&self, event: &AlertEvent, threshold_value: Option<f64>) -> bool {
+        match (event, threshold_value) {
+            (AlertEvent::TransactionStopped { duration_secs, .. }, Some(max_hours)) => {
+                *duration_secs as f64 > max_hours * 3600.0
+            },
+            _ => false,
+        }
+    }
+}
+
+struct ChargerOffline;
+
+impl AlertEvaluator for ChargerOffline {
+    fn matches(&self, event: &AlertEvent, _: Option<f64>) -> bool {
+        matches!(event, AlertEvent::ChargerDisconnected)
+    }
+}
+
+/// Fires once per streak, when the rejections in a row reach the threshold
+struct AuthFailureThreshold;
+
+impl AlertEvaluator for AuthFailureThreshold {
+    fn matches(&self, event: &AlertEvent, threshold_value: Option<f64>) -> bool {
+        match (event, threshold_value) {
+            (AlertEvent::AuthorizationRejected { consecutive_rejections }, Some(threshold)) => {
+                *consecutive_rejections == threshold.ceil() as u32
+            },
+            _ => false,
+        }
+    }
+}
+
+struct SessionOverlap;
+
+impl AlertEvaluator for SessionOverlap {
+    fn matches(&self, event: &AlertEvent, _: Option<f64>) -> bool {
+        matches!(event, AlertEvent::SessionOverlap { .. })
+    }
+}
+
+/// Evaluator of the rule type, `None` for an unknown one
+pub fn evaluator(rule_type: &str) -> Option<&'static dyn AlertEvaluator> {
+    match rule_type {
+        "charger_faulted" => Some(&ChargerFaulted),
+        "session_too_long" => Some(&SessionTooLong),
+        "charger_offline" => Some(&ChargerOffline),
+        "auth_failure_threshold" => Some(&AuthFailureThreshold),
+        "session_overlap" => Some(&SessionOverlap),
+        _ => None,
+    }
+}
+
+/// Whether the rule type needs a threshold
+pub fn needs_threshold(rule_type: &str) -> bool {
+    matches!(rule_type, "session_too_long" | "auth_failure_threshold")
+}
+
+/// Count the authorizations rejected in a row by the charger, every rejection is an event for the
+/// alert rules
+pub fn record_authorization(station_id: &StationId, status: &AuthorizationStatus) {
+    let mut rejections = CONSECUTIVE_REJECTIONS.lock().unwrap();
+    if *status == AuthorizationStatus::Accepted {
+        rejections.remove(station_id);
+        return;
+    }
+    let consecutive_rejections = rejections
+        .entry(station_id.clone())
+        .or_default();
+    *consecutive_rejections += 1;
+    let event = AlertEvent::AuthorizationRejected {
+        consecutive_rejections: *consecutive_rejections,
+    };
+    drop(rejections);
+    fire(station_id, event);
+}
+
+/// Check the webhook URL is an HTTP(S) URL of a public host, so that alert rules cannot make the
+/// server call its own network, e.g. the metadata endpoint of the cloud provider. A host name is
+/// resolved, and rejected when any of its addresses is loopback, link-local or private
+pub async fn validate_webhook_url(webhook_url: &str) -> Result<(), String> {
+    let url = reqwest::Url::parse(webhook_url)
+        .ok()
+        .filter(|url| matches!(url.scheme(), "http" | "https"))
+        .ok_or("webhook_url must be an HTTP(S) URL")?;
+    let host = url
+        .host_str()
+        .ok_or("webhook_url must have a host")?;
+    // IPv6 addresses are between brackets in URLs
+    let addresses = match host
+        .trim_start_matches('[')
+        .trim_end_matches(']')
+        .parse::<IpAddr>()
+    {
+        Ok(ip) => vec![ip],
+        Err(_) => {
+            let port = url
+                .port_or_known_default()
+                .unwrap_or(80);
+            tokio::net::lookup_host((host, port))
+                .await
+                .map_err(|err| format!("Host {host} of webhook_url could not be resolved: {err}"))?
+                .map(|address| address.ip())
+                .collect()
+        },
+    };
+    match addresses
+        .into_iter()
+        .find(|ip| !is_public(*ip))
+    {
+        Some(ip) => Err(format!(
+            "webhook_url must not point to the private address {ip}"
+        )),
+        None => Ok(()),
+    }
+}
+
+fn is_public(ip: IpAddr) -> bool {
+    match ip {
+        IpAddr::V4(ip) => {
+            !(ip.is_loopback()
+                || ip.is_private()
+                || ip.is_link_local()
+                || ip.is_unspecified()
+                || ip.is_broadcast())
+        },
+        IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+            Some(ip) => is_public(IpAddr::V4(ip)),
+            None => {
+                !(ip.is_loopback()
+                    || ip.is_unique_local()
+                    || ip.is_unicast_link_local()
+                    || ip.is_unspecified())
+            },
+        },
+    }
+}
+
+/// Evaluate the enabled rules against the event and call the webhooks of the matching ones, in the
+/// background so that the OCPP handlers do not wait for them
+pub fn fire(station_id: &StationId, event: AlertEvent) {
+    let station_id = station_id.clone();
+    tokio::spawn(
+        async move {
+            let rules = {
+                let Some(_permit) = db::ocpp_permit("evaluating the alert rules").await else {
+                    return;
+                };
+                match db::enabled_alert_rules().await {
+                    Ok(rules) => rules,
+                    Err(err) => {
+                        error!("Failed to load the alert rules: {err:?}");
+                        return;
+                    },
+                }
+            };
+            for rule in rules {
+                if evaluator(&rule.rule_type)
+                    .is_some_and(|evaluator| evaluator.matches(&event, rule.threshold_value))
+                {
+                    notify(&rule, &station_id, &event).await;
+                }
+            }
+        }
+        .instrument(Span::current()),
+    );
+}
+
+async fn notify(rule: &db::AlertRule, station_id: &StationId, event: &AlertEvent) {
+    let rule_id = rule.id;
+    let event = serde_json::to_value(event).unwrap();
+    let alert_event = {
+        let Some(_permit) = db::ocpp_permit("storing the alert").await else {
+            return;
+        };
+        match db::insert_alert_event(rule, station_id, &event).await {
+            Ok(alert_event) => alert_event,
+            Err(err) => {
+                error!("Failed to store alert of rule {rule_id}: {err:?}");
+                return;
+            },
+        }
+    };
+    info!(
+        "Alert rule {rule_id} ({}) fired on {station_id}",
+        rule.rule_type
+    );
+    // Checked again, the host may resolve to another address than when the rule was created
+    if let Err(err) = validate_webhook_url(&rule.webhook_url).await {
+        warn!("Did not call the webhook of alert rule {rule_id}: {err}");
+        store_delivery(alert_event.id, None, Some(&err)).await;
+        return;
+    }
+    let (webhook_status, webhook_error) = match WEBHOOK_CLIENT
+        .post(&rule.webhook_url)
+        .json(&alert_event)
+        .send()
+        .await
+    {
+        Ok(response) => {
+            if !response.status().is_success() {
+                warn!(
+                    "Webhook of alert rule {rule_id} answered {}",
+                    response.status()
+                );
+            }
+            (Some(i32::from(response.status().as_u16())), None)
+        },
+        Err(err) => {
+            warn!("Failed to call the webhook of alert rule {rule_id}: {err}");
+            (None, Some(err.to_string()))
+        },
+    };
+    store_delivery(alert_event.id, webhook_status, webhook_error.as_deref()).await;
+}
+
+async fn store_delivery(
+    alert_event_id: i64,
+    webhook_status: Option<i32>,
+    webhook_error: Option<&str>,
+) {
+    let Some(_permit) = db::ocpp_permit("storing the alert delivery").await else {
+        return;
+    };
+    if let Err(err) =
+        db::update_alert_event_delivery(alert_event_id, webhook_status, webhook_error).await
+    {
+        error!("Failed to store delivery of alert {alert_event_id}: {err:?}");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn assert_rejected(webhook_urls: &[&str]) {
+        for webhook_url in webhook_urls {
+            let result = validate_webhook_url(webhook_url).await;
+            assert!(result.is_err(), "{webhook_url}");
+        }
+    }
+
+    #[tokio::test]
+    async fn public_address() {
+        let result = validate_webhook_url("https://93.184.216.34/hook").await;
+        assert_eq!(result, Ok(()));
+    }
+
+    #[tokio::test]
+    async fn scheme_other_than_http() {
+        assert_rejected(&[
+            "file:///etc/passwd",
+            "ftp://93.184.216.34/hook",
+            "not a url",
+        ])
+        .await;
+    }
+
+    #[tokio::test]
+    async fn loopback_address() {
+        assert_rejected(&[
+            "http://127.0.0.1:8080/hook",
+            "http://localhost/hook",
+            "http://[::1]/hook",
+            "http://[::ffff:127.0.0.1]/hook",
+            "http://0.0.0.0/hook",
+        ])
+        .await;
+    }
+
+    #[tokio::test]
+    async fn link_local_address() {
+        assert_rejected(&[
+            "http://169.254.169.254/latest/meta-data",
+            "http://[fe80::1]/hook",
+        ])
+        .await;
+    }
+
+    #[tokio::test]
+    async fn private_address() {
+        assert_rejected(&[
+            "http://10.0.0.5/hook",
+            "http://172.16.0.1/hook",
+            "https://192.168.1.1/hook",
+            "http://[fd00::1]/hook",
+        ])
+        .await;
+    }
+}
@@ -0,0 +1,1757 @@
+use axum::{
+    extract::{
+        rejection::{JsonRejection, PathRejection, QueryRejection},
+        FromRequest, FromRequestParts, Request,
+    },
+    http::{header, HeaderValue, Method, StatusCode},
+    middleware::{self, Next},
+    response::{IntoResponse, Response},
+    routing::{delete, get, post, put},
+    Json, Router,
+};
+use chrono::{DateTime, DurationRound, SecondsFormat, TimeDelta, Utc};
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::{
+    messages::{
+        change_configuration::{ChangeConfigurationRequest, ChangeConfigurationResponse},
+        clear_charging_profile::{ClearChargingProfileRequest, ClearChargingProfileResponse},
+        data_transfer::{DataTransferRequest, DataTransferResponse},
+        get_diagnostics::{GetDiagnosticsRequest, GetDiagnosticsResponse},
+        remote_start_transaction::{RemoteStartTransactionRequest, RemoteStartTransactionResponse},
+        remote_stop_transaction::{RemoteStopTransactionRequest, RemoteStopTransactionResponse},
+        update_firmware::UpdateFirmwareRequest,
+    },
+    types::{
+        AvailabilityStatus, AvailabilityType, ChargingProfile, ChargingProfilePurposeType, KeyValue,
+    },
+};
+use tower_http::{limit::RequestBodyLimitLayer, set_header::SetResponseHeaderLayer};
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{
+    admin_auth, alerts, availability_overrides,
+    call_direction::{self, MessageSender},
+    capabilities, charger_auth,
+    charger_groups::{self, GroupAction, GroupJob},
+    client_ip::ClientIp,
+    commands::{self, OcppError},
+    configuration,
+    connectors::{self, ConnectorId, CHARGE_POINT_CONNECTOR_ID},
+    dashboard, db,
+    debug_mode::{self, DebugMode, DebugModeError},
+    diagnostics, feature_profiles, firmware, live_power, log_level, maintenance, meter_stats,
+    rate_limit, remote_start, remote_stop, server_configuration, stop_reasons, transactions,
+    uptime, OcppActionEnum, StationId,
+};
+
+/// REST API consumed by the management UI, nested under `/api`
+pub fn router() -> Router {
+    // Routes that change the credentials of the chargers or the server itself, make it call other
+    // hosts, or expose the IPs of its clients
+    let admin_router = Router::new()
+        .route(
+            "/chargers/:station_id/auth-key",
+            put(set_auth_key).delete(delete_auth_key),
+        )
+        .route("/chargers/:station_id/message", post(inject_message))
+        .route("/admin/log-level", get(log_level).put(set_log_level))
+        .route("/admin/blocked-ips", get(blocked_ips))
+        .route("/alert-rules", get(alert_rules).post(create_alert_rule))
+        .route("/alert-rules/:rule_id", delete(delete_alert_rule))
+        .route_layer(middleware::from_fn(admin_auth::require_admin_token));
+    let router = Router::new()
+        .route("/chargers", get(chargers))
+        .route("/chargers/offline", get(offline_chargers))
+        .route("/chargers/:station_id", get(charger))
+        .route("/chargers/:station_id/timezone", put(set_charger_timezone))
+        .route(
+            "/chargers/:station_id/session-overlap-log",
+            get(session_overlap_log),
+        )
+        .route("/chargers/:station_id/uptime", get(charger_uptime))
+        .route("/chargers/:station_id/faults", get(connector_faults))
+        .route(
+            "/chargers/:station_id/faults/:fault_id/resolve",
+            post(resolve_connector_fault),
+        )
+        .route(
+            "/chargers/:station_id/maintenance",
+            post(start_maintenance).delete(end_maintenance),
+        )
+        .route(
+            "/chargers/:station_id/debug-mode",
+            post(start_debug_mode).delete(end_debug_mode),
+        )
+        .route(
+            "/chargers/:station_id/firmware-history",
+            get(firmware_history),
+        )
+        .route(
+            "/chargers/:station_id/configuration",
+            get(charger_configuration),
+        )
+        .route(
+            "/chargers/:station_id/configuration/:key",
+            get(charger_configuration_key).put(change_charger_configuration),
+        )
+        .route("/chargers/:station_id/connectors", get(connectors))
+        .route(
+            "/chargers/:station_id/connectors/:connector_id/history",
+            get(connector_history),
+        )
+        .route(
+            "/chargers/:station_id/connectors/:connector_id/active-transaction",
+            get(active_transaction),
+        )
+        .route(
+            "/chargers/:station_id/connectors/:connector_id/last-transaction",
+            get(last_transaction),
+        )
+        .route(
+            "/chargers/:station_id/connectors/:connector_id/real-time-power",
+            get(real_time_power),
+        )
+        .route(
+            "/chargers/:station_id/connectors/:connector_id/availability-override",
+            post(availability_override),
+        )
+        .route("/chargers/:station_id/remote-start", post(remote_start))
+        .route("/chargers/:station_id/remote-stop", post(remote_stop))
+        .route("/chargers/:station_id/data-transfer", post(data_transfer))
+        .route(
+            "/chargers/:station_id/diagnostics",
+            get(diagnostic_events).post(get_diagnostics),
+        )
+        .route(
+            "/chargers/:station_id/charging-profiles",
+            delete(clear_charging_profiles),
+        )
+        .route("/alert-events", get(alert_events))
+        .route("/dashboard/summary", get(dashboard_summary))
+        .route("/server/configuration", get(server_configuration))
+        .route("/server/capabilities", get(server_capabilities))
+        .route("/stats/top-chargers", get(top_chargers))
+        .route("/groups", get(charger_groups).post(create_charger_group))
+        .route("/groups/:group_id", delete(delete_charger_group))
+        .route("/groups/:group_id/chargers", get(charger_group_chargers))
+        .route(
+            "/groups/:group_id/chargers/:station_id",
+            put(add_charger_to_group).delete(remove_charger_from_group),
+        )
+        .route(
+            "/groups/:group_id/groups/:subgroup_id",
+            put(add_subgroup).delete(remove_subgroup),
+        )
+        .route(
+            "/groups/:group_id/configuration",
+            get(group_configuration).post(change_group_configuration),
+        )
+        .route(
+            "/groups/:group_id/actions/:action_type",
+            post(charger_group_action),
+        )
+        .route("/group-jobs/:job_id", get(charger_group_job))
+        .route("/tariffs", get(tariffs).post(create_tariff))
+        .route("/users", get(users).post(create_user))
+        .route("/users/:user_id", delete(delete_user))
+        .route("/reports/stop-reasons", get(stop_reasons))
+        .route("/reports/utilization", get(utilization))
+        .route("/reports/v2g-sessions", get(v2g_sessions))
+        .route("/sessions/stale", get(stale_sessions))
+        .route("/transactions/export", get(export_transactions))
+        .route("/transactions/:transaction_id", get(session_summary))
+        .route(
+            "/transactions/:transaction_id/meter-stats",
+            get(meter_stats),
+        )
+        .merge(admin_router)
+        .layer(middleware::from_fn(require_json_body))
+        // A huge body would be buffered whole by the JSON extractor
+        .layer(RequestBodyLimitLayer::new(max_body_size_bytes()))
+        // Security headers for browsers accessing the API directly. Kept off the WebSocket router
+        // so they do not interfere with the upgrade handshake
+        .layer(SetResponseHeaderLayer::overriding(
+            header::X_CONTENT_TYPE_OPTIONS,
+            HeaderValue::from_static("nosniff"),
+        ))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::X_FRAME_OPTIONS,
+            HeaderValue::from_static("DENY"),
+        ))
+        .layer(SetResponseHeaderLayer::overriding(
+            header::CONTENT_SECURITY_POLICY,
+            HeaderValue::from_static("default-src 'none'"),
+        ));
+    // TLS is terminated in front of the server, HSTS only makes sense when it is
+    const TLS_ENABLED: &str = dotenv!("TLS_ENABLED");
+    if TLS_ENABLED == "true" {
+        router.layer(SetResponseHeaderLayer::overriding(
+            header::STRICT_TRANSPORT_SECURITY,
+            HeaderValue::from_static("max-age=31536000; includeSubDomains"),
+        ))
+    } else {
+        router
+    }
+}
+
+/// Error body of the REST API: `{"error": "<code>", "detail": "<description>"}`
+#[derive(Debug)]
+pub enum ApiError {
+    BadRequest(String),
+    /// Request body, path or query string that could not be parsed
+    InvalidInput(String),
+    Conflict(String),
+    /// Missing or wrong admin token
+    Unauthorized(String),
+    Forbidden(String),
+    NotFound(String),
+    /// Request body that parses but is not valid
+    Unprocessable(String),
+    Database(sqlx::Error),
+    Ocpp(OcppError),
+    Unavailable(String),
+    /// Request body that is not JSON
+    UnsupportedMediaType(String),
+    /// Request body longer than `MAX_BODY_SIZE_BYTES`
+    PayloadTooLarge(String),
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let (status, error, detail) = match self {
+            ApiError::BadRequest(detail) => (StatusCode::BAD_REQUEST, "bad_request", detail),
+            ApiError::InvalidInput(detail) => (StatusCode::BAD_REQUEST, "invalid_input", detail),
+            ApiError::Conflict(detail) => (StatusCode::CONFLICT, "conflict", detail),
+            ApiError::Unauthorized(detail) => (StatusCode::UNAUTHORIZED, "unauthorized", detail),
+            ApiError::Forbidden(detail) => (StatusCode::FORBIDDEN, "forbidden", detail),
+            ApiError::NotFound(detail) => (StatusCode::NOT_FOUND, "not_found", detail),
+            ApiError::Unprocessable(detail) => {
+                (StatusCode::UNPROCESSABLE_ENTITY, "unprocessable", detail)
+            },
+            ApiError::Database(err) => {
+                error!("Database error: {err:?}");
+                (
+                    StatusCode::INTERNAL_SERVER_ERROR,
+                    "database_error",
+                    "Failed to query the database".to_string(),
+                )
+            },
+            ApiError::Ocpp(err) => {
+                let (status, error) = match err {
+                    OcppError::NotConnected => (StatusCode::NOT_FOUND, "charger_not_connected"),
+                    OcppError::UnsupportedVersion(_) => {
+                        (StatusCode::NOT_IMPLEMENTED, "charger_version_unsupported")
+                    },
+                    OcppError::Timeout => (StatusCode::GATEWAY_TIMEOUT, "charger_timeout"),
+                    OcppError::CallError { .. } | OcppError::InvalidResponse(_) => {
+                        (StatusCode::BAD_GATEWAY, "charger_error")
+                    },
+                };
+                (status, error, err.to_string())
+            },
+            ApiError::Unavailable(detail) => {
+                (StatusCode::SERVICE_UNAVAILABLE, "unavailable", detail)
+            },
+            ApiError::UnsupportedMediaType(detail) => (
+                StatusCode::UNSUPPORTED_MEDIA_TYPE,
+                "unsupported_media_type",
+                detail,
+            ),
+            ApiError::PayloadTooLarge(detail) => {
+                (StatusCode::PAYLOAD_TOO_LARGE, "payload_too_large", detail)
+            },
+        };
+        (
+            status,
+            Json(serde_json::json!({ "error": error, "detail": detail })),
+        )
+            .into_response()
+    }
+}
+
+impl From<sqlx::Error> for ApiError {
+    fn from(err: sqlx::Error) -> Self { ApiError::Database(err) }
+}
+
+impl From<OcppError> for ApiError {
+    fn from(err: OcppError) -> Self { ApiError::Ocpp(err) }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        // A chunked body has no Content-Length to be rejected on before it is read
+        if rejection.status() == StatusCode::PAYLOAD_TOO_LARGE {
+            return ApiError::PayloadTooLarge(format!(
+                "Request body must be at most {} bytes",
+                max_body_size_bytes()
+            ));
+        }
+        ApiError::InvalidInput(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self { ApiError::InvalidInput(rejection.body_text()) }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self { ApiError::InvalidInput(rejection.body_text()) }
+}
+
+fn max_body_size_bytes() -> usize {
+    const MAX_BODY_SIZE_BYTES: &str = dotenv!("MAX_BODY_SIZE_BYTES");
+    MAX_BODY_SIZE_BYTES
+        .parse()
+        .expect("MAX_BODY_SIZE_BYTES must be a number of bytes")
+}
+
+/// Reject the POST, PUT and PATCH requests with a body that is not `application/json` with a 415,
+/// before the extractors fail with a less helpful error. Requests without a body, e.g. starting a
+/// maintenance, need no Content-Type
+async fn require_json_body(request: Request, next: Next) -> Response {
+    let has_body = request
+        .headers()
+        .get(header::CONTENT_LENGTH)
+        .is_some_and(|content_length| content_length != "0")
+        || request
+            .headers()
+            .contains_key(header::TRANSFER_ENCODING);
+    if !matches!(
+        *request.method(),
+        Method::POST | Method::PUT | Method::PATCH
+    ) || !has_body
+    {
+        return next.run(request).await;
+    }
+    let content_type = request
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|content_type| content_type.to_str().ok());
+    // Parameters like `charset=utf-8` are allowed
+    let is_json = content_type.is_some_and(|content_type| {
+        content_type
+            .split(';')
+            .next()
+            .is_some_and(|media_type| {
+                media_type
+                    .trim()
+                    .eq_ignore_ascii_case("application/json")
+            })
+    });
+    if is_json {
+        return next.run(request).await;
+    }
+    let detail = match content_type {
+        Some(content_type) => format!("Content-Type must be application/json, not {content_type}"),
+        None => "Content-Type application/json is missing".to_string(),
+    };
+    ApiError::UnsupportedMediaType(detail).into_response()
+}
+
+/// Extractors replying to bad input with an [`ApiError`] instead of the plain text rejections of
+/// axum
+#[derive(FromRequest)]
+#[from_request(via(Json), rejection(ApiError))]
+struct ApiJson<T>(T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Path), rejection(ApiError))]
+struct ApiPath<T>(T);
+
+#[derive(FromRequestParts)]
+#[from_request(via(axum::extract::Query), rejection(ApiError))]
+struct ApiQuery<T>(T);
+
+#[derive(Debug, serde::Deserialize)]
+struct ChargersQuery {
+    /// Only the chargers with a firmware newer than this version
+    firmware_version_gt: Option<String>,
+    /// Only the chargers with a firmware older than this version
+    firmware_version_lt: Option<String>,
+}
+
+/// Chargers, filtered on their normalized firmware version. Chargers whose firmware version could
+/// not be normalized never match a firmware filter
+async fn chargers(
+    ApiQuery(query): ApiQuery<ChargersQuery>,
+) -> Result<Json<Vec<db::Charger>>, ApiError> {
+    let parse_version = |version: Option<String>| {
+        version
+            .map(|version| {
+                firmware::normalize_firmware_version(&version).ok_or_else(|| {
+                    ApiError::BadRequest(format!("{version:?} is not a firmware version"))
+                })
+            })
+            .transpose()
+    };
+    let newer_than = parse_version(query.firmware_version_gt)?;
+    let older_than = parse_version(query.firmware_version_lt)?;
+    let mut chargers = db::chargers().await?;
+    if newer_than.is_some() || older_than.is_some() {
+        chargers.retain(|charger| {
+            let Some(version) = charger
+                .firmware_version_semver
+                .as_deref()
+                .and_then(|version| semver::Version::parse(version).ok())
+            else {
+                return false;
+            };
+            newer_than
+                .as_ref()
+                .is_none_or(|newer_than| version > *newer_than)
+                && older_than
+                    .as_ref()
+                    .is_none_or(|older_than| version < *older_than)
+        });
+    }
+    Ok(Json(chargers))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct OfflineChargersQuery {
+    /// Minutes since the charger was last seen, 5 by default
+    threshold_minutes: Option<u32>,
+}
+
+/// Chargers not seen for more than `threshold_minutes`, the longest offline first
+async fn offline_chargers(
+    ApiQuery(query): ApiQuery<OfflineChargersQuery>,
+) -> Result<Json<Vec<db::OfflineCharger>>, ApiError> {
+    let threshold = TimeDelta::minutes(
+        query
+            .threshold_minutes
+            .unwrap_or(5)
+            .into(),
+    );
+    let offline_chargers = db::offline_chargers(Utc::now() - threshold)
+        .await?
+        .into_iter()
+        // Connected again since, or still connected after a session was left open
+        .filter(|charger| !commands::is_connected(&charger.station_id))
+        .collect();
+    Ok(Json(offline_chargers))
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ChargerStatus {
+    #[serde(flatten)]
+    charger: db::Charger,
+    /// Until when the LogLevel of the charger is raised, see `debug_mode`
+    debug_mode: Option<DebugMode>,
+}
+
+async fn charger(ApiPath(station_id): ApiPath<StationId>) -> Result<Json<ChargerStatus>, ApiError> {
+    let charger = db::charger(&station_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("Charger {station_id} not found")))?;
+    Ok(Json(ChargerStatus {
+        charger,
+        debug_mode: debug_mode::debug_mode(&station_id),
+    }))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChargerTimezone {
+    /// IANA timezone, e.g. `America/Sao_Paulo`, `null` to send UTC times again
+    timezone: Option<String>,
+}
+
+/// Set the timezone in which the charger is sent the current time on boot
+async fn set_charger_timezone(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiJson(body): ApiJson<ChargerTimezone>,
+) -> Result<StatusCode, ApiError> {
+    if let Some(timezone) = &body.timezone
+        && timezone
+            .parse::<chrono_tz::Tz>()
+            .is_err()
+    {
+        return Err(ApiError::BadRequest(format!(
+            "{timezone:?} is not an IANA timezone"
+        )));
+    }
+    if db::set_charger_timezone(&station_id, body.timezone.as_deref()).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Charger {station_id} not found"
+        )))
+    }
+}
+
+/// Raise the LogLevel of the charger to Debug for `DEBUG_MODE_DURATION_SECS`, or extend its debug
+/// mode
+async fn start_debug_mode(
+    ApiPath(station_id): ApiPath<StationId>,
+) -> Result<Json<DebugMode>, ApiError> {
+    match debug_mode::start(&station_id).await {
+        Ok(debug_mode) => Ok(Json(debug_mode)),
+        Err(DebugModeError::Ocpp(err)) => Err(err.into()),
+        Err(err @ DebugModeError::UnknownLogLevel) => Err(ApiError::Unprocessable(err.to_string())),
+        Err(err @ DebugModeError::Refused(_)) => Err(ApiError::Conflict(err.to_string())),
+    }
+}
+
+/// Restore the LogLevel the charger had before its debug mode
+async fn end_debug_mode(ApiPath(station_id): ApiPath<StationId>) -> Result<StatusCode, ApiError> {
+    match debug_mode::stop(&station_id).await? {
+        Some(_) => Ok(StatusCode::NO_CONTENT),
+        None => Err(ApiError::NotFound(format!(
+            "Charger {station_id} is not in debug mode"
+        ))),
+    }
+}
+
+/// Reject new sessions on the charger, the running ones complete normally
+async fn start_maintenance(
+    ApiPath(station_id): ApiPath<StationId>,
+) -> Result<StatusCode, ApiError> {
+    set_maintenance_mode(station_id, true).await
+}
+
+async fn end_maintenance(ApiPath(station_id): ApiPath<StationId>) -> Result<StatusCode, ApiError> {
+    set_maintenance_mode(station_id, false).await
+}
+
+async fn set_maintenance_mode(
+    station_id: StationId,
+    maintenance_mode: bool,
+) -> Result<StatusCode, ApiError> {
+    if maintenance::set(&station_id, maintenance_mode).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Charger {station_id} not found"
+        )))
+    }
+}
+
+/// Overlapping transactions detected on the connectors of the charger, the most recent first
+async fn session_overlap_log(
+    ApiPath(station_id): ApiPath<StationId>,
+) -> Result<Json<Vec<db::BillingAnomaly>>, ApiError> {
+    Ok(Json(db::billing_anomalies(&station_id).await?))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct FaultsQuery {
+    /// Only the resolved faults, or only the open ones
+    resolved: Option<bool>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct ChargerFaults {
+    faults: Vec<db::ConnectorFault>,
+    /// Mean time from a fault of the charger to its resolution, over all its resolved faults.
+    /// `None` when none was resolved
+    mean_time_to_resolve_secs: Option<f64>,
+}
+
+/// Faults of the connectors of the charger, the most recent first
+async fn connector_faults(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiQuery(query): ApiQuery<FaultsQuery>,
+) -> Result<Json<ChargerFaults>, ApiError> {
+    Ok(Json(ChargerFaults {
+        faults: db::connector_faults(&station_id, query.resolved).await?,
+        mean_time_to_resolve_secs: db::mean_time_to_resolve_secs(&station_id).await?,
+    }))
+}
+
+/// Mark an open fault as resolved, after the maintenance of the connector
+async fn resolve_connector_fault(
+    ApiPath((station_id, fault_id)): ApiPath<(StationId, i64)>,
+) -> Result<Json<db::ConnectorFault>, ApiError> {
+    if let Some(fault) = db::resolve_connector_fault(&station_id, fault_id).await? {
+        return Ok(Json(fault));
+    }
+    match db::connector_fault(&station_id, fault_id).await? {
+        Some(fault) => Err(ApiError::Conflict(format!(
+            "Fault {fault_id} of {station_id} was already resolved at {}",
+            fault
+                .resolved_at
+                .map_or_else(String::new, |resolved_at| resolved_at.to_rfc3339())
+        ))),
+        None => Err(ApiError::NotFound(format!(
+            "Charger {station_id} has no fault {fault_id}"
+        ))),
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UptimeQuery {
+    /// `<n>d` or `<n>h`, 7 days by default
+    period: Option<String>,
+}
+
+/// Share of the period the charger was connected to the server, with its outages
+async fn charger_uptime(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiQuery(query): ApiQuery<UptimeQuery>,
+) -> Result<Json<uptime::Uptime>, ApiError> {
+    let period = query.period.as_deref().unwrap_or("7d");
+    let since = period_start(period).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "{period:?} is not a period, expected e.g. 7d or 24h"
+        ))
+    })?;
+    uptime::uptime(&station_id, since)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Charger {station_id} never connected")))
+}
+
+async fn firmware_history(
+    ApiPath(station_id): ApiPath<StationId>,
+) -> Result<Json<Vec<db::FirmwareChangeEvent>>, ApiError> {
+    Ok(Json(db::firmware_history(&station_id).await?))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct HistoryQuery {
+    limit: Option<i64>,
+}
+
+/// Status of the connectors of the charger, as last reported since the server started
+async fn connectors(
+    ApiPath(station_id): ApiPath<StationId>,
+) -> Json<Vec<connectors::ConnectorSummary>> {
+    Json(connectors::connectors(&station_id))
+}
+
+/// States of a connector reported by StatusNotifications, the most recent first
+async fn connector_history(
+    ApiPath((station_id, connector_id)): ApiPath<(StationId, i32)>,
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<Vec<db::StatusNotification>>, ApiError> {
+    let limit = query.limit.unwrap_or(100);
+    if !(1..=1000).contains(&limit) {
+        return Err(ApiError::BadRequest(
+            "limit must be between 1 and 1000".to_string(),
+        ));
+    }
+    Ok(Json(
+        db::status_notification_history(&station_id, connector_id, limit).await?,
+    ))
+}
+
+/// Most diagnostic events returned at once
+const DIAGNOSTIC_EVENTS_LIMIT: i64 = 200;
+
+#[derive(Debug, serde::Deserialize)]
+struct DiagnosticEventsQuery {
+    since: Option<DateTime<Utc>>,
+    /// `cursor` of the last event of the previous page
+    cursor: Option<String>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct DiagnosticEvent {
+    #[serde(flatten)]
+    event: db::DiagnosticEvent,
+    /// Passed back to get the events that follow
+    cursor: String,
+}
+
+/// Position of an event in the timeline, formatted as `timestamp,event_type,id`
+fn parse_diagnostic_cursor(cursor: &str) -> Option<(DateTime<Utc>, &str, i64)> {
+    let mut parts = cursor.splitn(3, ',');
+    let timestamp = parts.next()?.parse().ok()?;
+    let event_type = parts.next()?;
+    let id = parts.next()?.parse().ok()?;
+    Some((timestamp, event_type, id))
+}
+
+/// Timeline of the connector states, connections, firmware changes and rejected sampled values of
+/// the charger, the most recent first, by pages of 200 events
+async fn diagnostic_events(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiQuery(query): ApiQuery<DiagnosticEventsQuery>,
+) -> Result<Json<Vec<DiagnosticEvent>>, ApiError> {
+    let before = match query.cursor.as_deref() {
+        Some(cursor) => Some(
+            parse_diagnostic_cursor(cursor)
+                .ok_or_else(|| ApiError::BadRequest(format!("Invalid cursor {cursor:?}")))?,
+        ),
+        None => None,
+    };
+    let events =
+        db::diagnostic_events(&station_id, query.since, before, DIAGNOSTIC_EVENTS_LIMIT).await?;
+    Ok(Json(
+        events
+            .into_iter()
+            .map(|event| DiagnosticEvent {
+                cursor: format!(
+                    "{},{},{}",
+                    event
+                        .timestamp
+                        .to_rfc3339_opts(SecondsFormat::Micros, true),
+                    event.event_type,
+                    event.id
+                ),
+                event,
+            })
+            .collect(),
+    ))
+}
+
+#[derive(Debug, serde::Serialize)]
+struct Configuration {
+    keys: Vec<KeyValue>,
+}
+
+async fn charger_configuration(
+    ApiPath(station_id): ApiPath<StationId>,
+) -> Result<Json<Configuration>, ApiError> {
+    configuration::configuration(&station_id)
+        .await?
+        .map(|keys| Json(Configuration { keys }))
+        .ok_or_else(|| ApiError::NotFound(format!("No configuration of charger {station_id}")))
+}
+
+async fn charger_configuration_key(
+    ApiPath((station_id, key)): ApiPath<(StationId, String)>,
+) -> Result<Json<KeyValue>, ApiError> {
+    configuration::configuration(&station_id)
+        .await?
+        .and_then(|keys| {
+            keys.into_iter()
+                .find(|key_value| key_value.key == key)
+        })
+        .map(Json)
+        .ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "No configuration key {key} for charger {station_id}"
+            ))
+        })
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ConfigurationValue {
+    value: String,
+}
+
+/// Check a configuration key and its value fit OCPP 1.6, before they are sent to chargers
+fn validate_configuration(key: &str, value: &str) -> Result<(), ApiError> {
+    // CiString50 and CiString500 of OCPP 1.6
+    if key.is_empty() || key.len() > 50 {
+        return Err(ApiError::BadRequest(
+            "key must be between 1 and 50 characters".to_string(),
+        ));
+    }
+    if value.len() > 500 {
+        return Err(ApiError::BadRequest(
+            "value must be at most 500 characters".to_string(),
+        ));
+    }
+    configuration::validate(key, value).map_err(ApiError::Unprocessable)
+}
+
+/// Set a configuration key of the charger, see OCPP 1.6 ChangeConfiguration
+async fn change_charger_configuration(
+    ApiPath((station_id, key)): ApiPath<(StationId, String)>,
+    ApiJson(change): ApiJson<ConfigurationValue>,
+) -> Result<Json<ChangeConfigurationResponse>, ApiError> {
+    validate_configuration(&key, &change.value)?;
+    Ok(Json(
+        configuration::change(&station_id, &key, &change.value).await?,
+    ))
+}
+
+/// Transaction running on the connector, for dashboards polling the connectors
+async fn active_transaction(
+    ApiPath((station_id, connector_id)): ApiPath<(StationId, ConnectorId)>,
+) -> Json<serde_json::Value> {
+    match transactions::active_transaction(&station_id, connector_id) {
+        Some(transaction) => Json(serde_json::json!(transaction)),
+        None => Json(serde_json::json!({ "transaction": null })),
+    }
+}
+
+/// Power the connector last reported, from memory rather than from the stored meter readings
+async fn real_time_power(
+    ApiPath((station_id, connector_id)): ApiPath<(StationId, ConnectorId)>,
+) -> Result<Json<live_power::RealTimePower>, ApiError> {
+    live_power::real_time_power(&station_id, connector_id)
+        .map(Json)
+        .ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "No power reported by connector {connector_id} of {station_id}"
+            ))
+        })
+}
+
+/// Most recent transaction of the connector, running or not
+async fn last_transaction(
+    ApiPath((station_id, connector_id)): ApiPath<(StationId, ConnectorId)>,
+) -> Result<Json<db::SessionSummary>, ApiError> {
+    db::last_session_summary(&station_id, connector_id as i32)
+        .await?
+        .map(Json)
+        .ok_or_else(|| {
+            ApiError::NotFound(format!(
+                "No transaction on connector {connector_id} of {station_id}"
+            ))
+        })
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SetAvailabilityOverride {
+    /// `null` clears the override
+    availability_type: Option<AvailabilityType>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct AvailabilityOverride {
+    availability_override: Option<db::AvailabilityOverride>,
+    /// Status reported by the charger, `null` when it is not connected and gets the override at
+    /// its next boot, or when the override was cleared
+    status: Option<AvailabilityStatus>,
+}
+
+/// Set the availability of a connector for good, applied again whenever the charger reboots.
+/// Clearing the override leaves the connector in its current availability
+async fn availability_override(
+    ApiPath((station_id, connector_id)): ApiPath<(StationId, ConnectorId)>,
+    ApiJson(body): ApiJson<SetAvailabilityOverride>,
+) -> Result<Json<AvailabilityOverride>, ApiError> {
+    capabilities::require_connector(&station_id, connector_id).map_err(ApiError::Unprocessable)?;
+    let Some(availability) = body.availability_type else {
+        if !db::delete_availability_override(&station_id, connector_id as i32).await? {
+            return Err(ApiError::NotFound(format!(
+                "No availability override on connector {connector_id} of charger {station_id}"
+            )));
+        }
+        return Ok(Json(AvailabilityOverride {
+            availability_override: None,
+            status: None,
+        }));
+    };
+    let availability_override = db::upsert_availability_override(
+        &station_id,
+        connector_id as i32,
+        availability_overrides::availability_name(&availability),
+    )
+    .await?;
+    let status =
+        match availability_overrides::change_availability(&station_id, connector_id, availability)
+            .await
+        {
+            Ok(response) => Some(response.status),
+            Err(OcppError::NotConnected) => None,
+            Err(err) => return Err(err.into()),
+        };
+    Ok(Json(AvailabilityOverride {
+        availability_override: Some(availability_override),
+        status,
+    }))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct SetAuthKey {
+    auth_key: String,
+}
+
+/// Set the AuthorizationKey the charger authenticates with when `OCPP_BASIC_AUTH` is enabled. The
+/// charger has to be configured with the same key
+async fn set_auth_key(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiJson(body): ApiJson<SetAuthKey>,
+) -> Result<StatusCode, ApiError> {
+    // OCPP 1.6 security whitepaper: the AuthorizationKey is 16 to 40 characters
+    if !(16..=40).contains(&body.auth_key.chars().count()) {
+        return Err(ApiError::BadRequest(
+            "auth_key must be between 16 and 40 characters".to_string(),
+        ));
+    }
+    db::upsert_charger_credentials(&station_id, &charger_auth::credentials(&body.auth_key)).await?;
+    Ok(StatusCode::NO_CONTENT)
+}
+
+async fn delete_auth_key(ApiPath(station_id): ApiPath<StationId>) -> Result<StatusCode, ApiError> {
+    if db::delete_charger_credentials(&station_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Charger {station_id} has no AuthorizationKey"
+        )))
+    }
+}
+
+/// Ask the charger to start a transaction, see OCPP 1.6 RemoteStartTransaction. Rejected while
+/// another start on the same connector waits for the charger
+#[derive(Debug, serde::Deserialize)]
+struct RemoteStart {
+    connector_id: Option<ConnectorId>,
+    id_tag: String,
+    /// OCPP 1.6 ChargingProfile of the transaction, checked by [`tx_profile`]
+    charging_profile: Option<serde_json::Value>,
+}
+
+/// Check the charging profile of a remote start before it reaches the charger, which would only
+/// reject it. It must be a TxProfile with a non-negative stack level and a rate in W or A, and no
+/// transaction ID as the transaction does not exist yet
+fn tx_profile(charging_profile: serde_json::Value) -> Result<ChargingProfile, String> {
+    if !charging_profile["stackLevel"].is_u64() {
+        return Err("stackLevel must be a non-negative integer".to_string());
+    }
+    if charging_profile["chargingProfilePurpose"] != "TxProfile" {
+        return Err("chargingProfilePurpose must be TxProfile".to_string());
+    }
+    let charging_rate_unit = &charging_profile["chargingSchedule"]["chargingRateUnit"];
+    if charging_rate_unit != "W" && charging_rate_unit != "A" {
+        return Err("chargingSchedule.chargingRateUnit must be W or A".to_string());
+    }
+    if !charging_profile["transactionId"].is_null() {
+        return Err("transactionId must not be set on a remote start".to_string());
+    }
+    serde_json::from_value(charging_profile)
+        .map_err(|err| format!("Invalid charging profile: {err}"))
+}
+
+async fn remote_start(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiJson(remote_start): ApiJson<RemoteStart>,
+) -> Result<Json<RemoteStartTransactionResponse>, ApiError> {
+    let connector_id = remote_start
+        .connector_id
+        .unwrap_or(CHARGE_POINT_CONNECTOR_ID);
+    let charging_profile = remote_start
+        .charging_profile
+        .map(tx_profile)
+        .transpose()
+        .map_err(ApiError::Unprocessable)?;
+    if charging_profile.is_some() {
+        capabilities::require_smart_charging(&station_id).map_err(ApiError::Unprocessable)?;
+    }
+    capabilities::require_connector(&station_id, connector_id).map_err(ApiError::Unprocessable)?;
+    if maintenance::is_in_maintenance(&station_id) {
+        return Err(ApiError::Conflict(format!(
+            "Charger {station_id} is in maintenance"
+        )));
+    }
+    // Held until the charger answers or the call times out
+    let _pending = remote_start::acquire(&station_id, connector_id).ok_or_else(|| {
+        ApiError::Conflict(format!(
+            "A remote start is already pending on connector {connector_id} of charger {station_id}"
+        ))
+    })?;
+    let request = RemoteStartTransactionRequest {
+        connector_id: remote_start.connector_id,
+        id_tag: remote_start.id_tag,
+        charging_profile,
+    };
+    let response = commands::send_call(
+        &station_id,
+        OcppActionEnum::RemoteStartTransaction,
+        &request,
+    )
+    .await?;
+    Ok(Json(response))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RemoteStop {
+    transaction_id: i32,
+}
+
+/// Check the transaction can be stopped on the charger, from the charger it runs on and its status
+pub(crate) fn validate_remote_stop(
+    station_id: &StationId,
+    transaction_id: i32,
+    transaction_station_id: &str,
+    status: &str,
+) -> Result<(), ApiError> {
+    if transaction_station_id != station_id {
+        return Err(ApiError::Forbidden(format!(
+            "Transaction {transaction_id} belongs to another charger than {station_id}"
+        )));
+    }
+    if status != "active" {
+        return Err(ApiError::Conflict(format!(
+            "Transaction {transaction_id} is not active, it is {status}"
+        )));
+    }
+    Ok(())
+}
+
+/// Ask the charger to stop one of its active transactions. Replies with 202 and queues the request
+/// when the charger is offline, it is sent once the charger connects again
+async fn remote_stop(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiJson(remote_stop): ApiJson<RemoteStop>,
+) -> Result<Response, ApiError> {
+    let transaction_id = remote_stop.transaction_id;
+    // The running transactions are tracked in memory, the stopped ones and the ones started
+    // before the last restart of the server only in the database
+    let (transaction_station_id, status) = match transactions::station_of(transaction_id) {
+        Some(transaction_station_id) => (transaction_station_id, "active".to_string()),
+        None => {
+            let summary = db::session_summary(transaction_id)
+                .await?
+                .ok_or_else(|| {
+                    ApiError::NotFound(format!("Transaction {transaction_id} not found"))
+                })?;
+            (summary.station_id, summary.status)
+        },
+    };
+    validate_remote_stop(
+        &station_id,
+        transaction_id,
+        &transaction_station_id,
+        &status,
+    )?;
+    if !commands::is_connected(&station_id) {
+        remote_stop::queue(&station_id, transaction_id);
+        return Ok((
+            StatusCode::ACCEPTED,
+            Json(serde_json::json!({ "status": "Queued" })),
+        )
+            .into_response());
+    }
+    let request = RemoteStopTransactionRequest { transaction_id };
+    let response: RemoteStopTransactionResponse =
+        commands::send_call(&station_id, OcppActionEnum::RemoteStopTransaction, &request).await?;
+    Ok(Json(response).into_response())
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct DataTransfer {
+    vendor_id: String,
+    message_id: Option<String>,
+    data: Option<String>,
+}
+
+/// Send vendor-specific data to the charger, see OCPP 1.6 DataTransfer. This is how proprietary
+/// charger features are reached, e.g. custom messages on the charger screen
+async fn data_transfer(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiJson(data_transfer): ApiJson<DataTransfer>,
+) -> Result<Json<DataTransferResponse>, ApiError> {
+    // Field lengths of OCPP 1.6
+    if data_transfer.vendor_id.is_empty() || data_transfer.vendor_id.len() > 255 {
+        return Err(ApiError::BadRequest(
+            "vendor_id must be 1 to 255 characters long".to_string(),
+        ));
+    }
+    if data_transfer
+        .message_id
+        .as_ref()
+        .is_some_and(|message_id| message_id.len() > 50)
+    {
+        return Err(ApiError::BadRequest(
+            "message_id must be at most 50 characters long".to_string(),
+        ));
+    }
+    let request = DataTransferRequest {
+        vendor_string: data_transfer.vendor_id,
+        message_id: data_transfer.message_id,
+        data: data_transfer.data,
+    };
+    let response = commands::send_call(&station_id, OcppActionEnum::DataTransfer, &request).await?;
+    Ok(Json(response))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct GetDiagnostics {
+    /// URL the charger uploads the diagnostics file to
+    location: String,
+    retries: Option<i32>,
+    retry_interval: Option<i32>,
+    start_time: Option<DateTime<Utc>>,
+    stop_time: Option<DateTime<Utc>>,
+}
+
+/// Ask the charger to upload its diagnostics file, see OCPP 1.6 GetDiagnostics. The response holds
+/// the name of the file, absent when the charger has no diagnostics
+async fn get_diagnostics(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiJson(get_diagnostics): ApiJson<GetDiagnostics>,
+) -> Result<Json<GetDiagnosticsResponse>, ApiError> {
+    let request = GetDiagnosticsRequest {
+        location: get_diagnostics.location,
+        retries: get_diagnostics.retries,
+        retry_interval: get_diagnostics.retry_interval,
+        start_time: get_diagnostics.start_time,
+        stop_time: get_diagnostics.stop_time,
+    };
+    let response = diagnostics::get_diagnostics(&station_id, &request).await?;
+    Ok(Json(response))
+}
+
+/// Charging profiles to clear. Every given criterion must match, and every profile is cleared when
+/// none is given
+#[derive(Debug, serde::Deserialize)]
+struct ClearChargingProfiles {
+    charging_profile_id: Option<i32>,
+    connector_id: Option<i32>,
+    charging_profile_purpose: Option<ChargingProfilePurposeType>,
+    stack_level: Option<i32>,
+}
+
+/// Remove smart charging profiles from the charger, see OCPP 1.6 ClearChargingProfile
+async fn clear_charging_profiles(
+    ApiPath(station_id): ApiPath<StationId>,
+    ApiQuery(query): ApiQuery<ClearChargingProfiles>,
+) -> Result<Json<ClearChargingProfileResponse>, ApiError> {
+    capabilities::require_smart_charging(&station_id).map_err(ApiError::Unprocessable)?;
+    let request = ClearChargingProfileRequest {
+        id: query.charging_profile_id,
+        connector_id: query.connector_id,
+        charging_profile_purpose: query.charging_profile_purpose,
+        stack_level: query.stack_level,
+    };
+    let response =
+        commands::send_call(&station_id, OcppActionEnum::ClearChargingProfile, &request).await?;
+    Ok(Json(response))
+}
+
+async fn alert_rules() -> Result<Json<Vec<db::AlertRule>>, ApiError> {
+    Ok(Json(db::alert_rules().await?))
+}
+
+async fn create_alert_rule(
+    ApiJson(rule): ApiJson<db::NewAlertRule>,
+) -> Result<(StatusCode, Json<db::AlertRule>), ApiError> {
+    if alerts::evaluator(&rule.rule_type).is_none() {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown rule_type {:?}",
+            rule.rule_type
+        )));
+    }
+    match rule.threshold_value {
+        Some(threshold_value) if !threshold_value.is_finite() || threshold_value <= 0.0 => {
+            return Err(ApiError::BadRequest(
+                "threshold_value must be positive".to_string(),
+            ));
+        },
+        None if alerts::needs_threshold(&rule.rule_type) => {
+            return Err(ApiError::BadRequest(format!(
+                "{} rules need a threshold_value",
+                rule.rule_type
+            )));
+        },
+        _ => (),
+    }
+    alerts::validate_webhook_url(&rule.webhook_url)
+        .await
+        .map_err(ApiError::BadRequest)?;
+    Ok((
+        StatusCode::CREATED,
+        Json(db::insert_alert_rule(&rule).await?),
+    ))
+}
+
+async fn delete_alert_rule(ApiPath(rule_id): ApiPath<i32>) -> Result<StatusCode, ApiError> {
+    if db::delete_alert_rule(rule_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Alert rule {rule_id} not found"
+        )))
+    }
+}
+
+/// Alerts fired by the rules, the most recent first
+async fn alert_events(
+    ApiQuery(query): ApiQuery<HistoryQuery>,
+) -> Result<Json<Vec<db::AlertEvent>>, ApiError> {
+    let limit = query.limit.unwrap_or(100);
+    if !(1..=1000).contains(&limit) {
+        return Err(ApiError::BadRequest(
+            "limit must be between 1 and 1000".to_string(),
+        ));
+    }
+    Ok(Json(db::alert_events(limit).await?))
+}
+
+async fn charger_groups() -> Result<Json<Vec<db::ChargerGroup>>, ApiError> {
+    Ok(Json(db::charger_groups().await?))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct NewChargerGroup {
+    name: String,
+}
+
+async fn create_charger_group(
+    ApiJson(group): ApiJson<NewChargerGroup>,
+) -> Result<(StatusCode, Json<db::ChargerGroup>), ApiError> {
+    if group.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".to_string()));
+    }
+    match db::insert_charger_group(&group.name).await {
+        Ok(group) => Ok((StatusCode::CREATED, Json(group))),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Err(ApiError::Conflict(
+            "A group with this name already exists".to_string(),
+        )),
+        Err(err) => Err(err.into()),
+    }
+}
+
+fn charger_group_not_found(group_id: i32) -> ApiError {
+    ApiError::NotFound(format!("Charger group {group_id} not found"))
+}
+
+async fn delete_charger_group(ApiPath(group_id): ApiPath<i32>) -> Result<StatusCode, ApiError> {
+    if db::delete_charger_group(group_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(charger_group_not_found(group_id))
+    }
+}
+
+/// Chargers of the group, including those of its nested subgroups
+async fn charger_group_chargers(
+    ApiPath(group_id): ApiPath<i32>,
+) -> Result<Json<Vec<String>>, ApiError> {
+    db::charger_group_station_ids(group_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| charger_group_not_found(group_id))
+}
+
+async fn add_charger_to_group(
+    ApiPath((group_id, station_id)): ApiPath<(i32, StationId)>,
+) -> Result<StatusCode, ApiError> {
+    match db::add_charger_to_group(group_id, &station_id).await {
+        Ok(()) => Ok(StatusCode::NO_CONTENT),
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            Err(charger_group_not_found(group_id))
+        },
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn remove_charger_from_group(
+    ApiPath((group_id, station_id)): ApiPath<(i32, StationId)>,
+) -> Result<StatusCode, ApiError> {
+    if db::remove_charger_from_group(group_id, &station_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Charger {station_id} is not in group {group_id}"
+        )))
+    }
+}
+
+/// Nest a group in another, its chargers then receive the actions sent to the other
+async fn add_subgroup(
+    ApiPath((group_id, subgroup_id)): ApiPath<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    match db::add_subgroup(group_id, subgroup_id).await {
+        Ok(true) => Ok(StatusCode::NO_CONTENT),
+        Ok(false) => Err(ApiError::Conflict(format!(
+            "Group {group_id} would contain itself through group {subgroup_id}"
+        ))),
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            Err(ApiError::NotFound(format!(
+                "Charger group {group_id} or {subgroup_id} not found"
+            )))
+        },
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn remove_subgroup(
+    ApiPath((group_id, subgroup_id)): ApiPath<(i32, i32)>,
+) -> Result<StatusCode, ApiError> {
+    if db::remove_subgroup(group_id, subgroup_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Group {subgroup_id} is not nested in group {group_id}"
+        )))
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct ChangeConfigurationAction {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UpdateFirmwareAction {
+    location: String,
+    retrieve_date: DateTime<Utc>,
+    retries: Option<i32>,
+    retry_interval: Option<i32>,
+}
+
+/// Action of the given type, parameterized by the request body
+fn group_action(action_type: &str, body: serde_json::Value) -> Result<GroupAction, ApiError> {
+    fn parameters<T: serde::de::DeserializeOwned>(body: serde_json::Value) -> Result<T, ApiError> {
+        serde_json::from_value(body).map_err(|err| ApiError::InvalidInput(err.to_string()))
+    }
+    match action_type {
+        "clear-cache" => Ok(GroupAction::ClearCache),
+        "change-configuration" => {
+            let action: ChangeConfigurationAction = parameters(body)?;
+            validate_configuration(&action.key, &action.value)?;
+            Ok(GroupAction::ChangeConfiguration(
+                ChangeConfigurationRequest { key: action.key, value: action.value },
+            ))
+        },
+        "update-firmware" => {
+            let action: UpdateFirmwareAction = parameters(body)?;
+            Ok(GroupAction::UpdateFirmware(UpdateFirmwareRequest {
+                location: action.location,
+                retrieve_date: action.retrieve_date,
+                retries: action.retries,
+                retry_interval: action.retry_interval,
+            }))
+        },
+        _ => Err(ApiError::NotFound(format!(
+            "Unknown action type {action_type:?}, expected clear-cache, change-configuration or \
+             update-firmware"
+        ))),
+    }
+}
+
+/// Send an action to every charger of the group and its subgroups. Replies at once with the job
+/// to poll for the result of each charger. The body holds the parameters of the action, `{}` for a
+/// clear-cache
+async fn charger_group_action(
+    ApiPath((group_id, action_type)): ApiPath<(i32, String)>,
+    ApiJson(body): ApiJson<serde_json::Value>,
+) -> Result<(StatusCode, Json<GroupJob>), ApiError> {
+    let action = group_action(&action_type, body)?;
+    let station_ids = db::charger_group_station_ids(group_id)
+        .await?
+        .ok_or_else(|| charger_group_not_found(group_id))?;
+    let job = charger_groups::start(group_id, &action_type, action, station_ids);
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+/// Configuration pushed to the chargers of the group, again whenever one of them boots
+async fn group_configuration(
+    ApiPath(group_id): ApiPath<i32>,
+) -> Result<Json<Vec<db::GroupConfigurationKey>>, ApiError> {
+    if db::charger_group_station_ids(group_id)
+        .await?
+        .is_none()
+    {
+        return Err(charger_group_not_found(group_id));
+    }
+    Ok(Json(db::group_configuration(group_id).await?))
+}
+
+/// Store the configuration intended for the group and send it to its connected chargers. The
+/// others get it when they boot
+async fn change_group_configuration(
+    ApiPath(group_id): ApiPath<i32>,
+    ApiJson(change): ApiJson<ChangeConfigurationAction>,
+) -> Result<(StatusCode, Json<GroupJob>), ApiError> {
+    validate_configuration(&change.key, &change.value)?;
+    match db::upsert_group_configuration(group_id, &change.key, &change.value).await {
+        Ok(()) => (),
+        Err(sqlx::Error::Database(err)) if err.is_foreign_key_violation() => {
+            return Err(charger_group_not_found(group_id));
+        },
+        Err(err) => return Err(err.into()),
+    }
+    let station_ids = db::charger_group_station_ids(group_id)
+        .await?
+        .ok_or_else(|| charger_group_not_found(group_id))?
+        .into_iter()
+        .filter(commands::is_connected)
+        .collect();
+    let action = GroupAction::ChangeConfiguration(ChangeConfigurationRequest {
+        key: change.key,
+        value: change.value,
+    });
+    let job = charger_groups::start(group_id, "change-configuration", action, station_ids);
+    Ok((StatusCode::ACCEPTED, Json(job)))
+}
+
+async fn charger_group_job(ApiPath(job_id): ApiPath<Uuid>) -> Result<Json<GroupJob>, ApiError> {
+    charger_groups::job(job_id)
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Group job {job_id} not found")))
+}
+
+async fn tariffs() -> Result<Json<Vec<db::Tariff>>, ApiError> { Ok(Json(db::tariffs().await?)) }
+
+async fn create_tariff(
+    ApiJson(tariff): ApiJson<db::NewTariff>,
+) -> Result<(StatusCode, Json<db::Tariff>), ApiError> {
+    if !tariff.price_per_kwh.is_finite() || tariff.price_per_kwh < 0.0 {
+        return Err(ApiError::BadRequest(
+            "price_per_kwh must not be negative".to_string(),
+        ));
+    }
+    if tariff.currency.len() != 3
+        || !tariff
+            .currency
+            .chars()
+            .all(|c| c.is_ascii_uppercase())
+    {
+        return Err(ApiError::BadRequest(
+            "currency must be an ISO 4217 code, e.g. BRL".to_string(),
+        ));
+    }
+    if tariff
+        .valid_to
+        .is_some_and(|valid_to| valid_to <= tariff.valid_from)
+    {
+        return Err(ApiError::BadRequest(
+            "valid_to must be after valid_from".to_string(),
+        ));
+    }
+    if tariff.time_of_day_start.is_some() != tariff.time_of_day_end.is_some() {
+        return Err(ApiError::BadRequest(
+            "time_of_day_start and time_of_day_end must be set together".to_string(),
+        ));
+    }
+    if tariff
+        .day_of_week
+        .is_some_and(|day_of_week| !(1..=7).contains(&day_of_week))
+    {
+        return Err(ApiError::BadRequest(
+            "day_of_week must be from 1 (Monday) to 7 (Sunday)".to_string(),
+        ));
+    }
+    if tariff
+        .period
+        .as_ref()
+        .is_some_and(|period| period.is_empty() || period.len() > 50)
+    {
+        return Err(ApiError::BadRequest(
+            "period must have from 1 to 50 characters".to_string(),
+        ));
+    }
+    Ok((StatusCode::CREATED, Json(db::insert_tariff(&tariff).await?)))
+}
+
+/// Open transactions without meter values nor current for more than `INACTIVITY_TIMEOUT_SECS`
+async fn stale_sessions() -> Json<Vec<transactions::StaleSession>> {
+    Json(transactions::stale_sessions())
+}
+
+async fn session_summary(
+    ApiPath(transaction_id): ApiPath<i32>,
+) -> Result<Json<db::SessionSummary>, ApiError> {
+    db::session_summary(transaction_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Transaction {transaction_id} not found")))
+}
+
+/// Min, max, average and count of the readings of each measurand during the transaction
+async fn meter_stats(
+    ApiPath(transaction_id): ApiPath<i32>,
+) -> Result<Json<meter_stats::MeterStats>, ApiError> {
+    meter_stats::meter_stats(transaction_id)
+        .await?
+        .map(Json)
+        .ok_or_else(|| ApiError::NotFound(format!("Transaction {transaction_id} not found")))
+}
+
+/// Fleet-wide figures of the dashboard landing page
+async fn dashboard_summary() -> Result<Json<dashboard::Summary>, ApiError> {
+    Ok(Json(dashboard::summary().await?))
+}
+
+#[derive(Debug, Clone, Copy, serde::Deserialize)]
+#[serde(rename_all = "lowercase")]
+enum TopChargersMetric {
+    Energy,
+    Sessions,
+    Revenue,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct TopChargersQuery {
+    /// `<n>d` or `<n>h`, 7 days by default
+    period: Option<String>,
+    metric: TopChargersMetric,
+    limit: Option<i64>,
+}
+
+/// Start of a period of `<n>d` whole days or `<n>h` whole hours, the current one included
+fn period_start(period: &str) -> Option<DateTime<Utc>> {
+    let (count, unit) = period_units(period)?;
+    let current = Utc::now().duration_trunc(unit).ok()?;
+    Some(current - unit * (count - 1))
+}
+
+/// Number of days or hours of a `<n>d` or `<n>h` period, and the unit
+fn period_units(period: &str) -> Option<(i32, TimeDelta)> {
+    let (count, unit) = if let Some(days) = period.strip_suffix('d') {
+        (days, TimeDelta::days(1))
+    } else {
+        (period.strip_suffix('h')?, TimeDelta::hours(1))
+    };
+    let count: i32 = count.parse().ok()?;
+    (1..=366 * 24)
+        .contains(&count)
+        .then_some((count, unit))
+}
+
+/// Chargers with the most energy delivered, sessions or revenue over the period, the top one
+/// first. The period covers whole days or hours, so the ranking is stable within them
+async fn top_chargers(
+    ApiQuery(query): ApiQuery<TopChargersQuery>,
+) -> Result<Json<Vec<db::ChargerUsage>>, ApiError> {
+    let period = query.period.as_deref().unwrap_or("7d");
+    let since = period_start(period).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "{period:?} is not a period, expected e.g. 7d or 24h"
+        ))
+    })?;
+    let limit = query.limit.unwrap_or(10);
+    if !(1..=100).contains(&limit) {
+        return Err(ApiError::BadRequest(
+            "limit must be between 1 and 100".to_string(),
+        ));
+    }
+    let metric = match query.metric {
+        TopChargersMetric::Energy => "energy",
+        TopChargersMetric::Sessions => "sessions",
+        TopChargersMetric::Revenue => "revenue",
+    };
+    Ok(Json(db::top_chargers(since, metric, limit).await?))
+}
+
+/// Parameters of the server, as OCPP configuration keys
+async fn server_configuration() -> Json<Vec<KeyValue>> { Json(server_configuration::keys()) }
+
+#[derive(Debug, serde::Serialize)]
+struct ServerCapabilities {
+    enabled_profiles: Vec<feature_profiles::OcppFeatureProfile>,
+}
+
+/// OCPP feature profiles of the Calls the chargers may send, the others get a NotSupported
+async fn server_capabilities() -> Json<ServerCapabilities> {
+    Json(ServerCapabilities {
+        enabled_profiles: feature_profiles::enabled_profiles(),
+    })
+}
+
+/// Client IPs at or near `MAX_CONNECTIONS_PER_IP`
+async fn blocked_ips() -> Json<Vec<rate_limit::IpConnections>> { Json(rate_limit::near_limit()) }
+
+#[derive(Debug, serde::Serialize, serde::Deserialize)]
+struct LogLevel {
+    /// Directives in the syntax of `RUST_LOG`, e.g. `info,moovolt_backend_csms=debug`
+    filter: String,
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct RawMessage {
+    action: String,
+    payload: serde_json::Value,
+}
+
+fn allow_raw_inject() -> bool {
+    const DANGEROUS_ALLOW_RAW_INJECT: &str = dotenv!("DANGEROUS_ALLOW_RAW_INJECT");
+    DANGEROUS_ALLOW_RAW_INJECT == "true"
+}
+
+/// Send a Call of any action with any payload to the charger and return its response, for
+/// debugging and conformance testing. The payload is not validated, so it is only enabled when
+/// `DANGEROUS_ALLOW_RAW_INJECT` is `true`, and only to the holders of the admin token
+async fn inject_message(
+    ApiPath(station_id): ApiPath<StationId>,
+    ClientIp(client_ip): ClientIp,
+    ApiJson(message): ApiJson<RawMessage>,
+) -> Result<Json<serde_json::Value>, ApiError> {
+    if !allow_raw_inject() {
+        return Err(ApiError::Forbidden(
+            "Raw OCPP messages can only be sent when DANGEROUS_ALLOW_RAW_INJECT is true"
+                .to_string(),
+        ));
+    }
+    let action = message
+        .action
+        .parse::<OcppActionEnum>()
+        .map_err(|_| {
+            ApiError::BadRequest(format!("{:?} is not an OCPP 1.6 action", message.action))
+        })?;
+    if !call_direction::validate_call_direction(&action, MessageSender::Server) {
+        return Err(ApiError::BadRequest(format!(
+            "{action} is not a Call a server sends to a charger"
+        )));
+    }
+    // There are no user accounts on the REST API, the client address identifies the caller. The
+    // Call itself is logged with its idTags masked when sent
+    warn!(%client_ip, station_id, %action, "Injecting a raw OCPP Call");
+    let response = commands::send_call(&station_id, action, &message.payload).await?;
+    Ok(Json(response))
+}
+
+async fn log_level() -> Json<LogLevel> { Json(LogLevel { filter: log_level::filter() }) }
+
+/// Change the filter of the logs without a restart, e.g. to debug a module in production. It is
+/// back to `RUST_LOG` on the next restart
+async fn set_log_level(ApiJson(level): ApiJson<LogLevel>) -> Result<Json<LogLevel>, ApiError> {
+    log_level::set_filter(&level.filter).map_err(ApiError::BadRequest)?;
+    Ok(Json(LogLevel { filter: log_level::filter() }))
+}
+
+/// Every transaction as CSV, for billing and reporting
+async fn export_transactions() -> Result<impl IntoResponse, ApiError> {
+    let _permit = db::permit(&db::EXPORT_DB_SEMAPHORE)
+        .await
+        .ok_or_else(|| ApiError::Unavailable("Too many exports in progress".to_string()))?;
+    let mut csv = "transaction_id,station_id,connector_id,id_tag,user_name,status,start_time,\
+                   stop_time,stop_reason,energy_wh,import_energy_wh,export_energy_wh,cost,\
+                   currency\n"
+        .to_string();
+    for session in db::session_summaries().await? {
+        let fields = [
+            session.transaction_id.to_string(),
+            session.station_id,
+            session.connector_id.to_string(),
+            session.id_tag,
+            session
+                .user
+                .map(|user| user.name)
+                .unwrap_or_default(),
+            session.status,
+            session.start_time.to_rfc3339(),
+            session
+                .stop_time
+                .map(|stop_time| stop_time.to_rfc3339())
+                .unwrap_or_default(),
+            session.stop_reason.unwrap_or_default(),
+            session
+                .energy_wh
+                .map(|energy_wh| energy_wh.to_string())
+                .unwrap_or_default(),
+            session
+                .import_energy_wh
+                .map(|import_energy_wh| import_energy_wh.to_string())
+                .unwrap_or_default(),
+            session
+                .export_energy_wh
+                .map(|export_energy_wh| export_energy_wh.to_string())
+                .unwrap_or_default(),
+            session
+                .cost
+                .map(|cost| format!("{cost:.2}"))
+                .unwrap_or_default(),
+            session.currency.unwrap_or_default(),
+        ];
+        let fields: Vec<_> = fields
+            .iter()
+            .map(|field| csv_field(field))
+            .collect();
+        csv.push_str(&fields.join(","));
+        csv.push('\n');
+    }
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv"),
+            (
+                header::CONTENT_DISPOSITION,
+                "attachment; filename=\"transactions.csv\"",
+            ),
+        ],
+        csv,
+    ))
+}
+
+/// Sessions that fed energy back to the grid, from bi-directional (V2G) chargers
+async fn v2g_sessions() -> Result<Json<Vec<db::SessionSummary>>, ApiError> {
+    Ok(Json(db::v2g_session_summaries().await?))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct StopReasonsQuery {
+    /// `<n>d` or `<n>h`, 30 days by default
+    period: Option<String>,
+    station_id: Option<StationId>,
+}
+
+/// Transactions stopped over the period for each reason, the most frequent first. Many PowerLoss
+/// or Other stops at a station point to a hardware issue
+async fn stop_reasons(
+    ApiQuery(query): ApiQuery<StopReasonsQuery>,
+) -> Result<Json<Vec<db::StopReasonCount>>, ApiError> {
+    let period = query.period.as_deref().unwrap_or("30d");
+    let since = period_start(period).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "{period:?} is not a period, expected e.g. 30d or 24h"
+        ))
+    })?;
+    Ok(Json(
+        stop_reasons::report(since, query.station_id.as_deref()).await?,
+    ))
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct UtilizationQuery {
+    /// `<n>d` or `<n>h`, 7 days by default
+    period: Option<String>,
+    station_id: Option<StationId>,
+}
+
+/// Share of the last days or hours each connector spent Charging, the main indicator of the
+/// profitability of a site
+async fn utilization(
+    ApiQuery(query): ApiQuery<UtilizationQuery>,
+) -> Result<Json<Vec<db::ConnectorUtilization>>, ApiError> {
+    let period = query.period.as_deref().unwrap_or("7d");
+    let (count, unit) = period_units(period).ok_or_else(|| {
+        ApiError::BadRequest(format!(
+            "{period:?} is not a period, expected e.g. 7d or 24h"
+        ))
+    })?;
+    // The period ends now rather than at the end of the day or hour, so that the time to come
+    // does not count as idle
+    let until = Utc::now();
+    Ok(Json(
+        db::connector_utilization(until - unit * count, until, query.station_id.as_deref()).await?,
+    ))
+}
+
+/// Quote a CSV field when it contains a separator, a quote or a line break
+fn csv_field(field: &str) -> String {
+    if field.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+async fn users() -> Result<Json<Vec<db::User>>, ApiError> { Ok(Json(db::users().await?)) }
+
+async fn create_user(
+    ApiJson(user): ApiJson<db::NewUser>,
+) -> Result<(StatusCode, Json<db::User>), ApiError> {
+    if user.id_tag.is_empty() || user.id_tag.len() > 20 {
+        return Err(ApiError::BadRequest(
+            "id_tag must be between 1 and 20 characters".to_string(),
+        ));
+    }
+    if user.name.trim().is_empty() {
+        return Err(ApiError::BadRequest("name must not be empty".to_string()));
+    }
+    if user
+        .email
+        .as_ref()
+        .is_some_and(|email| !email.contains('@'))
+    {
+        return Err(ApiError::BadRequest(
+            "email must be an email address".to_string(),
+        ));
+    }
+    match db::insert_user(&user).await {
+        Ok(user) => Ok((StatusCode::CREATED, Json(user))),
+        Err(sqlx::Error::Database(err)) if err.is_unique_violation() => Err(ApiError::Conflict(
+            "A user with this id_tag or external_id already exists".to_string(),
+        )),
+        Err(err) => Err(err.into()),
+    }
+}
+
+async fn delete_user(ApiPath(user_id): ApiPath<i32>) -> Result<StatusCode, ApiError> {
+    if db::delete_user(user_id).await? {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound(format!("User {user_id} not found")))
+    }
+}
@@ -0,0 +1,283 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use chrono::Utc;
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::types::{AuthorizationStatus, IdTagInfo};
+use tokio::time::{Instant, MissedTickBehavior};
+use tracing::{error, info, warn};
+
+use crate::{db, mask::mask_id_tag};
+
+/// In-memory copy of the `id_tags` table, so authorizing a charge does not hit the database
+static AUTH_CACHE: LazyLock<RwLock<HashMap<String, IdTagInfo>>> = LazyLock::new(Default::default);
+
+/// Populate the authorization cache from the database. The new cache replaces the old one at
+/// once, so the OCPP handlers never see a partial cache nor wait for the query
+pub async fn load_cache() -> Result<usize, sqlx::Error> {
+    let id_tags = db::id_tags().await?;
+    let cache: HashMap<_, _> = id_tags
+        .into_iter()
+        .map(|id_tag| {
+            let id_tag_info = id_tag_info(&id_tag);
+            (id_tag.id_tag, id_tag_info)
+        })
+        .collect();
+    let id_tag_count = cache.len();
+    *AUTH_CACHE.write().unwrap() = cache;
+    Ok(id_tag_count)
+}
+
+/// Reload the authorization cache every `AUTH_CACHE_SYNC_INTERVAL_SECS`, so idTags changed in the
+/// database while the server runs are picked up
+pub async fn sync_cache() {
+    const AUTH_CACHE_SYNC_INTERVAL_SECS: &str = dotenv!("AUTH_CACHE_SYNC_INTERVAL_SECS");
+    let period = Duration::from_secs(
+        AUTH_CACHE_SYNC_INTERVAL_SECS
+            .parse()
+            .expect("AUTH_CACHE_SYNC_INTERVAL_SECS must be a number of seconds"),
+    );
+    let mut interval = tokio::time::interval_at(Instant::now() + period, period);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let started_at = Instant::now();
+        match load_cache().await {
+            Ok(id_tag_count) => info!(
+                "Synced {id_tag_count} idTags into the authorization cache in {:?}",
+                started_at.elapsed()
+            ),
+            Err(err) => error!("Failed to sync the authorization cache: {err:?}"),
+        }
+    }
+}
+
+/// Where the authorization of an idTag was found, the `method` label of
+/// `ocpp_authorize_duration_seconds`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum AuthMethod {
+    LocalCache,
+    Database,
+    /// The database could not be queried, so the idTag is Invalid
+    Fallback,
+}
+
+impl AuthMethod {
+    fn as_str(self) -> &'static str {
+        match self {
+            AuthMethod::LocalCache => "local_cache",
+            AuthMethod::Database => "database",
+            AuthMethod::Fallback => "fallback",
+        }
+    }
+}
+
+/// Authorization of an idTag, from the cache or else from the database. Unknown tags are
+/// `Invalid` and tags past their expiry date are `Expired`. A card of a group whose parent idTag
+/// expired or was blocked gets the status of its parent
+pub async fn authorize(id_tag: &str) -> IdTagInfo {
+    let started_at = Instant::now();
+    let (id_tag_info, method) = authorize_with_method(id_tag).await;
+    metrics::histogram!(
+        "ocpp_authorize_duration_seconds",
+        "method" => method.as_str(),
+        "result" => status_label(&id_tag_info.status)
+    )
+    .record(started_at.elapsed().as_secs_f64());
+    id_tag_info
+}
+
+/// Authorization of an idTag, and the slowest place one of the idTag or its parent was found in
+async fn authorize_with_method(id_tag: &str) -> (IdTagInfo, AuthMethod) {
+    let (id_tag_info, mut method) = known(id_tag).await;
+    let Some(id_tag_info) = id_tag_info else {
+        return (invalid(), method);
+    };
+    let mut id_tag_info = with_expiry(id_tag_info);
+    if id_tag_info.status == AuthorizationStatus::Accepted
+        && let Some(parent_id_tag) = &id_tag_info.parent_id_tag
+    {
+        let (parent_id_tag_info, parent_method) = known(parent_id_tag).await;
+        method = method.max(parent_method);
+        if let Some(parent_id_tag_info) = parent_id_tag_info {
+            let parent_status = with_expiry(parent_id_tag_info).status;
+            if matches!(
+                parent_status,
+                AuthorizationStatus::Blocked | AuthorizationStatus::Expired
+            ) {
+                id_tag_info.status = parent_status;
+            }
+        }
+    }
+    (id_tag_info, method)
+}
+
+fn status_label(status: &AuthorizationStatus) -> &'static str {
+    match status {
+        AuthorizationStatus::Accepted => "accepted",
+        AuthorizationStatus::Blocked => "blocked",
+        AuthorizationStatus::Expired => "expired",
+        AuthorizationStatus::Invalid => "invalid",
+        AuthorizationStatus::ConcurrentTx => "concurrent_tx",
+    }
+}
+
+/// Authorization of the idTag stopping a transaction. The charger already stopped the transaction,
+/// and caches the returned authorization, so the authorization of the card is returned even when
+/// it may not stop the transaction
+pub async fn authorize_stop(id_tag: &str, start_id_tag: &str) -> IdTagInfo {
+    let id_tag_info = authorize(id_tag).await;
+    if !may_stop(id_tag, &id_tag_info, start_id_tag).await {
+        warn!(
+            "Transaction of {} stopped by {}, which is not of the same group",
+            mask_id_tag(start_id_tag),
+            mask_id_tag(id_tag)
+        );
+    }
+    id_tag_info
+}
+
+/// Whether the card may stop the transaction started by `start_id_tag`: the same card, or a card
+/// of the same group, see OCPP 1.6 section 3.5.2
+async fn may_stop(id_tag: &str, id_tag_info: &IdTagInfo, start_id_tag: &str) -> bool {
+    if id_tag == start_id_tag {
+        return true;
+    }
+    let start_parent_id_tag = authorize(start_id_tag)
+        .await
+        .parent_id_tag;
+    start_parent_id_tag.is_some() && start_parent_id_tag == id_tag_info.parent_id_tag
+}
+
+/// Authorization of a known idTag, as stored, and where it was found
+async fn known(id_tag: &str) -> (Option<IdTagInfo>, AuthMethod) {
+    let cached = AUTH_CACHE
+        .read()
+        .unwrap()
+        .get(id_tag)
+        .cloned();
+    if let Some(id_tag_info) = cached {
+        return (Some(id_tag_info), AuthMethod::LocalCache);
+    }
+    let Some(_permit) = db::ocpp_permit("looking up the idTag").await else {
+        return (None, AuthMethod::Fallback);
+    };
+    match lookup(id_tag).await {
+        Ok(id_tag_info) => (id_tag_info, AuthMethod::Database),
+        Err(err) => {
+            error!("Failed to look up idTag: {err:?}");
+            (None, AuthMethod::Fallback)
+        },
+    }
+}
+
+fn with_expiry(mut id_tag_info: IdTagInfo) -> IdTagInfo {
+    if id_tag_info.status == AuthorizationStatus::Accepted
+        && id_tag_info
+            .expiry_date
+            .is_some_and(|expiry_date| expiry_date < Utc::now())
+    {
+        id_tag_info.status = AuthorizationStatus::Expired;
+    }
+    id_tag_info
+}
+
+/// Authorization of an idTag missing from the cache, cached once found in the database
+async fn lookup(id_tag: &str) -> Result<Option<IdTagInfo>, sqlx::Error> {
+    let Some(row) = db::id_tag(id_tag).await? else {
+        return Ok(None);
+    };
+    let id_tag_info = id_tag_info(&row);
+    AUTH_CACHE
+        .write()
+        .unwrap()
+        .insert(row.id_tag, id_tag_info.clone());
+    Ok(Some(id_tag_info))
+}
+
+fn invalid() -> IdTagInfo {
+    IdTagInfo {
+        status: AuthorizationStatus::Invalid,
+        expiry_date: None,
+        parent_id_tag: None,
+    }
+}
+
+fn id_tag_info(id_tag: &db::IdTag) -> IdTagInfo {
+    let status = match id_tag.status.as_str() {
+        "Accepted" => AuthorizationStatus::Accepted,
+        "Blocked" => AuthorizationStatus::Blocked,
+        "Expired" => AuthorizationStatus::Expired,
+        "ConcurrentTx" => AuthorizationStatus::ConcurrentTx,
+        _ => AuthorizationStatus::Invalid,
+    };
+    IdTagInfo {
+        status,
+        expiry_date: id_tag.expiry_date,
+        parent_id_tag: id_tag.parent_id_tag.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Put the idTag, and its parent, in the cache, so it is authorized without the database
+    fn cache(id_tag: &str, parent_id_tag: Option<&str>) {
+        if let Some(parent_id_tag) = parent_id_tag {
+            cache(parent_id_tag, None);
+        }
+        AUTH_CACHE.write().unwrap().insert(
+            id_tag.to_string(),
+            IdTagInfo {
+                status: AuthorizationStatus::Accepted,
+                expiry_date: None,
+                parent_id_tag: parent_id_tag.map(str::to_string),
+            },
+        );
+    }
+
+    async fn may_stop_transaction_of(id_tag: &str, start_id_tag: &str) -> bool {
+        let id_tag_info = authorize(id_tag).await;
+        may_stop(id_tag, &id_tag_info, start_id_tag).await
+    }
+
+    #[tokio::test]
+    async fn same_id_tag_stops() {
+        cache("STOP-SAME", None);
+        assert!(may_stop_transaction_of("STOP-SAME", "STOP-SAME").await);
+    }
+
+    #[tokio::test]
+    async fn id_tag_of_the_same_group_stops() {
+        cache("STOP-GROUP-1-START", Some("STOP-GROUP-1"));
+        cache("STOP-GROUP-1-STOP", Some("STOP-GROUP-1"));
+        assert!(may_stop_transaction_of("STOP-GROUP-1-STOP", "STOP-GROUP-1-START").await);
+    }
+
+    #[tokio::test]
+    async fn id_tag_of_another_group_does_not_stop() {
+        cache("STOP-GROUP-2-START", Some("STOP-GROUP-2"));
+        cache("STOP-GROUP-3-STOP", Some("STOP-GROUP-3"));
+        assert!(!may_stop_transaction_of("STOP-GROUP-3-STOP", "STOP-GROUP-2-START").await);
+    }
+
+    #[tokio::test]
+    async fn other_id_tag_without_group_does_not_stop() {
+        cache("STOP-NO-GROUP-START", None);
+        cache("STOP-NO-GROUP-STOP", None);
+        assert!(!may_stop_transaction_of("STOP-NO-GROUP-STOP", "STOP-NO-GROUP-START").await);
+    }
+
+    #[tokio::test]
+    async fn stopping_id_tag_keeps_its_authorization() {
+        cache("STOP-KEEP-START", Some("STOP-GROUP-4"));
+        cache("STOP-KEEP-STOP", Some("STOP-GROUP-5"));
+        let id_tag_info = authorize_stop("STOP-KEEP-STOP", "STOP-KEEP-START").await;
+        assert_eq!(id_tag_info.status, AuthorizationStatus::Accepted);
+        assert_eq!(id_tag_info.parent_id_tag.as_deref(), Some("STOP-GROUP-5"));
+    }
+}
@@ -0,0 +1,82 @@
+use rust_ocpp::v1_6::{
+    messages::change_availability::{ChangeAvailabilityRequest, ChangeAvailabilityResponse},
+    types::{AvailabilityStatus, AvailabilityType},
+};
+use tracing::{error, info, warn};
+
+use crate::{
+    commands,
+    connectors::{self, ConnectorId},
+    db, OcppActionEnum, StationId,
+};
+
+pub fn availability_name(availability: &AvailabilityType) -> &'static str {
+    match availability {
+        AvailabilityType::Inoperative => "Inoperative",
+        AvailabilityType::Operative => "Operative",
+    }
+}
+
+fn parse_availability(name: &str) -> Option<AvailabilityType> {
+    match name {
+        "Inoperative" => Some(AvailabilityType::Inoperative),
+        "Operative" => Some(AvailabilityType::Operative),
+        _ => None,
+    }
+}
+
+/// Ask the charger to change the availability of a connector, or of the whole charger with
+/// connector 0, see OCPP 1.6 ChangeAvailability. The change is recorded once the charger accepts or
+/// schedules it
+pub async fn change_availability(
+    station_id: &StationId,
+    connector_id: ConnectorId,
+    availability: AvailabilityType,
+) -> Result<ChangeAvailabilityResponse, commands::OcppError> {
+    let request = ChangeAvailabilityRequest { connector_id, kind: availability.clone() };
+    let response: ChangeAvailabilityResponse =
+        commands::send_call(station_id, OcppActionEnum::ChangeAvailability, &request).await?;
+    if response.status != AvailabilityStatus::Rejected {
+        connectors::change_availability(station_id, connector_id, availability);
+    }
+    Ok(response)
+}
+
+/// Send the overridden availabilities to a charger that just booted, as it forgot them. Waits for
+/// the responses of the charger, so it must run outside of the message loop of the connection
+pub async fn reapply(station_id: StationId) {
+    let overrides = {
+        let Some(_permit) = db::ocpp_permit("loading the availability overrides").await else {
+            return;
+        };
+        match db::availability_overrides(&station_id).await {
+            Ok(overrides) => overrides,
+            Err(err) => {
+                error!("Failed to load availability overrides of {station_id}: {err:?}");
+                return;
+            },
+        }
+    };
+    for availability_override in overrides {
+        let connector_id = availability_override.connector_id as ConnectorId;
+        let Some(availability) = parse_availability(&availability_override.availability_type)
+        else {
+            error!(
+                "Invalid availability override {} on connector {connector_id} of {station_id}",
+                availability_override.availability_type
+            );
+            continue;
+        };
+        match change_availability(&station_id, connector_id, availability).await {
+            Ok(response) => info!(
+                "Reapplied {} availability override on connector {connector_id} of {station_id}: \
+                 {:?}",
+                availability_override.availability_type, response.status
+            ),
+            Err(err) => warn!(
+                "Failed to reapply availability override on connector {connector_id} of \
+                 {station_id}: {err}"
+            ),
+        }
+    }
+}
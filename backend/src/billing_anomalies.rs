@@ -0,0 +1,46 @@
+use tracing::{error, warn, Instrument, Span};
+
+use crate::{
+    alerts::{self, AlertEvent},
+    db,
+};
+
+/// Look for the transactions of the connector that overlap the stopped transaction, in the
+/// background. A connector never runs two transactions at once, so an overlap means the same
+/// charge could be billed twice: it is recorded and fires the `session_overlap` alert rules
+pub fn check_session_overlaps(transaction_id: i32) {
+    tokio::spawn(
+        async move {
+            let anomalies = {
+                let Some(_permit) = db::ocpp_permit("checking the session overlaps").await else {
+                    return;
+                };
+                match db::record_session_overlaps(transaction_id).await {
+                    Ok(anomalies) => anomalies,
+                    Err(err) => {
+                        error!(
+                            "Failed to check the session overlaps of transaction \
+                             {transaction_id}: {err:?}"
+                        );
+                        return;
+                    },
+                }
+            };
+            for anomaly in anomalies {
+                warn!(
+                    "Transaction {transaction_id} overlaps transaction {} on connector {} of {}",
+                    anomaly.overlapping_transaction_id, anomaly.connector_id, anomaly.station_id
+                );
+                alerts::fire(
+                    &anomaly.station_id,
+                    AlertEvent::SessionOverlap {
+                        connector_id: anomaly.connector_id,
+                        transaction_id,
+                        overlapping_transaction_id: anomaly.overlapping_transaction_id,
+                    },
+                );
+            }
+        }
+        .instrument(Span::current()),
+    );
+}
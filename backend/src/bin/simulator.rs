@@ -0,0 +1,435 @@
+//! Simulates OCPP 1.6 chargers to load test the server.
+//!
+//! Every simulated charger sends a BootNotification, then exchanges Heartbeats and periodically
+//! runs a full charge cycle: Authorize → StartTransaction → MeterValues × 5 → StopTransaction.
+//! Throughput, per-action latency percentiles and error count are reported when the run ends.
+//!
+//! Every simulated charger connects from the same IP, so the server `MAX_CONNECTIONS_PER_IP` must
+//! be raised above the number of chargers.
+//!
+//! ```sh
+//! cargo run --bin simulator -- --chargers 100 --duration 60
+//! ```
+
+use std::{
+    collections::HashMap,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use chrono::Utc;
+use futures::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use tokio::{
+    net::TcpStream,
+    time::{self, Instant, MissedTickBehavior},
+};
+use tokio_tungstenite::{
+    tungstenite::{client::IntoClientRequest, http::HeaderValue, Message},
+    MaybeTlsStream, WebSocketStream,
+};
+use tracing::{warn, warn_span, Instrument, Level};
+
+const USAGE: &str = "\
+Usage: simulator [OPTIONS]
+
+Options:
+  --chargers <N>            Number of simulated chargers [default: 10]
+  --url <URL>               OCPP endpoint [default: ws://127.0.0.1:3000/ocpp16j]
+  --duration <SECS>         Duration of the run [default: 60]
+  --heartbeat-interval <S>  Interval between Heartbeats [default: 10]
+  --charge-interval <SECS>  Interval between charge cycles [default: 30]
+  --timeout <SECS>          Time to wait for each response [default: 10]
+  --id-tag <ID_TAG>         idTag used to authorize the charge cycles [default: SIMULATOR]
+  --serial-number <SERIAL>  Serial number sent in the BootNotifications [default: station ID]";
+
+/// Meter samples sent during every charge cycle
+const METER_VALUES_PER_CYCLE: usize = 5;
+
+#[derive(Debug, Clone)]
+struct Args {
+    chargers: usize,
+    url: String,
+    duration: Duration,
+    heartbeat_interval: Duration,
+    charge_interval: Duration,
+    timeout: Duration,
+    id_tag: String,
+    serial_number: Option<String>,
+}
+
+impl Args {
+    fn parse() -> Self {
+        let mut parsed = Self {
+            chargers: 10,
+            url: "ws://127.0.0.1:3000/ocpp16j".to_string(),
+            duration: Duration::from_secs(60),
+            heartbeat_interval: Duration::from_secs(10),
+            charge_interval: Duration::from_secs(30),
+            timeout: Duration::from_secs(10),
+            id_tag: "SIMULATOR".to_string(),
+            serial_number: None,
+        };
+        let mut args = std::env::args().skip(1);
+        while let Some(arg) = args.next() {
+            if arg == "--help" || arg == "-h" {
+                println!("{USAGE}");
+                std::process::exit(0);
+            }
+            let Some(value) = args.next() else {
+                exit_with_usage(&format!("Missing value for {arg}"));
+            };
+            match arg.as_str() {
+                "--chargers" => parsed.chargers = parse_number(&arg, &value) as usize,
+                "--url" => parsed.url = value.trim_end_matches('/').to_string(),
+                "--duration" => parsed.duration = Duration::from_secs(parse_number(&arg, &value)),
+                "--heartbeat-interval" => {
+                    parsed.heartbeat_interval = Duration::from_secs(parse_number(&arg, &value))
+                },
+                "--charge-interval" => {
+                    parsed.charge_interval = Duration::from_secs(parse_number(&arg, &value))
+                },
+                "--timeout" => parsed.timeout = Duration::from_secs(parse_number(&arg, &value)),
+                "--id-tag" => parsed.id_tag = value,
+                "--serial-number" => parsed.serial_number = Some(value),
+                _ => exit_with_usage(&format!("Unknown option {arg}")),
+            }
+        }
+        parsed
+    }
+}
+
+fn parse_number(arg: &str, value: &str) -> u64 {
+    match value.parse() {
+        Ok(number) if number > 0 => number,
+        _ => exit_with_usage(&format!("{arg} expects a positive number, got {value}")),
+    }
+}
+
+fn exit_with_usage(error: &str) -> ! {
+    eprintln!("{error}\n\n{USAGE}");
+    std::process::exit(2);
+}
+
+/// Counters shared by every simulated charger
+#[derive(Debug, Default)]
+struct Stats {
+    messages_sent: u64,
+    messages_received: u64,
+    errors: u64,
+    latencies: HashMap<&'static str, Vec<Duration>>,
+}
+
+type SharedStats = Arc<Mutex<Stats>>;
+
+struct SimulatedCharger {
+    station_id: String,
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    stats: SharedStats,
+    timeout: Duration,
+    next_message_id: u64,
+    meter_wh: i32,
+}
+
+impl SimulatedCharger {
+    async fn connect(
+        station_id: String,
+        args: &Args,
+        stats: SharedStats,
+    ) -> Result<Self, tokio_tungstenite::tungstenite::Error> {
+        let mut request = format!("{}/{station_id}", args.url).into_client_request()?;
+        let headers = request.headers_mut();
+        headers.insert(
+            "Sec-WebSocket-Protocol",
+            HeaderValue::from_static("ocpp1.6"),
+        );
+        headers.insert("User-Agent", HeaderValue::from_static("Websocket Client"));
+        let (socket, _) = tokio_tungstenite::connect_async(request).await?;
+        Ok(Self {
+            station_id,
+            socket,
+            stats,
+            timeout: args.timeout,
+            next_message_id: 0,
+            meter_wh: 0,
+        })
+    }
+
+    /// Send a Call and wait for its response, recording the latency. Returns `None` and counts an
+    /// error when the server answers with a CallError, does not answer in time or disconnects
+    async fn call(&mut self, action: &'static str, payload: Value) -> Option<Value> {
+        self.next_message_id += 1;
+        let message_id = self.next_message_id.to_string();
+        let call = json!([2, message_id, action, payload]).to_string();
+        let started_at = Instant::now();
+        if let Err(err) = self
+            .socket
+            .send(Message::Text(call))
+            .await
+        {
+            warn!("{} failed to send {action}: {err}", self.station_id);
+            self.record_error();
+            return None;
+        }
+        self.stats.lock().unwrap().messages_sent += 1;
+
+        let deadline = started_at + self.timeout;
+        loop {
+            let message = match time::timeout_at(deadline, self.socket.next()).await {
+                Ok(Some(Ok(Message::Text(message)))) => message,
+                Ok(Some(Ok(_))) => continue,
+                Ok(Some(Err(_)) | None) => {
+                    warn!(
+                        "{} disconnected while waiting for {action}",
+                        self.station_id
+                    );
+                    self.record_error();
+                    return None;
+                },
+                Err(_) => {
+                    warn!("{} got no response to {action} in time", self.station_id);
+                    self.record_error();
+                    return None;
+                },
+            };
+            self.stats
+                .lock()
+                .unwrap()
+                .messages_received += 1;
+            let Ok(message) = serde_json::from_str::<Value>(&message) else {
+                continue;
+            };
+            match parse_response(&message) {
+                Some((id, Ok(payload))) if id == message_id => {
+                    let latency = started_at.elapsed();
+                    self.stats
+                        .lock()
+                        .unwrap()
+                        .latencies
+                        .entry(action)
+                        .or_default()
+                        .push(latency);
+                    return Some(payload);
+                },
+                Some((id, Err(error_code))) if id == message_id => {
+                    warn!(
+                        "{} got {error_code} in response to {action}",
+                        self.station_id
+                    );
+                    self.record_error();
+                    return None;
+                },
+                _ => self.reject_server_call(&message).await,
+            }
+        }
+    }
+
+    /// Server-initiated Calls are not simulated, answer them with a CallError
+    async fn reject_server_call(&mut self, message: &Value) {
+        if message.get(0) != Some(&json!(2)) {
+            return;
+        }
+        let call_error = json!([
+            4,
+            message[1],
+            "NotSupported",
+            "Not supported by the simulator",
+            {}
+        ]);
+        let _ = self
+            .socket
+            .send(Message::Text(call_error.to_string()))
+            .await;
+    }
+
+    fn record_error(&self) { self.stats.lock().unwrap().errors += 1; }
+
+    async fn run(mut self, args: Args, deadline: Instant) {
+        let boot_notification = json!({
+            "chargePointVendor": "Moovolt",
+            "chargePointModel": "Simulator",
+            "chargePointSerialNumber": args.serial_number.as_ref().unwrap_or(&self.station_id),
+        });
+        // Like a real charger, keep going even if the server did not accept the boot
+        self.call("BootNotification", boot_notification)
+            .await;
+
+        let mut heartbeat = time::interval_at(
+            Instant::now() + args.heartbeat_interval,
+            args.heartbeat_interval,
+        );
+        heartbeat.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut charge = time::interval(args.charge_interval);
+        charge.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        loop {
+            tokio::select! {
+                _ = time::sleep_until(deadline) => break,
+                _ = heartbeat.tick() => {
+                    self.call("Heartbeat", json!({})).await;
+                },
+                _ = charge.tick() => self.charge_cycle(&args.id_tag).await,
+            }
+        }
+        let _ = self.socket.close(None).await;
+    }
+
+    /// Authorize → StartTransaction → MeterValues × 5 → StopTransaction, aborted on the first
+    /// failed step
+    async fn charge_cycle(&mut self, id_tag: &str) {
+        let connector_id = 1;
+        if self
+            .call("Authorize", json!({ "idTag": id_tag }))
+            .await
+            .is_none()
+        {
+            return;
+        }
+        let start_transaction = json!({
+            "connectorId": connector_id,
+            "idTag": id_tag,
+            "meterStart": self.meter_wh,
+            "timestamp": Utc::now(),
+        });
+        let Some(transaction_id) = self
+            .call("StartTransaction", start_transaction)
+            .await
+            .and_then(|response| response["transactionId"].as_i64())
+        else {
+            return;
+        };
+        for _ in 0..METER_VALUES_PER_CYCLE {
+            self.meter_wh += 250;
+            let meter_values = json!({
+                "connectorId": connector_id,
+                "transactionId": transaction_id,
+                "meterValue": [{
+                    "timestamp": Utc::now(),
+                    "sampledValue": [{
+                        "value": self.meter_wh.to_string(),
+                        "measurand": "Energy.Active.Import.Register",
+                        "unit": "Wh",
+                    }],
+                }],
+            });
+            if self
+                .call("MeterValues", meter_values)
+                .await
+                .is_none()
+            {
+                return;
+            }
+        }
+        let stop_transaction = json!({
+            "transactionId": transaction_id,
+            "idTag": id_tag,
+            "meterStop": self.meter_wh,
+            "timestamp": Utc::now(),
+            "reason": "Local",
+        });
+        self.call("StopTransaction", stop_transaction)
+            .await;
+    }
+}
+
+/// Message ID and payload, or error code, of a CallResult or CallError. The server currently
+/// answers with `{"MessageTypeId", "MessageId", "Payload"}` objects instead of OCPP arrays, so both
+/// shapes are accepted
+fn parse_response(message: &Value) -> Option<(String, Result<Value, String>)> {
+    let (message_type_id, message_id) = match message {
+        Value::Array(fields) => (fields.first()?, fields.get(1)?),
+        Value::Object(fields) => (fields.get("MessageTypeId")?, fields.get("MessageId")?),
+        _ => return None,
+    };
+    let message_id = message_id.as_str()?.to_string();
+    match (message_type_id.as_u64()?, message) {
+        (3, Value::Array(fields)) => Some((message_id, Ok(fields.get(2)?.clone()))),
+        (3, Value::Object(fields)) => Some((message_id, Ok(fields.get("Payload")?.clone()))),
+        (4, Value::Array(fields)) => Some((message_id, Err(fields.get(2)?.to_string()))),
+        (4, Value::Object(fields)) => Some((message_id, Err(fields.get("ErrorCode")?.to_string()))),
+        _ => None,
+    }
+}
+
+fn percentile(sorted_latencies: &[Duration], percentile: f64) -> Duration {
+    let index = ((sorted_latencies.len() - 1) as f64 * percentile / 100.0).round() as usize;
+    sorted_latencies[index]
+}
+
+fn report(stats: &Stats, elapsed: Duration) {
+    let messages = stats.messages_sent + stats.messages_received;
+    println!("\nDuration:   {:.1} s", elapsed.as_secs_f64());
+    println!(
+        "Messages:   {} sent, {} received",
+        stats.messages_sent, stats.messages_received
+    );
+    println!(
+        "Throughput: {:.1} messages/s",
+        messages as f64 / elapsed.as_secs_f64()
+    );
+    println!("Errors:     {}\n", stats.errors);
+    println!(
+        "{:<20} {:>8} {:>10} {:>10} {:>10}",
+        "Action", "Count", "p50 (ms)", "p95 (ms)", "p99 (ms)"
+    );
+    let mut actions: Vec<_> = stats.latencies.iter().collect();
+    actions.sort_by_key(|(action, _)| **action);
+    for (action, latencies) in actions {
+        let mut latencies = latencies.clone();
+        latencies.sort();
+        let millis = |latency: Duration| latency.as_secs_f64() * 1000.0;
+        println!(
+            "{action:<20} {:>8} {:>10.2} {:>10.2} {:>10.2}",
+            latencies.len(),
+            millis(percentile(&latencies, 50.0)),
+            millis(percentile(&latencies, 95.0)),
+            millis(percentile(&latencies, 99.0)),
+        );
+    }
+}
+
+#[tokio::main]
+async fn main() {
+    tracing_subscriber::fmt()
+        .with_max_level(Level::WARN)
+        .init();
+
+    let args = Args::parse();
+    println!(
+        "Simulating {} chargers against {} for {} s",
+        args.chargers,
+        args.url,
+        args.duration.as_secs()
+    );
+    let stats = SharedStats::default();
+    let started_at = Instant::now();
+    let deadline = started_at + args.duration;
+
+    let mut chargers = Vec::with_capacity(args.chargers);
+    for index in 0..args.chargers {
+        let args = args.clone();
+        let stats = stats.clone();
+        let station_id = format!("SIM{index:05}");
+        // Spawned tasks do not inherit the span of the spawner. Only warnings are logged, so the
+        // span has to be enabled at that level
+        let span = warn_span!("charger", station_id);
+        chargers.push(tokio::spawn(
+            async move {
+                match SimulatedCharger::connect(station_id.clone(), &args, stats.clone()).await {
+                    Ok(charger) => charger.run(args, deadline).await,
+                    Err(err) => {
+                        warn!("{station_id} failed to connect: {err}");
+                        stats.lock().unwrap().errors += 1;
+                    },
+                }
+            }
+            .instrument(span),
+        ));
+    }
+    for charger in chargers {
+        if let Err(err) = charger.await {
+            warn!("Simulated charger panicked: {err}");
+        }
+    }
+
+    report(&stats.lock().unwrap(), started_at.elapsed());
+}
@@ -0,0 +1,50 @@
+use crate::OcppActionEnum;
+
+/// Side of the connection sending a Call
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MessageSender {
+    Charger,
+    Server,
+}
+
+/// Whether the sender may send a Call of the action. OCPP 1.6 sets the direction of each action,
+/// so that a charger cannot inject the Calls of a server, e.g. a RemoteStartTransaction
+pub fn validate_call_direction(action: &OcppActionEnum, sender: MessageSender) -> bool {
+    use OcppActionEnum::*;
+    match sender {
+        MessageSender::Charger => matches!(
+            action,
+            Authorize
+                | BootNotification
+                | DataTransfer
+                | Heartbeat
+                | MeterValues
+                | StartTransaction
+                | StatusNotification
+                | StopTransaction
+                | SignCertificate
+                // Calls of a server that some firmware sends to the server, which answers them
+                | GetConfiguration
+                | GetLocalListVersion
+                | SendLocalList
+        ),
+        MessageSender::Server => matches!(
+            action,
+            ChangeAvailability
+                | ChangeConfiguration
+                | ClearCache
+                | DataTransfer
+                | GetConfiguration
+                | RemoteStartTransaction
+                | RemoteStopTransaction
+                | Reset
+                | UnlockConnector
+                | ClearChargingProfile
+                | GetLocalListVersion
+                | SendLocalList
+                | GetDiagnostics
+                | UpdateFirmware
+                | CertificateSigned
+        ),
+    }
+}
@@ -0,0 +1,88 @@
+use std::{collections::HashMap, sync::LazyLock};
+
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::messages::boot_notification::BootNotificationRequest;
+use tracing::{error, info};
+
+use crate::{commands, connectors::ConnectorId, StationId};
+
+/// Connectors assumed for the chargers of an unknown model
+const DEFAULT_MAX_CONNECTORS: ConnectorId = 10;
+
+/// Capabilities of the charger models, by `chargePointModel`, read from
+/// `CHARGER_CAPABILITIES_PATH`. Empty when the path is empty or the file could not be read, the
+/// commands are then sent to every charger
+static KNOWN_MODELS: LazyLock<HashMap<String, ChargerCapabilities>> = LazyLock::new(|| {
+    const CHARGER_CAPABILITIES_PATH: &str = dotenv!("CHARGER_CAPABILITIES_PATH");
+    if CHARGER_CAPABILITIES_PATH.is_empty() {
+        info!(
+            "CHARGER_CAPABILITIES_PATH is empty, the commands are not checked against the models"
+        );
+        return HashMap::new();
+    }
+    std::fs::read_to_string(CHARGER_CAPABILITIES_PATH)
+        .map_err(|err| err.to_string())
+        .and_then(|json| serde_json::from_str(&json).map_err(|err| err.to_string()))
+        .inspect_err(|err| error!("Failed to load {CHARGER_CAPABILITIES_PATH}: {err}"))
+        .unwrap_or_default()
+});
+
+/// OCPP features a charger model supports, see `charger_capabilities.example.json`
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct ChargerCapabilities {
+    pub supports_smart_charging: bool,
+    pub supports_reservation: bool,
+    pub supports_v2g: bool,
+    pub supports_local_auth_list: bool,
+    pub max_connectors: ConnectorId,
+}
+
+/// Capabilities of the model of a booting charger, `None` when the model is unknown
+pub fn capabilities_from_boot(boot: &BootNotificationRequest) -> Option<ChargerCapabilities> {
+    KNOWN_MODELS
+        .get(&boot.charge_point_model)
+        .cloned()
+}
+
+/// Check the charger supports smart charging, so its charging profiles can be changed. Returns
+/// why it does not otherwise
+pub fn require_smart_charging(station_id: &StationId) -> Result<(), String> {
+    match commands::capabilities(station_id) {
+        Some(capabilities) if !capabilities.supports_smart_charging => Err(format!(
+            "The model of charger {station_id} does not support smart charging"
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Check the charger has the connector. Returns why it does not otherwise
+pub fn require_connector(station_id: &StationId, connector_id: ConnectorId) -> Result<(), String> {
+    match commands::capabilities(station_id) {
+        Some(capabilities) if connector_id > capabilities.max_connectors => Err(format!(
+            "The model of charger {station_id} has {} connectors, there is no connector \
+             {connector_id}",
+            capabilities.max_connectors
+        )),
+        _ => Ok(()),
+    }
+}
+
+/// Check a connector ID sent by the charger is one of its connectors, or 0 for the whole charger.
+/// A charger of an unknown model is assumed to have at most 10. Returns why it is not otherwise
+pub fn validate_connector_id(
+    station_id: &StationId,
+    connector_id: ConnectorId,
+) -> Result<(), String> {
+    let max_connectors = commands::capabilities(station_id)
+        .map_or(DEFAULT_MAX_CONNECTORS, |capabilities| {
+            capabilities.max_connectors
+        });
+    if connector_id > max_connectors {
+        return Err(format!(
+            "Connector {connector_id} is above the {max_connectors} connectors of charger \
+             {station_id}"
+        ));
+    }
+    Ok(())
+}
@@ -0,0 +1,211 @@
+use std::sync::LazyLock;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use dotenvy_macro::dotenv;
+use rcgen::{
+    Certificate, CertificateParams, CertificateSigningRequestParams, DnType, DnValue,
+    ExtendedKeyUsagePurpose, IsCa, KeyPair, SerialNumber,
+};
+use time::OffsetDateTime;
+use tracing::{error, info, warn};
+use uuid::Uuid;
+
+use crate::{commands, db, OcppActionEnum, StationId};
+
+/// Longest CSR accepted, as by the SignCertificate.req schema of the OCPP 1.6 Security Whitepaper
+const MAX_CSR_LENGTH: usize = 5500;
+/// How long the certificates issued to the chargers are valid
+const CERTIFICATE_VALIDITY: TimeDelta = TimeDelta::days(365);
+
+/// `None` when `CA_CERT_PATH` or `CA_KEY_PATH` is empty or the CA could not be loaded, the CSRs
+/// are then rejected
+static CERTIFICATE_AUTHORITY: LazyLock<Option<CertificateAuthority>> = LazyLock::new(|| {
+    const CA_CERT_PATH: &str = dotenv!("CA_CERT_PATH");
+    const CA_KEY_PATH: &str = dotenv!("CA_KEY_PATH");
+    if CA_CERT_PATH.is_empty() || CA_KEY_PATH.is_empty() {
+        warn!("CA_CERT_PATH or CA_KEY_PATH is empty, the CSRs of the chargers are rejected");
+        return None;
+    }
+    CertificateAuthority::load(CA_CERT_PATH, CA_KEY_PATH)
+        .inspect_err(|err| error!("Failed to load the CA: {err}"))
+        .ok()
+});
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct SignCertificateRequest {
+    pub csr: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct SignCertificateResponse {
+    pub status: GenericStatus,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GenericStatus {
+    Accepted,
+    Rejected,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[serde(rename_all = "camelCase")]
+pub struct CertificateSignedRequest {
+    /// PEM of the certificate of the charger followed by the one of the CA
+    pub certificate_chain: String,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct CertificateSignedResponse {
+    pub status: CertificateSignedStatus,
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CertificateSignedStatus {
+    Accepted,
+    Rejected,
+}
+
+struct CertificateAuthority {
+    /// Only its subject and key identifier end up in the issued certificates
+    certificate: Certificate,
+    key_pair: KeyPair,
+    /// As read from `CA_CERT_PATH`, appended to the issued certificates
+    certificate_pem: String,
+}
+
+impl CertificateAuthority {
+    fn load(cert_path: &str, key_path: &str) -> Result<Self, String> {
+        let certificate_pem =
+            std::fs::read_to_string(cert_path).map_err(|err| format!("{cert_path}: {err}"))?;
+        let key_pem =
+            std::fs::read_to_string(key_path).map_err(|err| format!("{key_path}: {err}"))?;
+        let key_pair = KeyPair::from_pem(&key_pem).map_err(|err| format!("{key_path}: {err}"))?;
+        // rcgen signs with a `Certificate` of the issuer, rebuilt here from the parameters of the
+        // CA certificate
+        let certificate = CertificateParams::from_ca_cert_pem(&certificate_pem)
+            .and_then(|params| params.self_signed(&key_pair))
+            .map_err(|err| format!("{cert_path}: {err}"))?;
+        Ok(Self { certificate, key_pair, certificate_pem })
+    }
+}
+
+/// Certificate signed for a charger, to be stored and sent with a CertificateSigned
+pub struct IssuedCertificate {
+    station_id: StationId,
+    serial_number: String,
+    /// PEM of the certificate of the charger
+    certificate: String,
+    /// `certificate` followed by the PEM of the CA
+    certificate_chain: String,
+    issued_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+}
+
+/// Sign the CSR of a SignCertificate with the CA. The common name of the CSR must be the station
+/// ID of the charger, so a charger cannot get a certificate for another one
+pub fn sign(
+    station_id: &StationId,
+    request: &SignCertificateRequest,
+) -> Result<IssuedCertificate, String> {
+    let Some(ca) = CERTIFICATE_AUTHORITY.as_ref() else {
+        return Err("no CA is configured".to_string());
+    };
+    if request.csr.len() > MAX_CSR_LENGTH {
+        return Err(format!("CSR is longer than {MAX_CSR_LENGTH} characters"));
+    }
+    let mut csr = CertificateSigningRequestParams::from_pem(&request.csr)
+        .map_err(|err| format!("invalid CSR: {err}"))?;
+    let common_name = match csr
+        .params
+        .distinguished_name
+        .get(&DnType::CommonName)
+    {
+        Some(DnValue::Utf8String(common_name)) => Some(common_name.as_str()),
+        Some(DnValue::PrintableString(common_name)) => Some(common_name.as_str()),
+        Some(DnValue::Ia5String(common_name)) => Some(common_name.as_str()),
+        _ => None,
+    };
+    if common_name != Some(station_id.as_str()) {
+        return Err(format!(
+            "CSR common name {common_name:?} is not the station ID"
+        ));
+    }
+    let issued_at = Utc::now();
+    let expires_at = issued_at + CERTIFICATE_VALIDITY;
+    let mut serial_number = Uuid::new_v4().into_bytes();
+    // Positive, as RFC 5280 requires
+    serial_number[0] &= 0x7f;
+    csr.params.serial_number = Some(SerialNumber::from_slice(&serial_number));
+    csr.params.not_before = offset_date_time(issued_at);
+    csr.params.not_after = offset_date_time(expires_at);
+    csr.params.is_ca = IsCa::ExplicitNoCa;
+    csr.params.extended_key_usages = vec![ExtendedKeyUsagePurpose::ClientAuth];
+    let certificate = csr
+        .signed_by(&ca.certificate, &ca.key_pair)
+        .map_err(|err| format!("failed to sign the CSR: {err}"))?;
+    Ok(IssuedCertificate {
+        station_id: station_id.clone(),
+        serial_number: serial_number
+            .iter()
+            .map(|byte| format!("{byte:02X}"))
+            .collect(),
+        certificate: certificate.pem(),
+        certificate_chain: certificate.pem() + &ca.certificate_pem,
+        issued_at,
+        expires_at,
+    })
+}
+
+/// Store the certificate and send it to the charger with a CertificateSigned. Waits for the
+/// response of the charger, so it must run outside of the message loop of the connection
+pub async fn install(certificate: IssuedCertificate) {
+    let station_id = &certificate.station_id;
+    // Stored even if the charger rejects it, as it was issued
+    if let Some(_permit) = db::ocpp_permit("storing a charger certificate").await {
+        let new_certificate = db::NewChargerCertificate {
+            station_id,
+            serial_number: &certificate.serial_number,
+            certificate: &certificate.certificate,
+            issued_at: certificate.issued_at,
+            expires_at: certificate.expires_at,
+        };
+        if let Err(err) = db::insert_charger_certificate(&new_certificate).await {
+            error!(
+                "Failed to store certificate {} of {station_id}: {err:?}",
+                certificate.serial_number
+            );
+        }
+    }
+    let request = CertificateSignedRequest {
+        certificate_chain: certificate.certificate_chain,
+    };
+    match commands::send_call::<_, CertificateSignedResponse>(
+        station_id,
+        OcppActionEnum::CertificateSigned,
+        &request,
+    )
+    .await
+    {
+        Ok(CertificateSignedResponse {
+            status: CertificateSignedStatus::Accepted,
+        }) => info!(
+            "{station_id} installed certificate {}",
+            certificate.serial_number
+        ),
+        Ok(CertificateSignedResponse {
+            status: CertificateSignedStatus::Rejected,
+        }) => warn!(
+            "{station_id} rejected certificate {}",
+            certificate.serial_number
+        ),
+        Err(err) => warn!(
+            "Failed to send certificate {} to {station_id}: {err}",
+            certificate.serial_number
+        ),
+    }
+}
+
+fn offset_date_time(date_time: DateTime<Utc>) -> OffsetDateTime {
+    OffsetDateTime::from_unix_timestamp(date_time.timestamp()).unwrap()
+}
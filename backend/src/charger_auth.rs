@@ -0,0 +1,93 @@
+use axum::{
+    extract::{Path, Request},
+    http::{header, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use axum_extra::TypedHeader;
+use dotenvy_macro::dotenv;
+use headers::{authorization::Basic, Authorization};
+use sha2::{Digest, Sha256};
+use subtle::ConstantTimeEq;
+use tracing::{error, warn};
+use uuid::Uuid;
+
+use crate::{db, StationId};
+
+pub fn basic_auth_enabled() -> bool {
+    const OCPP_BASIC_AUTH: &str = dotenv!("OCPP_BASIC_AUTH");
+    OCPP_BASIC_AUTH == "true"
+}
+
+fn hash_auth_key(salt: &str, auth_key: &str) -> String {
+    Sha256::digest(format!("{salt}{auth_key}"))
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// Credentials storing the AuthorizationKey under a new random salt
+pub fn credentials(auth_key: &str) -> db::ChargerCredentials {
+    let auth_key_salt = Uuid::new_v4().simple().to_string();
+    db::ChargerCredentials {
+        auth_key_hash: hash_auth_key(&auth_key_salt, auth_key),
+        auth_key_salt,
+    }
+}
+
+fn unauthorized() -> Response {
+    (
+        StatusCode::UNAUTHORIZED,
+        [(header::WWW_AUTHENTICATE, "Basic realm=\"OCPP\"")],
+    )
+        .into_response()
+}
+
+/// HTTP Basic Auth of the chargers, OCPP 1.6 security profile 1: the username is the station ID
+/// and the password its AuthorizationKey. Runs as a layer of the WebSocket route, so a rejected
+/// charger gets a 401 before the upgrade and no connection task is spawned. Also a layer of the
+/// SOAP route. Enabled by `OCPP_BASIC_AUTH`
+pub async fn require_basic_auth(
+    Path(station_id): Path<StationId>,
+    authorization: Option<TypedHeader<Authorization<Basic>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    if !basic_auth_enabled() {
+        return next.run(request).await;
+    }
+    let Some(TypedHeader(Authorization(basic))) = authorization else {
+        warn!("Rejected connection of {station_id}: no Basic Auth credentials");
+        return unauthorized();
+    };
+    if basic.username() != station_id {
+        warn!("Rejected connection of {station_id}: username is not the station ID");
+        return unauthorized();
+    }
+    let credentials = {
+        let Some(_permit) = db::ocpp_permit("checking the charger credentials").await else {
+            return StatusCode::SERVICE_UNAVAILABLE.into_response();
+        };
+        match db::charger_credentials(&station_id).await {
+            Ok(credentials) => credentials,
+            Err(err) => {
+                error!("Failed to load credentials of {station_id}: {err:?}");
+                return StatusCode::INTERNAL_SERVER_ERROR.into_response();
+            },
+        }
+    };
+    let Some(credentials) = credentials else {
+        warn!("Rejected connection of {station_id}: the charger has no AuthorizationKey");
+        return unauthorized();
+    };
+    let auth_key_hash = hash_auth_key(&credentials.auth_key_salt, basic.password());
+    if !bool::from(
+        auth_key_hash
+            .as_bytes()
+            .ct_eq(credentials.auth_key_hash.as_bytes()),
+    ) {
+        warn!("Rejected connection of {station_id}: wrong AuthorizationKey");
+        return unauthorized();
+    }
+    next.run(request).await
+}
@@ -0,0 +1,204 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::future;
+use rust_ocpp::v1_6::{
+    messages::{
+        change_configuration::{ChangeConfigurationRequest, ChangeConfigurationResponse},
+        clear_cache::{ClearCacheRequest, ClearCacheResponse},
+        update_firmware::{UpdateFirmwareRequest, UpdateFirmwareResponse},
+    },
+    types::{ClearCacheStatus, ConfigurationStatus},
+};
+use tracing::{error, info, warn, Instrument, Span};
+use uuid::Uuid;
+
+use crate::{commands, db, OcppActionEnum, StationId};
+
+/// How long a completed job can still be polled
+const JOB_RETENTION: TimeDelta = TimeDelta::hours(1);
+
+/// Jobs of the actions sent to charger groups, polled by the clients for completion
+static JOBS: LazyLock<Mutex<HashMap<Uuid, GroupJob>>> = LazyLock::new(Default::default);
+
+/// Action sent to every charger of a group
+#[derive(Debug, Clone, PartialEq)]
+pub enum GroupAction {
+    ClearCache,
+    ChangeConfiguration(ChangeConfigurationRequest),
+    UpdateFirmware(UpdateFirmwareRequest),
+}
+
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum OutcomeStatus {
+    Pending,
+    Succeeded,
+    Failed,
+}
+
+/// Result of the action on a charger of the group
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct ChargerOutcome {
+    pub status: OutcomeStatus,
+    /// Status responded by the charger, or why the action failed
+    pub detail: Option<String>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct GroupJob {
+    pub job_id: Uuid,
+    pub group_id: i32,
+    pub action_type: String,
+    pub created_at: DateTime<Utc>,
+    /// `None` while some chargers have not responded yet
+    pub completed_at: Option<DateTime<Utc>>,
+    pub chargers: BTreeMap<StationId, ChargerOutcome>,
+}
+
+/// Job of the given ID, `None` when it is unknown or expired
+pub fn job(job_id: Uuid) -> Option<GroupJob> {
+    JOBS.lock()
+        .unwrap()
+        .get(&job_id)
+        .cloned()
+}
+
+/// Send the action to all the chargers of the group at once, in the background. The returned job
+/// is updated as the chargers respond
+pub fn start(
+    group_id: i32,
+    action_type: &str,
+    action: GroupAction,
+    station_ids: Vec<StationId>,
+) -> GroupJob {
+    let job_id = Uuid::new_v4();
+    let job = GroupJob {
+        job_id,
+        group_id,
+        action_type: action_type.to_string(),
+        created_at: Utc::now(),
+        completed_at: station_ids.is_empty().then(Utc::now),
+        chargers: station_ids
+            .iter()
+            .map(|station_id| {
+                let outcome = ChargerOutcome {
+                    status: OutcomeStatus::Pending,
+                    detail: None,
+                };
+                (station_id.clone(), outcome)
+            })
+            .collect(),
+    };
+    {
+        let mut jobs = JOBS.lock().unwrap();
+        jobs.retain(|_, job| {
+            job.completed_at
+                .is_none_or(|completed_at| Utc::now() - completed_at < JOB_RETENTION)
+        });
+        jobs.insert(job_id, job.clone());
+    }
+    info!(
+        "Sending {action_type} to the {} chargers of group {group_id} (job {job_id})",
+        station_ids.len()
+    );
+    tokio::spawn(
+        async move {
+            future::join_all(station_ids.iter().map(|station_id| {
+                let action = &action;
+                async move {
+                    let outcome = match run(station_id, action).await {
+                        Ok(detail) => ChargerOutcome { status: OutcomeStatus::Succeeded, detail },
+                        Err(detail) => ChargerOutcome {
+                            status: OutcomeStatus::Failed,
+                            detail: Some(detail),
+                        },
+                    };
+                    if let Some(job) = JOBS.lock().unwrap().get_mut(&job_id) {
+                        job.chargers
+                            .insert(station_id.clone(), outcome);
+                    }
+                }
+            }))
+            .await;
+            if let Some(job) = JOBS.lock().unwrap().get_mut(&job_id) {
+                job.completed_at = Some(Utc::now());
+            }
+        }
+        .instrument(Span::current()),
+    );
+    job
+}
+
+/// Send the action to a charger. Returns the status responded by the charger, or why it failed
+async fn run(station_id: &StationId, action: &GroupAction) -> Result<Option<String>, String> {
+    match action {
+        GroupAction::ClearCache => {
+            let response: ClearCacheResponse = commands::send_call(
+                station_id,
+                OcppActionEnum::ClearCache,
+                &ClearCacheRequest {},
+            )
+            .await
+            .map_err(|err| err.to_string())?;
+            match response.status {
+                ClearCacheStatus::Accepted => Ok(Some("Accepted".to_string())),
+                ClearCacheStatus::Rejected => Err("Rejected".to_string()),
+            }
+        },
+        GroupAction::ChangeConfiguration(request) => {
+            let response: ChangeConfigurationResponse =
+                commands::send_call(station_id, OcppActionEnum::ChangeConfiguration, request)
+                    .await
+                    .map_err(|err| err.to_string())?;
+            match response.status {
+                ConfigurationStatus::Accepted => Ok(Some("Accepted".to_string())),
+                ConfigurationStatus::RebootRequired => Ok(Some("RebootRequired".to_string())),
+                ConfigurationStatus::Rejected => Err("Rejected".to_string()),
+                ConfigurationStatus::NotSupported => Err("NotSupported".to_string()),
+            }
+        },
+        GroupAction::UpdateFirmware(request) => {
+            let _: UpdateFirmwareResponse =
+                commands::send_call(station_id, OcppActionEnum::UpdateFirmware, request)
+                    .await
+                    .map_err(|err| err.to_string())?;
+            Ok(None)
+        },
+    }
+}
+
+/// Send the configuration intended by its groups to a charger that just booted, as it may have
+/// missed the last changes while offline or joined a group after them. Waits for the responses of
+/// the charger, so it must run outside of the message loop of the connection
+pub async fn reapply_configuration(station_id: StationId) {
+    let configuration = {
+        let Some(_permit) = db::ocpp_permit("loading the group configuration").await else {
+            return;
+        };
+        match db::intended_configuration(&station_id).await {
+            Ok(configuration) => configuration,
+            Err(err) => {
+                error!("Failed to load group configuration of {station_id}: {err:?}");
+                return;
+            },
+        }
+    };
+    for configuration_key in configuration {
+        let key = configuration_key.key;
+        let request = ChangeConfigurationRequest {
+            key: key.clone(),
+            value: configuration_key.value,
+        };
+        match run(&station_id, &GroupAction::ChangeConfiguration(request)).await {
+            Ok(status) => info!(
+                "Reapplied group configuration {key} on {station_id}: {}",
+                status.unwrap_or_default()
+            ),
+            Err(err) => warn!("Failed to reapply group configuration {key} on {station_id}: {err}"),
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use std::net::{IpAddr, SocketAddr};
+
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts},
+    http::{request::Parts, HeaderMap, StatusCode},
+};
+use dotenvy_macro::dotenv;
+use tracing::warn;
+
+/// IP address of the client. Behind a reverse proxy, the connection comes from the proxy, so the
+/// address is read from the `X-Forwarded-For` or `X-Real-Ip` header set by the proxy when
+/// `BEHIND_PROXY` is `true`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ClientIp(pub IpAddr);
+
+fn behind_proxy() -> bool {
+    const BEHIND_PROXY: &str = dotenv!("BEHIND_PROXY");
+    BEHIND_PROXY == "true"
+}
+
+/// Address the proxy received the request from. The proxy appends it to `X-Forwarded-For`, so
+/// the last address is the only one the client cannot forge
+fn forwarded_ip(headers: &HeaderMap) -> Option<IpAddr> {
+    let forwarded_for = headers
+        .get_all("x-forwarded-for")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .last();
+    let real_ip = headers
+        .get("x-real-ip")
+        .and_then(|value| value.to_str().ok());
+    let forwarded = forwarded_for.or(real_ip)?.trim();
+    // Anything else than an IP address is a misconfigured proxy or an injection attempt
+    match forwarded.parse() {
+        Ok(ip) => Some(ip),
+        Err(_) => {
+            warn!("Ignoring invalid forwarded client IP {forwarded:?}");
+            None
+        },
+    }
+}
+
+#[async_trait]
+impl<S: Send + Sync> FromRequestParts<S> for ClientIp {
+    type Rejection = StatusCode;
+
+    async fn from_request_parts(parts: &mut Parts, _: &S) -> Result<Self, Self::Rejection> {
+        if behind_proxy()
+            && let Some(ip) = forwarded_ip(&parts.headers)
+        {
+            return Ok(ClientIp(ip));
+        }
+        parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| ClientIp(addr.ip()))
+            .ok_or(StatusCode::INTERNAL_SERVER_ERROR)
+    }
+}
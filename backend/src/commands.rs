@@ -0,0 +1,369 @@
+use std::{
+    collections::{BTreeSet, HashMap, VecDeque},
+    fmt,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use dotenvy_macro::dotenv;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::sync::{mpsc, oneshot};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::{
+    call_direction::{self, MessageSender},
+    capabilities::ChargerCapabilities,
+    mask::Masked,
+    ocpp_version::OcppVersion,
+    MessageTypeId, OcppActionEnum, OcppMessageId, OcppMessageType, StationId,
+};
+
+/// Outbound channel of every connected charger, used to send server-initiated Calls
+static CHARGER_REGISTRY: LazyLock<Mutex<HashMap<StationId, RegisteredCharger>>> =
+    LazyLock::new(Default::default);
+
+struct RegisteredCharger {
+    /// WebSocket connection the channel belongs to
+    connection_id: Uuid,
+    /// The Calls are OCPP 1.6 ones, they are only sent to the chargers of that version
+    version: OcppVersion,
+    sender: mpsc::UnboundedSender<String>,
+    /// MessageIds of the last Calls received on the connection
+    message_id_log: MessageIdLog,
+    /// Server time of the last Heartbeat received on the connection
+    last_heartbeat_server_time: Option<DateTime<Utc>>,
+    /// Interval the charger sends its Heartbeats at, the one given in the BootNotification
+    /// response until its HeartbeatInterval is read or changed
+    heartbeat_interval: TimeDelta,
+    heartbeat_count: u32,
+    /// Whether the last Heartbeat was already reported as overdue
+    heartbeat_late_reported: bool,
+    /// Capabilities of the model given in the BootNotification of the connection, `None` before it
+    /// or when the model is unknown
+    capabilities: Option<ChargerCapabilities>,
+}
+
+/// Most MessageIds remembered per connection to detect duplicate Calls
+const MESSAGE_ID_LOG_SIZE: usize = 1000;
+
+#[derive(Default)]
+struct MessageIdLog {
+    message_ids: BTreeSet<OcppMessageId>,
+    /// The same MessageIds, the oldest first
+    order: VecDeque<OcppMessageId>,
+}
+
+/// Calls sent to the chargers that are still waiting for their CallResult or CallError
+static PENDING_CALLS: LazyLock<Mutex<HashMap<OcppMessageId, PendingCall>>> =
+    LazyLock::new(Default::default);
+
+struct PendingCall {
+    station_id: StationId,
+    action: OcppActionEnum,
+    sender: oneshot::Sender<Result<serde_json::Value, OcppError>>,
+}
+
+/// Failure of a server-initiated Call
+#[derive(Debug)]
+pub enum OcppError {
+    /// The charger is not connected to the server
+    NotConnected,
+    /// The charger is connected with an OCPP version the server cannot send Calls in
+    UnsupportedVersion(OcppVersion),
+    /// The charger did not respond within `OCPP_CALL_TIMEOUT_SECS`
+    Timeout,
+    /// The charger responded with a CallError
+    CallError {
+        error_code: String,
+        error_description: String,
+    },
+    /// The CallResult payload does not match the expected response
+    InvalidResponse(serde_json::Error),
+}
+
+impl fmt::Display for OcppError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            OcppError::NotConnected => write!(f, "Charger is not connected"),
+            OcppError::UnsupportedVersion(version) => write!(
+                f,
+                "Charger is connected with {}, only OCPP 1.6 Calls can be sent",
+                version.protocol()
+            ),
+            OcppError::Timeout => write!(f, "Charger did not respond in time"),
+            OcppError::CallError { error_code, error_description } => write!(
+                f,
+                "Charger responded with {error_code}: {error_description}"
+            ),
+            OcppError::InvalidResponse(err) => write!(f, "Invalid response from charger: {err}"),
+        }
+    }
+}
+
+pub fn register_charger(
+    station_id: &StationId,
+    connection_id: Uuid,
+    version: OcppVersion,
+    sender: mpsc::UnboundedSender<String>,
+) {
+    let replaced = CHARGER_REGISTRY.lock().unwrap().insert(
+        station_id.clone(),
+        RegisteredCharger {
+            connection_id,
+            version,
+            sender,
+            message_id_log: MessageIdLog::default(),
+            last_heartbeat_server_time: None,
+            heartbeat_interval: TimeDelta::seconds(crate::HEARTBEAT_INTERVAL_SECS.into()),
+            heartbeat_count: 0,
+            heartbeat_late_reported: false,
+            capabilities: None,
+        },
+    );
+    if let Some(replaced) = replaced {
+        warn!(
+            "Charger {station_id} connected again, replacing connection {}",
+            replaced.connection_id
+        );
+    }
+}
+
+/// Remove the charger from the registry, unless it already reconnected with a new connection
+pub fn unregister_charger(station_id: &StationId, connection_id: Uuid) {
+    let mut registry = CHARGER_REGISTRY.lock().unwrap();
+    if registry
+        .get(station_id)
+        .is_some_and(|registered| registered.connection_id == connection_id)
+    {
+        registry.remove(station_id);
+    }
+}
+
+/// Record the MessageId of a Call received from the charger. Returns `false` when one of the last
+/// 1000 Calls of the connection had the same MessageId, which OCPP 1.6 forbids within a session.
+/// Chargers without a connection, as over SOAP, have no session to check
+pub fn record_call_message_id(station_id: &StationId, message_id: &OcppMessageId) -> bool {
+    let mut registry = CHARGER_REGISTRY.lock().unwrap();
+    let Some(registered) = registry.get_mut(station_id) else {
+        return true;
+    };
+    let log = &mut registered.message_id_log;
+    if !log
+        .message_ids
+        .insert(message_id.clone())
+    {
+        return false;
+    }
+    log.order.push_back(message_id.clone());
+    if log.order.len() > MESSAGE_ID_LOG_SIZE
+        && let Some(oldest) = log.order.pop_front()
+    {
+        log.message_ids.remove(&oldest);
+    }
+    true
+}
+
+/// Record a Heartbeat received from the charger at the given server time. Returns how many
+/// Heartbeats the connection sent, this one included, the time since the previous one and the
+/// interval of the charger
+pub fn record_heartbeat(
+    station_id: &StationId,
+    server_time: DateTime<Utc>,
+) -> Option<(u32, TimeDelta, TimeDelta)> {
+    let mut registry = CHARGER_REGISTRY.lock().unwrap();
+    let registered = registry.get_mut(station_id)?;
+    registered.heartbeat_count += 1;
+    registered.heartbeat_late_reported = false;
+    let last_heartbeat_server_time = registered
+        .last_heartbeat_server_time
+        .replace(server_time)?;
+    Some((
+        registered.heartbeat_count,
+        server_time - last_heartbeat_server_time,
+        registered.heartbeat_interval,
+    ))
+}
+
+/// Set the interval the charger sends its Heartbeats at, once it reported or accepted it
+pub fn set_heartbeat_interval(station_id: &StationId, heartbeat_interval: TimeDelta) {
+    if let Some(registered) = CHARGER_REGISTRY
+        .lock()
+        .unwrap()
+        .get_mut(station_id)
+    {
+        registered.heartbeat_interval = heartbeat_interval;
+    }
+}
+
+/// Chargers whose Heartbeat is overdue at the given server time by more than `grace` of their own
+/// interval, with the time of their last Heartbeat and their interval. Each Heartbeat is returned
+/// once. Chargers that never sent one are left out, OCPP-J does not require them
+pub fn late_heartbeats(
+    now: DateTime<Utc>,
+    grace: impl Fn(TimeDelta) -> TimeDelta,
+) -> Vec<(StationId, DateTime<Utc>, TimeDelta)> {
+    CHARGER_REGISTRY
+        .lock()
+        .unwrap()
+        .iter_mut()
+        .filter_map(|(station_id, registered)| {
+            let last_heartbeat = registered.last_heartbeat_server_time?;
+            let heartbeat_interval = registered.heartbeat_interval;
+            if now - last_heartbeat <= heartbeat_interval + grace(heartbeat_interval)
+                || registered.heartbeat_late_reported
+            {
+                return None;
+            }
+            registered.heartbeat_late_reported = true;
+            Some((station_id.clone(), last_heartbeat, heartbeat_interval))
+        })
+        .collect()
+}
+
+pub fn set_capabilities(station_id: &StationId, capabilities: Option<ChargerCapabilities>) {
+    if let Some(registered) = CHARGER_REGISTRY
+        .lock()
+        .unwrap()
+        .get_mut(station_id)
+    {
+        registered.capabilities = capabilities;
+    }
+}
+
+/// Capabilities of the connected charger, `None` when they are unknown
+pub fn capabilities(station_id: &StationId) -> Option<ChargerCapabilities> {
+    CHARGER_REGISTRY
+        .lock()
+        .unwrap()
+        .get(station_id)?
+        .capabilities
+        .clone()
+}
+
+/// Number of chargers with an open WebSocket connection
+pub fn connected_chargers() -> usize { CHARGER_REGISTRY.lock().unwrap().len() }
+
+pub fn is_connected(station_id: &StationId) -> bool {
+    CHARGER_REGISTRY
+        .lock()
+        .unwrap()
+        .contains_key(station_id)
+}
+
+/// Send a Call to a charger and wait for its response, for at most `OCPP_CALL_TIMEOUT_SECS`
+pub async fn send_call<Request, Response>(
+    station_id: &StationId,
+    action: OcppActionEnum,
+    payload: &Request,
+) -> Result<Response, OcppError>
+where
+    Request: Serialize,
+    Response: DeserializeOwned,
+{
+    const OCPP_CALL_TIMEOUT_SECS: &str = dotenv!("OCPP_CALL_TIMEOUT_SECS");
+    let call_timeout = Duration::from_secs(
+        OCPP_CALL_TIMEOUT_SECS
+            .parse()
+            .expect("OCPP_CALL_TIMEOUT_SECS must be a number of seconds"),
+    );
+
+    debug_assert!(
+        call_direction::validate_call_direction(&action, MessageSender::Server),
+        "{action} is not a Call of a server"
+    );
+    let (connection_id, version, charger) = CHARGER_REGISTRY
+        .lock()
+        .unwrap()
+        .get(station_id)
+        .map(|registered| {
+            (
+                registered.connection_id,
+                registered.version,
+                registered.sender.clone(),
+            )
+        })
+        .ok_or(OcppError::NotConnected)?;
+    if version != OcppVersion::V16 {
+        return Err(OcppError::UnsupportedVersion(version));
+    }
+    let message_id = Uuid::new_v4().to_string();
+    let call = OcppMessageType::Call(
+        MessageTypeId::CALL,
+        message_id.clone(),
+        action.to_string(),
+        serde_json::to_value(payload).expect("OCPP requests serialize to JSON"),
+    );
+    let (sender, receiver) = oneshot::channel();
+    PENDING_CALLS.lock().unwrap().insert(
+        message_id.clone(),
+        PendingCall {
+            station_id: station_id.clone(),
+            action: action.clone(),
+            sender,
+        },
+    );
+    if charger
+        .send(serde_json::to_string(&call).unwrap())
+        .is_err()
+    {
+        PENDING_CALLS
+            .lock()
+            .unwrap()
+            .remove(&message_id);
+        return Err(OcppError::NotConnected);
+    }
+    info!(
+        %connection_id,
+        "Sent OCPP {action} Call to {station_id}: {}",
+        Masked(&call)
+    );
+
+    match tokio::time::timeout(call_timeout, receiver).await {
+        Ok(Ok(response)) => serde_json::from_value(response?).map_err(OcppError::InvalidResponse),
+        // The pending call was dropped without a response
+        Ok(Err(_)) => Err(OcppError::NotConnected),
+        Err(_) => {
+            PENDING_CALLS
+                .lock()
+                .unwrap()
+                .remove(&message_id);
+            warn!(
+                station_id,
+                %connection_id,
+                %action,
+                message_id,
+                "Charger did not respond to the Call in time"
+            );
+            Err(OcppError::Timeout)
+        },
+    }
+}
+
+/// Resolve the pending Call matching a CallResult or CallError received from a charger. Returns
+/// `false` when no Call is waiting for it
+pub fn complete_call(
+    station_id: &StationId,
+    message_id: &OcppMessageId,
+    response: Result<serde_json::Value, OcppError>,
+) -> bool {
+    let mut pending_calls = PENDING_CALLS.lock().unwrap();
+    // A charger can only answer the Calls that were sent to it
+    if pending_calls
+        .get(message_id)
+        .is_none_or(|pending_call| &pending_call.station_id != station_id)
+    {
+        return false;
+    }
+    let pending_call = pending_calls
+        .remove(message_id)
+        .unwrap();
+    debug!(
+        "Received response to {} Call {message_id} from {station_id}",
+        pending_call.action
+    );
+    // The caller may have given up waiting already
+    let _ = pending_call.sender.send(response);
+    true
+}
@@ -0,0 +1,172 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, RwLock},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::{
+    messages::{
+        change_configuration::{ChangeConfigurationRequest, ChangeConfigurationResponse},
+        get_configuration::{GetConfigurationRequest, GetConfigurationResponse},
+    },
+    types::{ConfigurationStatus, KeyValue},
+};
+use tracing::{info, warn};
+
+use crate::{
+    commands::{self, OcppError},
+    OcppActionEnum, StationId,
+};
+
+/// Configuration keys last reported by each charger in a GetConfiguration response
+static CONFIGURATIONS: LazyLock<RwLock<HashMap<StationId, CachedConfiguration>>> =
+    LazyLock::new(Default::default);
+
+#[derive(Debug, Clone)]
+struct CachedConfiguration {
+    keys: Vec<KeyValue>,
+    fetched_at: DateTime<Utc>,
+}
+
+impl CachedConfiguration {
+    fn is_stale(&self) -> bool {
+        const CONFIG_CACHE_TTL_SECS: &str = dotenv!("CONFIG_CACHE_TTL_SECS");
+        let ttl = TimeDelta::seconds(
+            CONFIG_CACHE_TTL_SECS
+                .parse()
+                .expect("CONFIG_CACHE_TTL_SECS must be a number of seconds"),
+        );
+        Utc::now() - self.fetched_at > ttl
+    }
+}
+
+/// Configuration of a charger, refreshed with a GetConfiguration when the cached one is older than
+/// `CONFIG_CACHE_TTL_SECS` and the charger is connected. Returns `None` when the configuration was
+/// never fetched and the charger is not connected
+pub async fn configuration(station_id: &StationId) -> Result<Option<Vec<KeyValue>>, OcppError> {
+    let cached = CONFIGURATIONS
+        .read()
+        .unwrap()
+        .get(station_id)
+        .cloned();
+    if let Some(cached) = &cached
+        && !cached.is_stale()
+    {
+        return Ok(Some(cached.keys.clone()));
+    }
+    match refresh(station_id).await {
+        Ok(keys) => Ok(Some(keys)),
+        Err(OcppError::NotConnected) => Ok(cached.map(|cached| cached.keys)),
+        // Better stale than nothing
+        Err(err) if cached.is_some() => {
+            warn!("Failed to refresh configuration of {station_id}, serving the cached one: {err}");
+            Ok(cached.map(|cached| cached.keys))
+        },
+        Err(err) => Err(err),
+    }
+}
+
+/// Fetch every configuration key of the charger and cache them
+async fn refresh(station_id: &StationId) -> Result<Vec<KeyValue>, OcppError> {
+    let response: GetConfigurationResponse = commands::send_call(
+        station_id,
+        OcppActionEnum::GetConfiguration,
+        &GetConfigurationRequest { key: None },
+    )
+    .await?;
+    let keys = response
+        .configuration_key
+        .unwrap_or_default();
+    if let Some(heartbeat_interval) = keys
+        .iter()
+        .find(|key_value| key_value.key == "HeartbeatInterval")
+        .and_then(|key_value| heartbeat_interval(key_value.value.as_deref()?))
+    {
+        commands::set_heartbeat_interval(station_id, heartbeat_interval);
+    }
+    CONFIGURATIONS.write().unwrap().insert(
+        station_id.clone(),
+        CachedConfiguration {
+            keys: keys.clone(),
+            fetched_at: Utc::now(),
+        },
+    );
+    Ok(keys)
+}
+
+/// Set a configuration key of the charger, and in the cached configuration once the charger
+/// accepted it
+pub async fn change(
+    station_id: &StationId,
+    key: &str,
+    value: &str,
+) -> Result<ChangeConfigurationResponse, OcppError> {
+    let request = ChangeConfigurationRequest {
+        key: key.to_string(),
+        value: value.to_string(),
+    };
+    let response: ChangeConfigurationResponse =
+        commands::send_call(station_id, OcppActionEnum::ChangeConfiguration, &request).await?;
+    if matches!(
+        response.status,
+        ConfigurationStatus::Accepted | ConfigurationStatus::RebootRequired
+    ) && let Some(cached) = CONFIGURATIONS
+        .write()
+        .unwrap()
+        .get_mut(station_id)
+        && let Some(key_value) = cached
+            .keys
+            .iter_mut()
+            .find(|key_value| key_value.key == key)
+    {
+        key_value.value = Some(value.to_string());
+    }
+    if key == "HeartbeatInterval"
+        && response.status == ConfigurationStatus::Accepted
+        && let Some(heartbeat_interval) = heartbeat_interval(value)
+    {
+        commands::set_heartbeat_interval(station_id, heartbeat_interval);
+    }
+    if key == "ClockAlignedDataInterval"
+        && response.status != ConfigurationStatus::Rejected
+        && let Ok(interval) = clock_aligned_data_interval(value)
+    {
+        match interval {
+            Some(interval) => {
+                info!("{station_id} sends clock-aligned MeterValues every {interval:?}")
+            },
+            None => info!("{station_id} no longer sends clock-aligned MeterValues"),
+        }
+    }
+    Ok(response)
+}
+
+/// Check the value of a configuration key before sending it to a charger, for the keys whose
+/// format OCPP defines. Returns why it is invalid otherwise
+pub fn validate(key: &str, value: &str) -> Result<(), String> {
+    match key {
+        "ClockAlignedDataInterval" => clock_aligned_data_interval(value).map(|_| ()),
+        _ => Ok(()),
+    }
+}
+
+/// Interval of the Heartbeats of the charger, `None` when the value is not a positive number of
+/// seconds
+fn heartbeat_interval(value: &str) -> Option<TimeDelta> {
+    value
+        .parse::<u32>()
+        .ok()
+        .filter(|secs| *secs > 0)
+        .map(|secs| TimeDelta::seconds(secs.into()))
+}
+
+/// Interval of the MeterValues the charger sends aligned to the clock, e.g. at :00, :15, :30 and
+/// :45 for 900 seconds. `None` when it is 0, which disables them
+pub fn clock_aligned_data_interval(value: &str) -> Result<Option<Duration>, String> {
+    let secs: u64 = value.parse().map_err(|_| {
+        format!("ClockAlignedDataInterval must be a non-negative number of seconds, not {value:?}")
+    })?;
+    Ok((secs > 0).then(|| Duration::from_secs(secs)))
+}
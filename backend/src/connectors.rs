@@ -0,0 +1,240 @@
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, Utc};
+use rust_ocpp::v1_6::types::{AvailabilityStatus, AvailabilityType, ChargePointStatus};
+
+use crate::StationId;
+
+pub type ConnectorId = u32;
+
+/// Connector ID addressing the whole charge point instead of a single connector
+pub const CHARGE_POINT_CONNECTOR_ID: ConnectorId = 0;
+
+/// Status of a connector, displayed with its OCPP name, e.g. `SuspendedEV`
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectorStatus(pub ChargePointStatus);
+
+impl fmt::Display for ConnectorStatus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self.0 {
+            ChargePointStatus::Available => "Available",
+            ChargePointStatus::Preparing => "Preparing",
+            ChargePointStatus::Charging => "Charging",
+            ChargePointStatus::SuspendedEVSE => "SuspendedEVSE",
+            ChargePointStatus::SuspendedEV => "SuspendedEV",
+            ChargePointStatus::Finishing => "Finishing",
+            ChargePointStatus::Reserved => "Reserved",
+            ChargePointStatus::Unavailable => "Unavailable",
+            ChargePointStatus::Faulted => "Faulted",
+        };
+        f.write_str(name)
+    }
+}
+
+impl serde::Serialize for ConnectorStatus {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Last known state of a connector
+#[derive(Debug, Clone, PartialEq)]
+pub struct ConnectorState {
+    pub status: ChargePointStatus,
+    pub availability: AvailabilityType,
+    /// Availability to apply once the ongoing transaction finishes
+    pub scheduled_availability: Option<AvailabilityType>,
+    pub since: DateTime<Utc>,
+}
+
+impl Default for ConnectorState {
+    fn default() -> Self {
+        Self {
+            status: ChargePointStatus::Available,
+            availability: AvailabilityType::Operative,
+            scheduled_availability: None,
+            since: Utc::now(),
+        }
+    }
+}
+
+impl ConnectorState {
+    /// Whether the connector is in one of the states a transaction runs in
+    pub fn has_active_transaction(&self) -> bool {
+        matches!(
+            self.status,
+            ChargePointStatus::Charging
+                | ChargePointStatus::SuspendedEV
+                | ChargePointStatus::SuspendedEVSE
+        )
+    }
+}
+
+static CONNECTORS: LazyLock<Mutex<HashMap<(StationId, ConnectorId), ConnectorState>>> =
+    LazyLock::new(Default::default);
+
+/// Status of a connector, as returned by the REST API
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct ConnectorSummary {
+    pub connector_id: ConnectorId,
+    pub status: ConnectorStatus,
+    /// When the connector entered the status
+    pub since: DateTime<Utc>,
+}
+
+/// Connectors of the station that reported their status, by connector ID
+pub fn connectors(station_id: &StationId) -> Vec<ConnectorSummary> {
+    let mut connectors: Vec<_> = CONNECTORS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|((station, _), _)| station == station_id)
+        .map(|((_, connector_id), connector)| ConnectorSummary {
+            connector_id: *connector_id,
+            status: ConnectorStatus(connector.status.clone()),
+            since: connector.since,
+        })
+        .collect();
+    connectors.sort_by_key(|connector| connector.connector_id);
+    connectors
+}
+
+/// Last status reported by the connector, `None` when it never reported one
+pub fn status(station_id: &StationId, connector_id: ConnectorId) -> Option<ChargePointStatus> {
+    CONNECTORS
+        .lock()
+        .unwrap()
+        .get(&(station_id.clone(), connector_id))
+        .map(|connector| connector.status.clone())
+}
+
+/// Update the connector status reported by a StatusNotification, applying the availability that
+/// was scheduled while a transaction was running
+pub fn update_status(station_id: &StationId, connector_id: ConnectorId, status: ChargePointStatus) {
+    let mut connectors = CONNECTORS.lock().unwrap();
+    let connector = connectors
+        .entry((station_id.clone(), connector_id))
+        .or_default();
+    connector.status = status;
+    connector.since = Utc::now();
+    if !connector.has_active_transaction()
+        && let Some(availability) = connector.scheduled_availability.take()
+    {
+        connector.availability = availability;
+    }
+}
+
+/// Change the availability of a connector, or of every known connector of the station when
+/// `connector_id` is 0. Connectors running a transaction only get the change scheduled for when
+/// the transaction finishes, in which case the whole request is reported as `Scheduled`
+pub fn change_availability(
+    station_id: &StationId,
+    connector_id: ConnectorId,
+    availability: AvailabilityType,
+) -> AvailabilityStatus {
+    let mut connectors = CONNECTORS.lock().unwrap();
+    connectors
+        .entry((station_id.clone(), connector_id))
+        .or_default();
+    let mut status = AvailabilityStatus::Accepted;
+    for ((station, id), connector) in connectors.iter_mut() {
+        if station != station_id
+            || (connector_id != CHARGE_POINT_CONNECTOR_ID && *id != connector_id)
+        {
+            continue;
+        }
+        if connector.has_active_transaction() {
+            connector.scheduled_availability = Some(availability.clone());
+            status = AvailabilityStatus::Scheduled;
+        } else {
+            connector.availability = availability.clone();
+            connector.scheduled_availability = None;
+        }
+    }
+    status
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn availability(
+        station_id: &StationId,
+        connector_id: ConnectorId,
+    ) -> (AvailabilityType, Option<AvailabilityType>) {
+        let connectors = CONNECTORS.lock().unwrap();
+        let connector = &connectors[&(station_id.clone(), connector_id)];
+        (
+            connector.availability.clone(),
+            connector.scheduled_availability.clone(),
+        )
+    }
+
+    #[test]
+    fn whole_charger() {
+        let station_id = "CHANGE-AVAILABILITY-1".to_string();
+        update_status(&station_id, 1, ChargePointStatus::Available);
+        update_status(&station_id, 2, ChargePointStatus::Available);
+        assert_eq!(
+            change_availability(&station_id, 0, AvailabilityType::Inoperative),
+            AvailabilityStatus::Accepted
+        );
+        for connector_id in [0, 1, 2] {
+            assert_eq!(
+                availability(&station_id, connector_id),
+                (AvailabilityType::Inoperative, None)
+            );
+        }
+    }
+
+    #[test]
+    fn single_connector() {
+        let station_id = "CHANGE-AVAILABILITY-2".to_string();
+        update_status(&station_id, 1, ChargePointStatus::Available);
+        update_status(&station_id, 2, ChargePointStatus::Available);
+        assert_eq!(
+            change_availability(&station_id, 2, AvailabilityType::Inoperative),
+            AvailabilityStatus::Accepted
+        );
+        assert_eq!(
+            availability(&station_id, 1),
+            (AvailabilityType::Operative, None)
+        );
+        assert_eq!(
+            availability(&station_id, 2),
+            (AvailabilityType::Inoperative, None)
+        );
+    }
+
+    #[test]
+    fn whole_charger_while_charging() {
+        let station_id = "CHANGE-AVAILABILITY-3".to_string();
+        update_status(&station_id, 1, ChargePointStatus::Charging);
+        update_status(&station_id, 2, ChargePointStatus::Available);
+        assert_eq!(
+            change_availability(&station_id, 0, AvailabilityType::Inoperative),
+            AvailabilityStatus::Scheduled
+        );
+        assert_eq!(
+            availability(&station_id, 1),
+            (
+                AvailabilityType::Operative,
+                Some(AvailabilityType::Inoperative)
+            )
+        );
+        assert_eq!(
+            availability(&station_id, 2),
+            (AvailabilityType::Inoperative, None)
+        );
+        // The scheduled change applies once the transaction finishes
+        update_status(&station_id, 1, ChargePointStatus::Finishing);
+        assert_eq!(
+            availability(&station_id, 1),
+            (AvailabilityType::Inoperative, None)
+        );
+    }
+}
@@ -0,0 +1,147 @@
+use std::collections::BTreeMap;
+
+use chrono::{DateTime, Datelike, NaiveTime, Utc};
+
+use crate::db::{self, CompletedTransaction, CostBreakdown, Tariff};
+
+/// Energy and cost of the transaction in each tariff period it charged in. The energy between two
+/// readings of the import register is spread evenly over the time between them, and split at the
+/// times the applicable tariff changes. Energy charged while no tariff applied is not priced
+pub async fn estimate(
+    transaction: &CompletedTransaction,
+) -> Result<Vec<CostBreakdown>, sqlx::Error> {
+    let tariffs = db::station_tariffs(
+        &transaction.station_id,
+        transaction.start_time,
+        transaction.stop_time,
+    )
+    .await?;
+    let mut readings = vec![(transaction.start_time, transaction.meter_start as f64)];
+    readings.extend(
+        db::import_register_readings_wh(transaction.id)
+            .await?
+            .into_iter()
+            .filter(|(timestamp, _)| {
+                transaction.start_time < *timestamp && *timestamp < transaction.stop_time
+            }),
+    );
+    readings.push((transaction.stop_time, transaction.meter_stop as f64));
+    Ok(breakdown(&tariffs, &readings))
+}
+
+fn breakdown(tariffs: &[Tariff], readings: &[(DateTime<Utc>, f64)]) -> Vec<CostBreakdown> {
+    // Energy in Wh and cost by period and currency
+    let mut periods: BTreeMap<(String, String), (f64, f64)> = BTreeMap::new();
+    for pair in readings.windows(2) {
+        let [(from, from_wh), (to, to_wh)] = pair else {
+            unreachable!("windows of 2 readings")
+        };
+        // A register that went back, e.g. after a reset of the meter, charged nothing
+        let energy_wh = (to_wh - from_wh).max(0.0);
+        let duration_ms = (*to - *from).num_milliseconds();
+        let mut segment_start = *from;
+        for segment_end in boundaries(tariffs, *from, *to)
+            .into_iter()
+            .chain([*to])
+        {
+            let segment_wh = if duration_ms > 0 {
+                energy_wh * (segment_end - segment_start).num_milliseconds() as f64
+                    / duration_ms as f64
+            } else {
+                energy_wh
+            };
+            if let Some(tariff) = applicable(tariffs, segment_start) {
+                let (period_wh, period_cost) = periods
+                    .entry((period_name(tariff), tariff.currency.clone()))
+                    .or_default();
+                *period_wh += segment_wh;
+                *period_cost += segment_wh / 1000.0 * tariff.price_per_kwh;
+            }
+            segment_start = segment_end;
+        }
+    }
+    periods
+        .into_iter()
+        .map(|((period, currency), (energy_wh, cost))| CostBreakdown {
+            period,
+            energy_wh: energy_wh.round() as i64,
+            cost,
+            currency,
+        })
+        .collect()
+}
+
+/// Name of the period of the tariff in the breakdowns
+fn period_name(tariff: &Tariff) -> String {
+    tariff
+        .period
+        .clone()
+        .unwrap_or_else(|| format!("tariff {}", tariff.id))
+}
+
+/// Tariff applicable at the time. Like for the start of a transaction, the tariffs with a time of
+/// day take precedence over the all day ones, then the ones of a day of the week over the ones of
+/// every day, and the most recent one wins among equals
+fn applicable(tariffs: &[Tariff], at: DateTime<Utc>) -> Option<&Tariff> {
+    tariffs
+        .iter()
+        .filter(|tariff| applies(tariff, at))
+        .max_by_key(|tariff| {
+            (
+                tariff.time_of_day_start.is_some(),
+                tariff.day_of_week.is_some(),
+                tariff.valid_from,
+            )
+        })
+}
+
+fn applies(tariff: &Tariff, at: DateTime<Utc>) -> bool {
+    if at < tariff.valid_from
+        || tariff
+            .valid_to
+            .is_some_and(|valid_to| at >= valid_to)
+    {
+        return false;
+    }
+    // The day of the time itself, also after midnight in a period wrapping around it
+    if tariff
+        .day_of_week
+        .is_some_and(|day_of_week| at.weekday().number_from_monday() as i16 != day_of_week)
+    {
+        return false;
+    }
+    match (tariff.time_of_day_start, tariff.time_of_day_end) {
+        (Some(start), Some(end)) if start <= end => at.time() >= start && at.time() < end,
+        (Some(start), Some(end)) => at.time() >= start || at.time() < end,
+        _ => true,
+    }
+}
+
+/// Times strictly between `from` and `to` at which the applicable tariff may change: the start
+/// and end of validity of the tariffs, of their time of day, and of each day
+fn boundaries(tariffs: &[Tariff], from: DateTime<Utc>, to: DateTime<Utc>) -> Vec<DateTime<Utc>> {
+    let mut boundaries: Vec<_> = tariffs
+        .iter()
+        .flat_map(|tariff| [Some(tariff.valid_from), tariff.valid_to])
+        .flatten()
+        .collect();
+    for day in from
+        .date_naive()
+        .iter_days()
+        .take_while(|day| *day <= to.date_naive())
+    {
+        boundaries.push(day.and_time(NaiveTime::MIN).and_utc());
+        for tariff in tariffs {
+            for time in [tariff.time_of_day_start, tariff.time_of_day_end]
+                .into_iter()
+                .flatten()
+            {
+                boundaries.push(day.and_time(time).and_utc());
+            }
+        }
+    }
+    boundaries.retain(|boundary| from < *boundary && *boundary < to);
+    boundaries.sort();
+    boundaries.dedup();
+    boundaries
+}
@@ -0,0 +1,72 @@
+use std::sync::{LazyLock, RwLock};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use tracing::warn;
+
+use crate::{
+    commands,
+    db::{self, DashboardSummary},
+};
+
+/// How long the summary is served without querying the database again
+const CACHE_TTL: TimeDelta = TimeDelta::seconds(30);
+/// Age after which a summary served because the database failed is flagged as stale
+const STALE_AFTER: TimeDelta = TimeDelta::seconds(60);
+
+/// Last computed summary, every dashboard load would query the whole fleet otherwise
+static SUMMARY_CACHE: LazyLock<RwLock<Option<CachedSummary>>> = LazyLock::new(Default::default);
+
+#[derive(Debug, Clone)]
+struct CachedSummary {
+    summary: DashboardSummary,
+    computed_at: DateTime<Utc>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Summary {
+    /// Chargers connected right now, never cached
+    pub chargers_online: usize,
+    #[serde(flatten)]
+    pub summary: DashboardSummary,
+    /// Whether the figures are older than a minute
+    pub stale: bool,
+}
+
+/// Fleet-wide figures, recomputed when the cached ones are older than 30 seconds. The cached ones
+/// are served when the database fails
+pub async fn summary() -> Result<Summary, sqlx::Error> {
+    let cached = SUMMARY_CACHE.read().unwrap().clone();
+    if let Some(cached) = &cached
+        && Utc::now() - cached.computed_at < CACHE_TTL
+    {
+        return Ok(Summary {
+            chargers_online: commands::connected_chargers(),
+            summary: cached.summary.clone(),
+            stale: false,
+        });
+    }
+    match db::dashboard_summary().await {
+        Ok(summary) => {
+            *SUMMARY_CACHE.write().unwrap() = Some(CachedSummary {
+                summary: summary.clone(),
+                computed_at: Utc::now(),
+            });
+            Ok(Summary {
+                chargers_online: commands::connected_chargers(),
+                summary,
+                stale: false,
+            })
+        },
+        Err(err) => match cached {
+            Some(cached) => {
+                warn!("Failed to compute the dashboard summary, serving the cached one: {err:?}");
+                Ok(Summary {
+                    chargers_online: commands::connected_chargers(),
+                    stale: Utc::now() - cached.computed_at > STALE_AFTER,
+                    summary: cached.summary,
+                })
+            },
+            None => Err(err),
+        },
+    }
+}
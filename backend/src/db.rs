@@ -0,0 +1,1891 @@
+use std::time::Duration;
+
+use chrono::{DateTime, NaiveTime, Utc};
+use rust_ocpp::v1_6::messages::boot_notification::BootNotificationRequest;
+use sqlx::{postgres::PgPoolOptions, types::Json, PgPool};
+use tokio::sync::{OnceCell, Semaphore, SemaphorePermit};
+use tracing::warn;
+use uuid::Uuid;
+
+static DB_POOL: OnceCell<PgPool> = OnceCell::const_new();
+
+/// Database operations the OCPP message handlers may run concurrently
+pub static OCPP_DB_SEMAPHORE: Semaphore = Semaphore::const_new(20);
+/// Database operations the export endpoints may run concurrently, so heavy exports cannot starve
+/// the OCPP message handlers
+pub static EXPORT_DB_SEMAPHORE: Semaphore = Semaphore::const_new(2);
+/// How long to wait for a permit of the semaphores before giving up
+const DB_PERMIT_TIMEOUT: Duration = Duration::from_secs(5);
+/// Enough connections for every permit of the semaphores, plus the other REST endpoints
+const MAX_DB_CONNECTIONS: u32 = 32;
+
+/// Connect to the database and run the pending migrations
+pub async fn init(database_url: &str) {
+    let pool = PgPoolOptions::new()
+        .max_connections(MAX_DB_CONNECTIONS)
+        .connect(database_url)
+        .await
+        .expect("Failed to connect to the database");
+    sqlx::migrate!()
+        .run(&pool)
+        .await
+        .expect("Failed to run database migrations");
+    DB_POOL
+        .set(pool)
+        .expect("Database pool already initialized");
+}
+
+pub fn pool() -> &'static PgPool {
+    DB_POOL
+        .get()
+        .expect("Database pool not initialized")
+}
+
+/// Wait for a permit of the semaphore, for at most 5 s
+pub async fn permit(semaphore: &'static Semaphore) -> Option<SemaphorePermit<'static>> {
+    tokio::time::timeout(DB_PERMIT_TIMEOUT, semaphore.acquire())
+        .await
+        .ok()?
+        .ok()
+}
+
+/// Permit for a database operation of an OCPP message handler. When none is available in time the
+/// handler skips the persistence step
+pub async fn ocpp_permit(operation: &str) -> Option<SemaphorePermit<'static>> {
+    let permit = permit(&OCPP_DB_SEMAPHORE).await;
+    if permit.is_none() {
+        warn!("No database connection available, skipping {operation}");
+    }
+    permit
+}
+
+/// A sampled value that could not be parsed into the OCPP 1.6 types
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterReadingRaw {
+    pub station_id: String,
+    pub connector_id: Option<i32>,
+    pub transaction_id: Option<i32>,
+    pub measurand: Option<String>,
+    pub unit: Option<String>,
+    pub sampled_value: serde_json::Value,
+}
+
+pub async fn insert_meter_reading_raw(reading: &MeterReadingRaw) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO meter_readings_raw (station_id, connector_id, transaction_id, measurand, \
+         unit, sampled_value) VALUES ($1, $2, $3, $4, $5, $6)",
+        reading.station_id,
+        reading.connector_id,
+        reading.transaction_id,
+        reading.measurand,
+        reading.unit,
+        reading.sampled_value,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Store the charger as reported by its BootNotification. Returns the firmware version that was
+/// stored before, or `None` when it is the first boot of the charger
+pub async fn upsert_charger(
+    station_id: &str,
+    boot_notification: &BootNotificationRequest,
+    firmware_version_semver: Option<&str>,
+) -> Result<Option<Option<String>>, sqlx::Error> {
+    let previous_firmware = sqlx::query_scalar!(
+        "SELECT firmware_version FROM chargers WHERE station_id = $1",
+        station_id,
+    )
+    .fetch_optional(pool())
+    .await?;
+    sqlx::query!(
+        "INSERT INTO chargers (station_id, charge_point_vendor, charge_point_model, \
+         charge_point_serial_number, charge_box_serial_number, firmware_version, \
+         firmware_version_semver, iccid, imsi, meter_type, meter_serial_number) VALUES ($1, $2, \
+         $3, $4, $5, $6, $7, $8, $9, $10, $11) ON CONFLICT (station_id) DO UPDATE SET \
+         charge_point_vendor = EXCLUDED.charge_point_vendor, charge_point_model = \
+         EXCLUDED.charge_point_model, charge_point_serial_number = \
+         EXCLUDED.charge_point_serial_number, charge_box_serial_number = \
+         EXCLUDED.charge_box_serial_number, firmware_version = EXCLUDED.firmware_version, \
+         firmware_version_semver = EXCLUDED.firmware_version_semver, iccid = EXCLUDED.iccid, imsi \
+         = EXCLUDED.imsi, meter_type = EXCLUDED.meter_type, meter_serial_number = \
+         EXCLUDED.meter_serial_number, last_boot_at = now()",
+        station_id,
+        boot_notification.charge_point_vendor,
+        boot_notification.charge_point_model,
+        boot_notification.charge_point_serial_number,
+        boot_notification.charge_box_serial_number,
+        boot_notification.firmware_version,
+        firmware_version_semver,
+        boot_notification.iccid,
+        boot_notification.imsi,
+        boot_notification.meter_type,
+        boot_notification.meter_serial_number,
+    )
+    .execute(pool())
+    .await?;
+    Ok(previous_firmware)
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct FirmwareChangeEvent {
+    pub station_id: String,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+    pub changed_at: DateTime<Utc>,
+}
+
+pub async fn insert_firmware_change_event(
+    station_id: &str,
+    old_version: Option<&str>,
+    new_version: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO firmware_change_events (station_id, old_version, new_version) VALUES ($1, \
+         $2, $3)",
+        station_id,
+        old_version,
+        new_version,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+pub async fn firmware_history(station_id: &str) -> Result<Vec<FirmwareChangeEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        FirmwareChangeEvent,
+        "SELECT station_id, old_version, new_version, changed_at FROM firmware_change_events \
+         WHERE station_id = $1 ORDER BY changed_at DESC",
+        station_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Protocol version negotiated in the last session of the charger, or `None` when the charger
+/// never connected before
+pub async fn last_protocol_version(
+    station_id: &str,
+) -> Result<Option<Option<String>>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT protocol_version FROM charger_sessions WHERE station_id = $1 ORDER BY \
+         connected_at DESC LIMIT 1",
+        station_id,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+/// Time of the first connection of the charger, `None` when it never connected
+pub async fn first_connected_at(station_id: &str) -> Result<Option<DateTime<Utc>>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT MIN(connected_at) FROM charger_sessions WHERE station_id = $1",
+        station_id,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Time the charger was connected between `since` and `until`, the overlapping sessions merged
+#[derive(Debug, Clone, PartialEq)]
+pub struct OnlineInterval {
+    pub online_from: DateTime<Utc>,
+    pub online_to: DateTime<Utc>,
+}
+
+/// Intervals the charger was connected between `since` and `until`, the earliest first. A session
+/// left open, e.g. by a crash of the server, ends when the next one starts, or at `until` only when
+/// the charger is `connected`
+pub async fn online_intervals(
+    station_id: &str,
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    connected: bool,
+) -> Result<Vec<OnlineInterval>, sqlx::Error> {
+    sqlx::query_as!(
+        OnlineInterval,
+        "SELECT lower(online) AS \"online_from!\", upper(online) AS \"online_to!\" FROM \
+         unnest((SELECT range_agg(tstzrange(GREATEST(connected_at, $2), LEAST(disconnected_at, \
+         $3))) FROM (SELECT connected_at, COALESCE(disconnected_at, LEAD(connected_at) OVER \
+         (ORDER BY connected_at), CASE WHEN $4 THEN $3 ELSE connected_at END) AS disconnected_at \
+         FROM charger_sessions WHERE station_id = $1) s WHERE disconnected_at > $2 AND \
+         connected_at < $3)) online ORDER BY 1",
+        station_id,
+        since,
+        until,
+        connected,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct OfflineCharger {
+    pub station_id: String,
+    /// End of the last session of the charger, or its start when the session was left open
+    pub last_seen: DateTime<Utc>,
+    pub offline_for_secs: i64,
+    /// Latest error code other than NoError reported in a StatusNotification
+    pub last_error: Option<String>,
+}
+
+/// Chargers whose last session ended before `seen_before`, the longest offline first. A session
+/// left open, e.g. by a crash of the server, counts as ended when it started, the callers filter
+/// out the chargers still connected
+pub async fn offline_chargers(
+    seen_before: DateTime<Utc>,
+) -> Result<Vec<OfflineCharger>, sqlx::Error> {
+    sqlx::query_as!(
+        OfflineCharger,
+        "SELECT s.station_id, s.last_seen AS \"last_seen!\", EXTRACT(EPOCH FROM now() - \
+         s.last_seen)::BIGINT AS \"offline_for_secs!\", e.error_code AS \"last_error?\" FROM \
+         (SELECT DISTINCT ON (station_id) station_id, COALESCE(disconnected_at, connected_at) AS \
+         last_seen FROM charger_sessions ORDER BY station_id, connected_at DESC) s LEFT JOIN \
+         LATERAL (SELECT error_code FROM status_notification_history WHERE station_id = \
+         s.station_id AND error_code <> 'NoError' ORDER BY timestamp DESC LIMIT 1) e ON true \
+         WHERE s.last_seen < $1 ORDER BY s.last_seen",
+        seen_before,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn open_charger_session(
+    station_id: &str,
+    connection_id: Uuid,
+    remote_addr: &str,
+    protocol_version: Option<&str>,
+) -> Result<i64, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO charger_sessions (station_id, connection_id, remote_addr, protocol_version) \
+         VALUES ($1, $2, $3, $4) RETURNING id",
+        station_id,
+        connection_id,
+        remote_addr,
+        protocol_version,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+pub async fn close_charger_session(session_id: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE charger_sessions SET disconnected_at = now() WHERE id = $1",
+        session_id,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Charger {
+    pub station_id: String,
+    pub charge_point_vendor: String,
+    pub charge_point_model: String,
+    pub charge_point_serial_number: Option<String>,
+    /// Firmware version as reported by the charger
+    pub firmware_version: Option<String>,
+    /// Firmware version normalized to a semantic version, `None` when it could not be parsed
+    pub firmware_version_semver: Option<String>,
+    pub first_boot_at: DateTime<Utc>,
+    pub last_boot_at: DateTime<Utc>,
+    /// Protocol negotiated in the last session of the charger
+    pub protocol_version: Option<String>,
+    /// Time between the connection and the first BootNotification in the last session of the
+    /// charger
+    pub initial_latency_ms: Option<i32>,
+    /// IANA timezone of the charger, `None` when unknown
+    pub timezone: Option<String>,
+    /// Whether new sessions are rejected, see `maintenance`
+    pub maintenance_mode: bool,
+}
+
+pub async fn chargers() -> Result<Vec<Charger>, sqlx::Error> {
+    sqlx::query_as!(
+        Charger,
+        "SELECT c.station_id, c.charge_point_vendor, c.charge_point_model, \
+         c.charge_point_serial_number, c.firmware_version, c.firmware_version_semver, \
+         c.first_boot_at, c.last_boot_at, s.protocol_version AS \"protocol_version?\", \
+         s.initial_latency_ms AS \"initial_latency_ms?\", c.timezone, c.maintenance_mode FROM \
+         chargers c LEFT JOIN LATERAL (SELECT protocol_version, initial_latency_ms FROM \
+         charger_sessions WHERE station_id = c.station_id ORDER BY connected_at DESC LIMIT 1) s \
+         ON true ORDER BY c.station_id",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn charger(station_id: &str) -> Result<Option<Charger>, sqlx::Error> {
+    sqlx::query_as!(
+        Charger,
+        "SELECT c.station_id, c.charge_point_vendor, c.charge_point_model, \
+         c.charge_point_serial_number, c.firmware_version, c.firmware_version_semver, \
+         c.first_boot_at, c.last_boot_at, s.protocol_version AS \"protocol_version?\", \
+         s.initial_latency_ms AS \"initial_latency_ms?\", c.timezone, c.maintenance_mode FROM \
+         chargers c LEFT JOIN LATERAL (SELECT protocol_version, initial_latency_ms FROM \
+         charger_sessions WHERE station_id = c.station_id ORDER BY connected_at DESC LIMIT 1) s \
+         ON true WHERE c.station_id = $1",
+        station_id,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+/// Returns whether the charger exists
+pub async fn set_charger_timezone(
+    station_id: &str,
+    timezone: Option<&str>,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE chargers SET timezone = $2 WHERE station_id = $1",
+        station_id,
+        timezone,
+    )
+    .execute(pool())
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// `None` when the charger is unknown or has no timezone
+pub async fn charger_timezone(station_id: &str) -> Result<Option<String>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT timezone FROM chargers WHERE station_id = $1",
+        station_id,
+    )
+    .fetch_optional(pool())
+    .await
+    .map(Option::flatten)
+}
+
+pub async fn chargers_in_maintenance() -> Result<Vec<String>, sqlx::Error> {
+    sqlx::query_scalar!("SELECT station_id FROM chargers WHERE maintenance_mode")
+        .fetch_all(pool())
+        .await
+}
+
+/// Returns whether the charger exists
+pub async fn set_maintenance_mode(
+    station_id: &str,
+    maintenance_mode: bool,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "UPDATE chargers SET maintenance_mode = $2 WHERE station_id = $1",
+        station_id,
+        maintenance_mode,
+    )
+    .execute(pool())
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Record the clock skew in the currently open session of the charger
+pub async fn update_clock_skew(station_id: &str, clock_skew_secs: i64) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE charger_sessions SET clock_skew_secs = $2 WHERE id = (SELECT id FROM \
+         charger_sessions WHERE station_id = $1 AND disconnected_at IS NULL ORDER BY connected_at \
+         DESC LIMIT 1)",
+        station_id,
+        clock_skew_secs,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Record the time between the connection and the first BootNotification in the currently open
+/// session of the charger. Returns `None` when it was already recorded for the session
+pub async fn record_initial_latency(
+    station_id: &str,
+    received_at: DateTime<Utc>,
+) -> Result<Option<i32>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "UPDATE charger_sessions SET initial_latency_ms = (EXTRACT(EPOCH FROM $2 - connected_at) \
+         * 1000)::INTEGER WHERE id = (SELECT id FROM charger_sessions WHERE station_id = $1 AND \
+         disconnected_at IS NULL ORDER BY connected_at DESC LIMIT 1) AND initial_latency_ms IS \
+         NULL RETURNING initial_latency_ms AS \"initial_latency_ms!\"",
+        station_id,
+        received_at,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct IdTag {
+    pub id_tag: String,
+    pub status: String,
+    pub expiry_date: Option<DateTime<Utc>>,
+    pub parent_id_tag: Option<String>,
+}
+
+pub async fn id_tags() -> Result<Vec<IdTag>, sqlx::Error> {
+    sqlx::query_as!(
+        IdTag,
+        "SELECT id_tag, status, expiry_date, parent_id_tag FROM id_tags",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn id_tag(id_tag: &str) -> Result<Option<IdTag>, sqlx::Error> {
+    sqlx::query_as!(
+        IdTag,
+        "SELECT id_tag, status, expiry_date, parent_id_tag FROM id_tags WHERE id_tag = $1",
+        id_tag,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct NewTransaction<'a> {
+    pub station_id: &'a str,
+    pub connector_id: i32,
+    pub id_tag: &'a str,
+    pub status: &'a str,
+    pub meter_start: i32,
+    pub start_time: DateTime<Utc>,
+}
+
+/// Persist a new transaction, returning its generated transaction ID. The transaction is attributed
+/// to the user owning the idTag, if any. Blocked transactions start with no energy so they never
+/// count towards billing
+pub async fn insert_transaction(transaction: &NewTransaction<'_>) -> Result<i32, sqlx::Error> {
+    sqlx::query_scalar!(
+        "INSERT INTO transactions (station_id, connector_id, id_tag, status, meter_start, \
+         start_time, energy_wh, user_id) VALUES ($1, $2, $3, $4, $5, $6, CASE WHEN $4 = 'blocked' \
+         THEN 0 END, (SELECT id FROM users WHERE id_tag = $3)) RETURNING id",
+        transaction.station_id,
+        transaction.connector_id,
+        transaction.id_tag,
+        transaction.status,
+        transaction.meter_start,
+        transaction.start_time,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Transaction started and not stopped yet
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct OpenTransaction {
+    pub id: i32,
+    pub station_id: String,
+    pub connector_id: i32,
+    pub id_tag: String,
+    pub meter_start: i32,
+    pub start_time: DateTime<Utc>,
+    /// Last reading of the import register stored for the transaction, in Wh
+    pub last_reading_wh: Option<f64>,
+}
+
+/// Active transactions without a stop, the oldest first
+pub async fn open_transactions() -> Result<Vec<OpenTransaction>, sqlx::Error> {
+    sqlx::query_as!(
+        OpenTransaction,
+        "SELECT t.id, t.station_id, t.connector_id, t.id_tag, t.meter_start, t.start_time, \
+         (SELECT r.value * CASE WHEN r.unit = 'kWh' THEN 1000 ELSE 1 END FROM meter_readings r \
+         WHERE r.transaction_id = t.id AND r.measurand = 'Energy.Active.Import.Register' AND \
+         r.unit IN ('Wh', 'kWh') AND r.phase IS NULL ORDER BY r.timestamp DESC LIMIT 1) AS \
+         last_reading_wh FROM transactions t WHERE t.status = 'active' AND t.stop_time IS NULL \
+         ORDER BY t.start_time",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Transaction stopped by a StopTransaction
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct CompletedTransaction {
+    #[serde(rename = "transaction_id")]
+    pub id: i32,
+    pub station_id: String,
+    pub connector_id: i32,
+    /// idTag that started the transaction
+    pub id_tag: String,
+    pub status: String,
+    pub meter_start: i32,
+    pub meter_stop: i32,
+    pub start_time: DateTime<Utc>,
+    pub stop_time: DateTime<Utc>,
+    pub stop_reason: Option<String>,
+    pub energy_wh: i64,
+    pub import_energy_wh: i64,
+    /// Energy fed back to the grid by a bi-directional (V2G) charger
+    pub export_energy_wh: i64,
+    /// Set once the cost is estimated
+    pub cost: Option<f64>,
+    pub currency: Option<String>,
+}
+
+/// Record the end of a transaction. Blocked transactions keep their status and no energy. Returns
+/// `None` when the transaction does not exist or was already stopped
+pub async fn complete_transaction(
+    transaction_id: i32,
+    meter_stop: i32,
+    export_energy_wh: i64,
+    stop_time: DateTime<Utc>,
+    stop_reason: Option<&str>,
+) -> Result<Option<CompletedTransaction>, sqlx::Error> {
+    sqlx::query_as!(
+        CompletedTransaction,
+        "UPDATE transactions SET meter_stop = $2, stop_time = $4, stop_reason = $5, status = CASE \
+         WHEN status = 'active' THEN 'completed' ELSE status END, energy_wh = CASE WHEN status = \
+         'blocked' THEN 0 ELSE $2 - meter_start END, import_energy_wh = CASE WHEN status = \
+         'blocked' THEN 0 ELSE $2 - meter_start END, export_energy_wh = CASE WHEN status = \
+         'blocked' THEN 0 ELSE $3::bigint END WHERE id = $1 AND stop_time IS NULL RETURNING id, \
+         station_id, connector_id, id_tag, status, meter_start, meter_stop AS \"meter_stop!\", \
+         start_time, stop_time AS \"stop_time!\", stop_reason, energy_wh AS \"energy_wh!\", \
+         import_energy_wh AS \"import_energy_wh!\", export_energy_wh AS \"export_energy_wh!\", \
+         cost, currency",
+        transaction_id,
+        meter_stop,
+        export_energy_wh,
+        stop_time,
+        stop_reason,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+pub async fn set_transaction_cost(
+    transaction_id: i32,
+    cost: f64,
+    currency: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE transactions SET cost = $2, currency = $3 WHERE id = $1",
+        transaction_id,
+        cost,
+        currency,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct SessionSummary {
+    pub transaction_id: i32,
+    pub station_id: String,
+    pub connector_id: i32,
+    pub id_tag: String,
+    /// Owner of the idTag, when known
+    pub user: Option<SessionUser>,
+    pub status: String,
+    pub start_time: DateTime<Utc>,
+    pub stop_time: Option<DateTime<Utc>>,
+    pub stop_reason: Option<String>,
+    /// So far while the transaction is active, `None` for a blocked one
+    pub duration_secs: Option<i64>,
+    pub energy_wh: Option<i64>,
+    pub import_energy_wh: Option<i64>,
+    /// Energy fed back to the grid by a bi-directional (V2G) charger
+    pub export_energy_wh: Option<i64>,
+    /// Estimated from the tariffs applicable while the transaction charged
+    pub cost: Option<f64>,
+    pub currency: Option<String>,
+    /// Energy and cost in each tariff period, empty until the transaction is completed
+    pub cost_breakdown: Vec<CostBreakdown>,
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct SessionUser {
+    pub name: String,
+    pub email: Option<String>,
+}
+
+struct SessionSummaryRow {
+    transaction_id: i32,
+    station_id: String,
+    connector_id: i32,
+    id_tag: String,
+    user_name: Option<String>,
+    user_email: Option<String>,
+    status: String,
+    start_time: DateTime<Utc>,
+    stop_time: Option<DateTime<Utc>>,
+    stop_reason: Option<String>,
+    energy_wh: Option<i64>,
+    import_energy_wh: Option<i64>,
+    export_energy_wh: Option<i64>,
+    cost: Option<f64>,
+    currency: Option<String>,
+    cost_breakdown: Json<Vec<CostBreakdown>>,
+}
+
+impl From<SessionSummaryRow> for SessionSummary {
+    fn from(row: SessionSummaryRow) -> Self {
+        let duration_secs = match row.stop_time {
+            Some(stop_time) => Some((stop_time - row.start_time).num_seconds()),
+            None if row.status == "active" => Some((Utc::now() - row.start_time).num_seconds()),
+            None => None,
+        };
+        Self {
+            transaction_id: row.transaction_id,
+            station_id: row.station_id,
+            connector_id: row.connector_id,
+            id_tag: row.id_tag,
+            user: row
+                .user_name
+                .map(|name| SessionUser { name, email: row.user_email }),
+            status: row.status,
+            start_time: row.start_time,
+            stop_time: row.stop_time,
+            stop_reason: row.stop_reason,
+            duration_secs,
+            energy_wh: row.energy_wh,
+            import_energy_wh: row.import_energy_wh,
+            export_energy_wh: row.export_energy_wh,
+            cost: row.cost,
+            currency: row.currency,
+            cost_breakdown: row.cost_breakdown.0,
+        }
+    }
+}
+
+pub async fn session_summaries() -> Result<Vec<SessionSummary>, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        SessionSummaryRow,
+        "SELECT t.id AS transaction_id, t.station_id, t.connector_id, t.id_tag, u.name AS \
+         \"user_name?\", u.email AS user_email, t.status, t.start_time, t.stop_time, \
+         t.stop_reason, t.energy_wh, t.import_energy_wh, t.export_energy_wh, t.cost, t.currency, \
+         COALESCE((SELECT json_agg(json_build_object('period', b.period, 'energy_wh', \
+         b.energy_wh, 'cost', b.cost, 'currency', b.currency) ORDER BY b.period) FROM \
+         transaction_cost_breakdown b WHERE b.transaction_id = t.id), '[]') AS \"cost_breakdown!: \
+         Json<Vec<CostBreakdown>>\" FROM transactions t LEFT JOIN users u ON u.id = t.user_id \
+         ORDER BY t.start_time",
+    )
+    .fetch_all(pool())
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(SessionSummary::from)
+        .collect())
+}
+
+/// Sessions of bi-directional (V2G) chargers that fed energy back to the grid
+pub async fn v2g_session_summaries() -> Result<Vec<SessionSummary>, sqlx::Error> {
+    let rows = sqlx::query_as!(
+        SessionSummaryRow,
+        "SELECT t.id AS transaction_id, t.station_id, t.connector_id, t.id_tag, u.name AS \
+         \"user_name?\", u.email AS user_email, t.status, t.start_time, t.stop_time, \
+         t.stop_reason, t.energy_wh, t.import_energy_wh, t.export_energy_wh, t.cost, t.currency, \
+         COALESCE((SELECT json_agg(json_build_object('period', b.period, 'energy_wh', \
+         b.energy_wh, 'cost', b.cost, 'currency', b.currency) ORDER BY b.period) FROM \
+         transaction_cost_breakdown b WHERE b.transaction_id = t.id), '[]') AS \"cost_breakdown!: \
+         Json<Vec<CostBreakdown>>\" FROM transactions t LEFT JOIN users u ON u.id = t.user_id \
+         WHERE t.export_energy_wh > 0 ORDER BY t.start_time",
+    )
+    .fetch_all(pool())
+    .await?;
+    Ok(rows
+        .into_iter()
+        .map(SessionSummary::from)
+        .collect())
+}
+
+pub async fn session_summary(transaction_id: i32) -> Result<Option<SessionSummary>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        SessionSummaryRow,
+        "SELECT t.id AS transaction_id, t.station_id, t.connector_id, t.id_tag, u.name AS \
+         \"user_name?\", u.email AS user_email, t.status, t.start_time, t.stop_time, \
+         t.stop_reason, t.energy_wh, t.import_energy_wh, t.export_energy_wh, t.cost, t.currency, \
+         COALESCE((SELECT json_agg(json_build_object('period', b.period, 'energy_wh', \
+         b.energy_wh, 'cost', b.cost, 'currency', b.currency) ORDER BY b.period) FROM \
+         transaction_cost_breakdown b WHERE b.transaction_id = t.id), '[]') AS \"cost_breakdown!: \
+         Json<Vec<CostBreakdown>>\" FROM transactions t LEFT JOIN users u ON u.id = t.user_id \
+         WHERE t.id = $1",
+        transaction_id,
+    )
+    .fetch_optional(pool())
+    .await?;
+    Ok(row.map(SessionSummary::from))
+}
+
+/// Most recent transaction of the connector, whatever its status
+pub async fn last_session_summary(
+    station_id: &str,
+    connector_id: i32,
+) -> Result<Option<SessionSummary>, sqlx::Error> {
+    let row = sqlx::query_as!(
+        SessionSummaryRow,
+        "SELECT t.id AS transaction_id, t.station_id, t.connector_id, t.id_tag, u.name AS \
+         \"user_name?\", u.email AS user_email, t.status, t.start_time, t.stop_time, \
+         t.stop_reason, t.energy_wh, t.import_energy_wh, t.export_energy_wh, t.cost, t.currency, \
+         COALESCE((SELECT json_agg(json_build_object('period', b.period, 'energy_wh', \
+         b.energy_wh, 'cost', b.cost, 'currency', b.currency) ORDER BY b.period) FROM \
+         transaction_cost_breakdown b WHERE b.transaction_id = t.id), '[]') AS \"cost_breakdown!: \
+         Json<Vec<CostBreakdown>>\" FROM transactions t LEFT JOIN users u ON u.id = t.user_id \
+         WHERE t.station_id = $1 AND t.connector_id = $2 ORDER BY t.start_time DESC, t.id DESC \
+         LIMIT 1",
+        station_id,
+        connector_id,
+    )
+    .fetch_optional(pool())
+    .await?;
+    Ok(row.map(SessionSummary::from))
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct User {
+    pub id: i32,
+    pub id_tag: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub external_id: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct NewUser {
+    pub id_tag: String,
+    pub name: String,
+    pub email: Option<String>,
+    pub external_id: Option<String>,
+}
+
+pub async fn users() -> Result<Vec<User>, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        "SELECT id, id_tag, name, email, external_id FROM users ORDER BY name",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn insert_user(user: &NewUser) -> Result<User, sqlx::Error> {
+    sqlx::query_as!(
+        User,
+        "INSERT INTO users (id_tag, name, email, external_id) VALUES ($1, $2, $3, $4) RETURNING \
+         id, id_tag, name, email, external_id",
+        user.id_tag,
+        user.name,
+        user.email,
+        user.external_id,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Delete a user, keeping its transactions. Returns whether the user existed
+pub async fn delete_user(user_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM users WHERE id = $1", user_id)
+        .execute(pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct Tariff {
+    pub id: i32,
+    pub station_id: String,
+    pub price_per_kwh: f64,
+    pub currency: String,
+    pub valid_from: DateTime<Utc>,
+    pub valid_to: Option<DateTime<Utc>>,
+    /// Start of the daily period the tariff applies to (UTC), e.g. for peak pricing
+    pub time_of_day_start: Option<NaiveTime>,
+    pub time_of_day_end: Option<NaiveTime>,
+    /// ISO 8601 day of the week the tariff applies to (UTC), 1 for Monday to 7 for Sunday
+    pub day_of_week: Option<i16>,
+    /// Name of the period in the cost breakdowns, e.g. `peak`
+    pub period: Option<String>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct NewTariff {
+    pub station_id: String,
+    pub price_per_kwh: f64,
+    pub currency: String,
+    pub valid_from: DateTime<Utc>,
+    pub valid_to: Option<DateTime<Utc>>,
+    pub time_of_day_start: Option<NaiveTime>,
+    pub time_of_day_end: Option<NaiveTime>,
+    pub day_of_week: Option<i16>,
+    pub period: Option<String>,
+}
+
+pub async fn tariffs() -> Result<Vec<Tariff>, sqlx::Error> {
+    sqlx::query_as!(
+        Tariff,
+        "SELECT id, station_id, price_per_kwh, currency, valid_from, valid_to, time_of_day_start, \
+         time_of_day_end, day_of_week, period FROM tariffs ORDER BY station_id, valid_from",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn insert_tariff(tariff: &NewTariff) -> Result<Tariff, sqlx::Error> {
+    sqlx::query_as!(
+        Tariff,
+        "INSERT INTO tariffs (station_id, price_per_kwh, currency, valid_from, valid_to, \
+         time_of_day_start, time_of_day_end, day_of_week, period) VALUES ($1, $2, $3, $4, $5, $6, \
+         $7, $8, $9) RETURNING id, station_id, price_per_kwh, currency, valid_from, valid_to, \
+         time_of_day_start, time_of_day_end, day_of_week, period",
+        tariff.station_id,
+        tariff.price_per_kwh,
+        tariff.currency,
+        tariff.valid_from,
+        tariff.valid_to,
+        tariff.time_of_day_start,
+        tariff.time_of_day_end,
+        tariff.day_of_week,
+        tariff.period,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Tariffs of the station valid at some point between `from` and `to`
+pub async fn station_tariffs(
+    station_id: &str,
+    from: DateTime<Utc>,
+    to: DateTime<Utc>,
+) -> Result<Vec<Tariff>, sqlx::Error> {
+    sqlx::query_as!(
+        Tariff,
+        "SELECT id, station_id, price_per_kwh, currency, valid_from, valid_to, time_of_day_start, \
+         time_of_day_end, day_of_week, period FROM tariffs WHERE station_id = $1 AND valid_from < \
+         $3 AND (valid_to IS NULL OR valid_to > $2) ORDER BY valid_from",
+        station_id,
+        from,
+        to,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Energy and cost of a transaction in one tariff period
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct CostBreakdown {
+    pub period: String,
+    pub energy_wh: i64,
+    pub cost: f64,
+    pub currency: String,
+}
+
+pub async fn insert_cost_breakdown(
+    transaction_id: i32,
+    breakdown: &[CostBreakdown],
+) -> Result<(), sqlx::Error> {
+    let mut transaction = pool().begin().await?;
+    for period in breakdown {
+        sqlx::query!(
+            "INSERT INTO transaction_cost_breakdown (transaction_id, period, currency, energy_wh, \
+             cost) VALUES ($1, $2, $3, $4, $5)",
+            transaction_id,
+            period.period,
+            period.currency,
+            period.energy_wh,
+            period.cost,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    transaction.commit().await
+}
+
+/// A connector state reported by a StatusNotification
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct StatusNotification {
+    pub station_id: String,
+    pub connector_id: i32,
+    pub status: String,
+    pub error_code: String,
+    pub vendor_error_code: Option<String>,
+    pub info: Option<String>,
+    pub timestamp: DateTime<Utc>,
+}
+
+pub async fn insert_status_notification(
+    status_notification: &StatusNotification,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO status_notification_history (station_id, connector_id, status, error_code, \
+         vendor_error_code, info, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7)",
+        status_notification.station_id,
+        status_notification.connector_id,
+        status_notification.status,
+        status_notification.error_code,
+        status_notification.vendor_error_code,
+        status_notification.info,
+        status_notification.timestamp,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Fault of a connector reported by a Faulted StatusNotification
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ConnectorFault {
+    pub id: i64,
+    pub station_id: String,
+    pub connector_id: i32,
+    pub error_code: String,
+    pub vendor_error_code: Option<String>,
+    pub info: Option<String>,
+    pub faulted_at: DateTime<Utc>,
+    /// Set once the fault is resolved after maintenance
+    pub resolved_at: Option<DateTime<Utc>>,
+}
+
+/// Open a fault for the Faulted status notification. A connector still faulted with the same error
+/// code keeps its open fault, with the details of the latest notification
+pub async fn record_connector_fault(
+    status_notification: &StatusNotification,
+) -> Result<ConnectorFault, sqlx::Error> {
+    sqlx::query_as!(
+        ConnectorFault,
+        "INSERT INTO connector_faults (station_id, connector_id, error_code, vendor_error_code, \
+         info, faulted_at) VALUES ($1, $2, $3, $4, $5, $6) ON CONFLICT (station_id, connector_id, \
+         error_code) WHERE resolved_at IS NULL DO UPDATE SET vendor_error_code = \
+         EXCLUDED.vendor_error_code, info = EXCLUDED.info RETURNING id, station_id, connector_id, \
+         error_code, vendor_error_code, info, faulted_at, resolved_at",
+        status_notification.station_id,
+        status_notification.connector_id,
+        status_notification.error_code,
+        status_notification.vendor_error_code,
+        status_notification.info,
+        status_notification.timestamp,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Faults of the charger, the most recent first. Only the resolved or the open ones when
+/// `resolved` is set
+pub async fn connector_faults(
+    station_id: &str,
+    resolved: Option<bool>,
+) -> Result<Vec<ConnectorFault>, sqlx::Error> {
+    sqlx::query_as!(
+        ConnectorFault,
+        "SELECT id, station_id, connector_id, error_code, vendor_error_code, info, faulted_at, \
+         resolved_at FROM connector_faults WHERE station_id = $1 AND ($2::boolean IS NULL OR \
+         (resolved_at IS NOT NULL) = $2) ORDER BY faulted_at DESC, id DESC",
+        station_id,
+        resolved,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn connector_fault(
+    station_id: &str,
+    fault_id: i64,
+) -> Result<Option<ConnectorFault>, sqlx::Error> {
+    sqlx::query_as!(
+        ConnectorFault,
+        "SELECT id, station_id, connector_id, error_code, vendor_error_code, info, faulted_at, \
+         resolved_at FROM connector_faults WHERE station_id = $1 AND id = $2",
+        station_id,
+        fault_id,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+/// Mark the open fault as resolved. Returns `None` when the charger has no such open fault
+pub async fn resolve_connector_fault(
+    station_id: &str,
+    fault_id: i64,
+) -> Result<Option<ConnectorFault>, sqlx::Error> {
+    sqlx::query_as!(
+        ConnectorFault,
+        "UPDATE connector_faults SET resolved_at = now() WHERE station_id = $1 AND id = $2 AND \
+         resolved_at IS NULL RETURNING id, station_id, connector_id, error_code, \
+         vendor_error_code, info, faulted_at, resolved_at",
+        station_id,
+        fault_id,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+/// Mean time from the fault to its resolution of the resolved faults of the charger, in seconds.
+/// `None` when none was resolved
+pub async fn mean_time_to_resolve_secs(station_id: &str) -> Result<Option<f64>, sqlx::Error> {
+    sqlx::query_scalar!(
+        "SELECT AVG(EXTRACT(EPOCH FROM resolved_at - faulted_at))::double precision FROM \
+         connector_faults WHERE station_id = $1 AND resolved_at IS NOT NULL",
+        station_id,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Latest states of a connector, the most recent first
+pub async fn status_notification_history(
+    station_id: &str,
+    connector_id: i32,
+    limit: i64,
+) -> Result<Vec<StatusNotification>, sqlx::Error> {
+    sqlx::query_as!(
+        StatusNotification,
+        "SELECT station_id, connector_id, status, error_code, vendor_error_code, info, timestamp \
+         FROM status_notification_history WHERE station_id = $1 AND connector_id = $2 ORDER BY \
+         timestamp DESC LIMIT $3",
+        station_id,
+        connector_id,
+        limit,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// An entry of the diagnostics timeline of a charger
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct DiagnosticEvent {
+    pub timestamp: DateTime<Utc>,
+    /// `status_change`, `reconnect`, `firmware_change` or `anomaly`
+    pub event_type: String,
+    /// ID of the event in its own table, unique along with the event type
+    pub id: i64,
+    pub details: serde_json::Value,
+}
+
+/// Connector states, connections, firmware changes and rejected sampled values of the charger,
+/// the most recent first. Only the events before `before` are returned, to page through them
+pub async fn diagnostic_events(
+    station_id: &str,
+    since: Option<DateTime<Utc>>,
+    before: Option<(DateTime<Utc>, &str, i64)>,
+    limit: i64,
+) -> Result<Vec<DiagnosticEvent>, sqlx::Error> {
+    let (before_timestamp, before_event_type, before_id) = match before {
+        Some((timestamp, event_type, id)) => (Some(timestamp), Some(event_type), Some(id)),
+        None => (None, None, None),
+    };
+    sqlx::query_as!(
+        DiagnosticEvent,
+        "SELECT timestamp AS \"timestamp!\", event_type AS \"event_type!\", id AS \"id!\", \
+         details AS \"details!\" FROM (SELECT timestamp, 'status_change' AS event_type, id, \
+         jsonb_build_object('connector_id', connector_id, 'status', status, 'error_code', \
+         error_code, 'vendor_error_code', vendor_error_code, 'info', info) AS details FROM \
+         status_notification_history WHERE station_id = $1 UNION ALL SELECT connected_at, \
+         'reconnect', id, jsonb_build_object('connection_id', connection_id, 'remote_addr', \
+         remote_addr, 'protocol_version', protocol_version, 'disconnected_at', disconnected_at, \
+         'clock_skew_secs', clock_skew_secs, 'initial_latency_ms', initial_latency_ms) FROM \
+         charger_sessions WHERE station_id = $1 UNION ALL SELECT changed_at, 'firmware_change', \
+         id, jsonb_build_object('old_version', old_version, 'new_version', new_version) FROM \
+         firmware_change_events WHERE station_id = $1 UNION ALL SELECT received_at, 'anomaly', \
+         id, jsonb_build_object('connector_id', connector_id, 'transaction_id', transaction_id, \
+         'measurand', measurand, 'unit', unit, 'sampled_value', sampled_value) FROM \
+         meter_readings_raw WHERE station_id = $1) events WHERE ($2::timestamptz IS NULL OR \
+         timestamp >= $2) AND ($3::timestamptz IS NULL OR (timestamp, event_type, id) < ($3, $4, \
+         $5)) ORDER BY timestamp DESC, event_type DESC, id DESC LIMIT $6",
+        station_id,
+        since,
+        before_timestamp,
+        before_event_type,
+        before_id,
+        limit,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Availability of a connector that outlives the reboots of the charger
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct AvailabilityOverride {
+    pub station_id: String,
+    pub connector_id: i32,
+    pub availability_type: String,
+    pub set_at: DateTime<Utc>,
+}
+
+pub async fn upsert_availability_override(
+    station_id: &str,
+    connector_id: i32,
+    availability_type: &str,
+) -> Result<AvailabilityOverride, sqlx::Error> {
+    sqlx::query_as!(
+        AvailabilityOverride,
+        "INSERT INTO availability_overrides (station_id, connector_id, availability_type) VALUES \
+         ($1, $2, $3) ON CONFLICT (station_id, connector_id) DO UPDATE SET availability_type = \
+         EXCLUDED.availability_type, set_at = now() RETURNING station_id, connector_id, \
+         availability_type, set_at",
+        station_id,
+        connector_id,
+        availability_type,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Remove the override of a connector. Returns whether the connector had one
+pub async fn delete_availability_override(
+    station_id: &str,
+    connector_id: i32,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM availability_overrides WHERE station_id = $1 AND connector_id = $2",
+        station_id,
+        connector_id,
+    )
+    .execute(pool())
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn availability_overrides(
+    station_id: &str,
+) -> Result<Vec<AvailabilityOverride>, sqlx::Error> {
+    sqlx::query_as!(
+        AvailabilityOverride,
+        "SELECT station_id, connector_id, availability_type, set_at FROM availability_overrides \
+         WHERE station_id = $1 ORDER BY connector_id",
+        station_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Version of the Local Authorization List of the station, 0 when it has none
+pub async fn local_auth_list_version(station_id: &str) -> Result<i32, sqlx::Error> {
+    let list_version = sqlx::query_scalar!(
+        "SELECT list_version FROM local_auth_lists WHERE station_id = $1",
+        station_id,
+    )
+    .fetch_optional(pool())
+    .await?;
+    Ok(list_version.unwrap_or(0))
+}
+
+/// Entry of a Local Authorization List update
+#[derive(Debug, Clone, PartialEq)]
+pub struct LocalAuthListEntry {
+    pub id_tag: String,
+    /// `None` removes the idTag from the list
+    pub status: Option<String>,
+    pub expiry_date: Option<DateTime<Utc>>,
+    pub parent_id_tag: Option<String>,
+}
+
+/// Replace the Local Authorization List of the station when `full`, or update its entries
+/// otherwise. A differential update older than the current list is not applied and returns `false`
+pub async fn update_local_auth_list(
+    station_id: &str,
+    list_version: i32,
+    full: bool,
+    entries: &[LocalAuthListEntry],
+) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool().begin().await?;
+    let current_version = sqlx::query_scalar!(
+        "SELECT list_version FROM local_auth_lists WHERE station_id = $1 FOR UPDATE",
+        station_id,
+    )
+    .fetch_optional(&mut *transaction)
+    .await?;
+    if !crate::local_auth_list::applies_over(full, list_version, current_version) {
+        return Ok(false);
+    }
+    sqlx::query!(
+        "INSERT INTO local_auth_lists (station_id, list_version) VALUES ($1, $2) ON CONFLICT \
+         (station_id) DO UPDATE SET list_version = EXCLUDED.list_version, updated_at = now()",
+        station_id,
+        list_version,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    if full {
+        sqlx::query!(
+            "DELETE FROM local_auth_list_entries WHERE station_id = $1",
+            station_id,
+        )
+        .execute(&mut *transaction)
+        .await?;
+    }
+    for entry in entries {
+        match &entry.status {
+            Some(status) => {
+                sqlx::query!(
+                    "INSERT INTO local_auth_list_entries (station_id, id_tag, status, \
+                     expiry_date, parent_id_tag) VALUES ($1, $2, $3, $4, $5) ON CONFLICT \
+                     (station_id, id_tag) DO UPDATE SET status = EXCLUDED.status, expiry_date = \
+                     EXCLUDED.expiry_date, parent_id_tag = EXCLUDED.parent_id_tag",
+                    station_id,
+                    entry.id_tag,
+                    status,
+                    entry.expiry_date,
+                    entry.parent_id_tag,
+                )
+                .execute(&mut *transaction)
+                .await?
+            },
+            None => {
+                sqlx::query!(
+                    "DELETE FROM local_auth_list_entries WHERE station_id = $1 AND id_tag = $2",
+                    station_id,
+                    entry.id_tag,
+                )
+                .execute(&mut *transaction)
+                .await?
+            },
+        };
+    }
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// A numeric sampled value of a MeterValues request
+#[derive(Debug, Clone, PartialEq)]
+pub struct MeterReading {
+    pub station_id: String,
+    pub connector_id: i32,
+    pub transaction_id: Option<i32>,
+    pub measurand: String,
+    pub unit: String,
+    pub phase: Option<String>,
+    pub value: f64,
+    pub timestamp: DateTime<Utc>,
+}
+
+pub async fn insert_meter_reading(reading: &MeterReading) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO meter_readings (station_id, connector_id, transaction_id, measurand, unit, \
+         phase, value, timestamp) VALUES ($1, $2, $3, $4, $5, $6, $7, $8)",
+        reading.station_id,
+        reading.connector_id,
+        reading.transaction_id,
+        reading.measurand,
+        reading.unit,
+        reading.phase,
+        reading.value,
+        reading.timestamp,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Readings of the import register stored for the transaction, in Wh, the earliest first
+pub async fn import_register_readings_wh(
+    transaction_id: i32,
+) -> Result<Vec<(DateTime<Utc>, f64)>, sqlx::Error> {
+    let readings = sqlx::query!(
+        "SELECT timestamp, value * CASE WHEN unit = 'kWh' THEN 1000 ELSE 1 END AS \"wh!\" FROM \
+         meter_readings WHERE transaction_id = $1 AND measurand = 'Energy.Active.Import.Register' \
+         AND unit IN ('Wh', 'kWh') AND phase IS NULL ORDER BY timestamp",
+        transaction_id,
+    )
+    .fetch_all(pool())
+    .await?;
+    Ok(readings
+        .into_iter()
+        .map(|reading| (reading.timestamp, reading.wh))
+        .collect())
+}
+
+/// First and last readings of the export register stored for the transaction, in Wh
+pub async fn export_register_readings_wh(
+    transaction_id: i32,
+) -> Result<Option<(f64, f64)>, sqlx::Error> {
+    let readings = sqlx::query!(
+        "SELECT (array_agg(wh ORDER BY timestamp))[1] AS first, (array_agg(wh ORDER BY timestamp \
+         DESC))[1] AS last FROM (SELECT timestamp, value * CASE WHEN unit = 'kWh' THEN 1000 ELSE \
+         1 END AS wh FROM meter_readings WHERE transaction_id = $1 AND measurand = \
+         'Energy.Active.Export.Register' AND unit IN ('Wh', 'kWh') AND phase IS NULL) readings",
+        transaction_id,
+    )
+    .fetch_one(pool())
+    .await?;
+    Ok(readings.first.zip(readings.last))
+}
+
+/// Statistics of the readings of a measurand during a transaction
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct MeasurandStats {
+    #[serde(skip)]
+    pub measurand: String,
+    pub min: f64,
+    pub max: f64,
+    pub avg: f64,
+    pub count: i64,
+    pub unit: String,
+}
+
+pub async fn meter_stats(transaction_id: i32) -> Result<Vec<MeasurandStats>, sqlx::Error> {
+    sqlx::query_as!(
+        MeasurandStats,
+        "SELECT measurand, MIN(value) AS \"min!\", MAX(value) AS \"max!\", AVG(value) AS \
+         \"avg!\", COUNT(*) AS \"count!\", unit FROM meter_readings WHERE transaction_id = $1 \
+         GROUP BY measurand, unit ORDER BY measurand, unit",
+        transaction_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Fleet-wide figures of the dashboard landing page. The daily figures count from midnight UTC
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct DashboardSummary {
+    pub chargers_total: i64,
+    /// Chargers with a connector whose last reported status is Faulted
+    pub chargers_faulted: i64,
+    pub active_sessions: i64,
+    /// Energy of the transactions that ended today
+    pub energy_today_kwh: f64,
+    /// Transactions started today
+    pub sessions_today: i64,
+    /// Cost of the transactions in EUR that ended today
+    pub revenue_today_eur: f64,
+}
+
+pub async fn dashboard_summary() -> Result<DashboardSummary, sqlx::Error> {
+    sqlx::query_as!(
+        DashboardSummary,
+        "WITH today AS (SELECT date_trunc('day', now() AT TIME ZONE 'UTC') AT TIME ZONE 'UTC' AS \
+         start), latest_status AS (SELECT DISTINCT ON (station_id, connector_id) station_id, \
+         status FROM status_notification_history ORDER BY station_id, connector_id, timestamp \
+         DESC) SELECT (SELECT COUNT(*) FROM chargers) AS \"chargers_total!\", (SELECT \
+         COUNT(DISTINCT station_id) FROM latest_status WHERE status = 'Faulted') AS \
+         \"chargers_faulted!\", (SELECT COUNT(*) FROM transactions WHERE status = 'active' AND \
+         stop_time IS NULL) AS \"active_sessions!\", (SELECT COALESCE(SUM(energy_wh), 0)::DOUBLE \
+         PRECISION / 1000 FROM transactions, today WHERE stop_time >= today.start) AS \
+         \"energy_today_kwh!\", (SELECT COUNT(*) FROM transactions, today WHERE start_time >= \
+         today.start) AS \"sessions_today!\", (SELECT COALESCE(SUM(cost), 0) FROM transactions, \
+         today WHERE stop_time >= today.start AND currency = 'EUR') AS \"revenue_today_eur!\"",
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Usage of a charger over a period
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ChargerUsage {
+    pub station_id: String,
+    /// `None` for a charger that never sent a BootNotification
+    pub charge_point_vendor: Option<String>,
+    pub charge_point_model: Option<String>,
+    pub energy_kwh: f64,
+    pub sessions: i64,
+    /// Cost of the transactions in EUR, like the dashboard
+    pub revenue_eur: f64,
+}
+
+/// Chargers ranked by the metric (`energy`, `sessions` or `revenue`) of their transactions started
+/// since the given time. Blocked transactions are left out
+pub async fn top_chargers(
+    since: DateTime<Utc>,
+    metric: &str,
+    limit: i64,
+) -> Result<Vec<ChargerUsage>, sqlx::Error> {
+    sqlx::query_as!(
+        ChargerUsage,
+        "SELECT t.station_id, c.charge_point_vendor AS \"charge_point_vendor?\", \
+         c.charge_point_model AS \"charge_point_model?\", COALESCE(SUM(t.energy_wh), 0)::DOUBLE \
+         PRECISION / 1000 AS \"energy_kwh!\", COUNT(*) AS \"sessions!\", COALESCE(SUM(t.cost) \
+         FILTER (WHERE t.currency = 'EUR'), 0) AS \"revenue_eur!\" FROM transactions t LEFT JOIN \
+         chargers c ON c.station_id = t.station_id WHERE t.start_time >= $1 AND t.status <> \
+         'blocked' GROUP BY t.station_id, c.charge_point_vendor, c.charge_point_model ORDER BY \
+         CASE $2 WHEN 'sessions' THEN COUNT(*)::DOUBLE PRECISION WHEN 'revenue' THEN \
+         COALESCE(SUM(t.cost) FILTER (WHERE t.currency = 'EUR'), 0) ELSE \
+         COALESCE(SUM(t.energy_wh), 0)::DOUBLE PRECISION END DESC, t.station_id LIMIT $3",
+        since,
+        metric,
+        limit,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct StopReasonCount {
+    pub reason: String,
+    pub count: i64,
+}
+
+/// Transactions stopped since the given time for each reason, the most frequent first. A
+/// StopTransaction without reason is counted as Local, as OCPP 1.6 assumes
+pub async fn stop_reason_counts(
+    since: DateTime<Utc>,
+    station_id: Option<&str>,
+) -> Result<Vec<StopReasonCount>, sqlx::Error> {
+    sqlx::query_as!(
+        StopReasonCount,
+        "SELECT COALESCE(stop_reason, 'Local') AS \"reason!\", COUNT(*) AS \"count!\" FROM \
+         transactions WHERE stop_time >= $1 AND ($2::TEXT IS NULL OR station_id = $2) GROUP BY \
+         COALESCE(stop_reason, 'Local') ORDER BY COUNT(*) DESC, 1",
+        since,
+        station_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct ConnectorUtilization {
+    pub station_id: String,
+    pub connector_id: i32,
+    /// Share of the period the connector was Charging, in percent
+    pub utilization_pct: f64,
+    pub period_hours: f64,
+}
+
+/// Share of the time between `since` and `until` each connector spent Charging, the most used
+/// first. A state lasts until the next StatusNotification of the connector, the last one until
+/// `until`. The state a connector was in at the start of the period is its last one before it
+pub async fn connector_utilization(
+    since: DateTime<Utc>,
+    until: DateTime<Utc>,
+    station_id: Option<&str>,
+) -> Result<Vec<ConnectorUtilization>, sqlx::Error> {
+    sqlx::query_as!(
+        ConnectorUtilization,
+        "WITH states AS (SELECT station_id, connector_id, status, GREATEST(timestamp, $1) AS \
+         started_at, LEAD(timestamp) OVER (PARTITION BY station_id, connector_id ORDER BY \
+         timestamp, id) AS ended_at FROM status_notification_history WHERE connector_id > 0 AND \
+         timestamp < $2 AND ($3::TEXT IS NULL OR station_id = $3) AND (timestamp >= $1 OR id IN \
+         (SELECT DISTINCT ON (station_id, connector_id) id FROM status_notification_history WHERE \
+         timestamp < $1 ORDER BY station_id, connector_id, timestamp DESC, id DESC))) SELECT \
+         station_id, connector_id, ROUND((100 * COALESCE(SUM(EXTRACT(EPOCH FROM \
+         LEAST(COALESCE(ended_at, $2), $2) - started_at)) FILTER (WHERE status = 'Charging'), 0) \
+         / EXTRACT(EPOCH FROM $2 - $1))::NUMERIC, 1)::FLOAT8 AS \"utilization_pct!\", \
+         ROUND((EXTRACT(EPOCH FROM $2 - $1) / 3600)::NUMERIC, 1)::FLOAT8 AS \"period_hours!\" \
+         FROM states GROUP BY station_id, connector_id ORDER BY 3 DESC, 1, 2",
+        since,
+        until,
+        station_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// A condition on the OCPP events notified through a webhook
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct AlertRule {
+    pub id: i32,
+    pub rule_type: String,
+    pub threshold_value: Option<f64>,
+    pub webhook_url: String,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(serde::Deserialize, Debug, Clone, PartialEq)]
+pub struct NewAlertRule {
+    pub rule_type: String,
+    pub threshold_value: Option<f64>,
+    pub webhook_url: String,
+    #[serde(default = "enabled_by_default")]
+    pub enabled: bool,
+}
+
+fn enabled_by_default() -> bool { true }
+
+pub async fn alert_rules() -> Result<Vec<AlertRule>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertRule,
+        "SELECT id, rule_type, threshold_value, webhook_url, enabled, created_at FROM alert_rules \
+         ORDER BY id",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn enabled_alert_rules() -> Result<Vec<AlertRule>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertRule,
+        "SELECT id, rule_type, threshold_value, webhook_url, enabled, created_at FROM alert_rules \
+         WHERE enabled ORDER BY id",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn insert_alert_rule(rule: &NewAlertRule) -> Result<AlertRule, sqlx::Error> {
+    sqlx::query_as!(
+        AlertRule,
+        "INSERT INTO alert_rules (rule_type, threshold_value, webhook_url, enabled) VALUES ($1, \
+         $2, $3, $4) RETURNING id, rule_type, threshold_value, webhook_url, enabled, created_at",
+        rule.rule_type,
+        rule.threshold_value,
+        rule.webhook_url,
+        rule.enabled,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Returns whether the rule existed. The alerts it fired are kept
+pub async fn delete_alert_rule(rule_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM alert_rules WHERE id = $1", rule_id)
+        .execute(pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// An alert fired by a rule
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct AlertEvent {
+    pub id: i64,
+    /// `None` once the rule is deleted
+    pub rule_id: Option<i32>,
+    pub rule_type: String,
+    pub station_id: String,
+    pub event: serde_json::Value,
+    pub fired_at: DateTime<Utc>,
+    pub webhook_status: Option<i32>,
+    pub webhook_error: Option<String>,
+}
+
+pub async fn insert_alert_event(
+    rule: &AlertRule,
+    station_id: &str,
+    event: &serde_json::Value,
+) -> Result<AlertEvent, sqlx::Error> {
+    sqlx::query_as!(
+        AlertEvent,
+        "INSERT INTO alert_events (rule_id, rule_type, station_id, event) VALUES ($1, $2, $3, $4) \
+         RETURNING id, rule_id, rule_type, station_id, event, fired_at, webhook_status, \
+         webhook_error",
+        rule.id,
+        rule.rule_type,
+        station_id,
+        event,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Record the outcome of the webhook of an alert
+pub async fn update_alert_event_delivery(
+    alert_event_id: i64,
+    webhook_status: Option<i32>,
+    webhook_error: Option<&str>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "UPDATE alert_events SET webhook_status = $2, webhook_error = $3 WHERE id = $1",
+        alert_event_id,
+        webhook_status,
+        webhook_error,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Most recent alerts first
+pub async fn alert_events(limit: i64) -> Result<Vec<AlertEvent>, sqlx::Error> {
+    sqlx::query_as!(
+        AlertEvent,
+        "SELECT id, rule_id, rule_type, station_id, event, fired_at, webhook_status, \
+         webhook_error FROM alert_events ORDER BY fired_at DESC, id DESC LIMIT $1",
+        limit,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Two overlapping transactions of a connector
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct BillingAnomaly {
+    pub id: i64,
+    pub station_id: String,
+    pub connector_id: i32,
+    pub transaction_id: i32,
+    pub overlapping_transaction_id: i32,
+    pub detected_at: DateTime<Utc>,
+}
+
+/// Record the transactions of the connector that overlap the stopped transaction, blocked ones
+/// aside as they are not billed. Returns the overlaps that were not recorded yet
+pub async fn record_session_overlaps(
+    transaction_id: i32,
+) -> Result<Vec<BillingAnomaly>, sqlx::Error> {
+    sqlx::query_as!(
+        BillingAnomaly,
+        "INSERT INTO billing_anomalies (station_id, connector_id, transaction_id, \
+         overlapping_transaction_id) SELECT t.station_id, t.connector_id, t.id, o.id FROM \
+         transactions t JOIN transactions o ON o.station_id = t.station_id AND o.connector_id = \
+         t.connector_id AND o.id <> t.id AND o.status <> 'blocked' AND o.start_time < t.stop_time \
+         AND (o.stop_time > t.start_time OR o.stop_time IS NULL) WHERE t.id = $1 AND t.stop_time \
+         IS NOT NULL ON CONFLICT DO NOTHING RETURNING id, station_id, connector_id, \
+         transaction_id, overlapping_transaction_id, detected_at",
+        transaction_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+/// Most recent anomalies first
+pub async fn billing_anomalies(station_id: &str) -> Result<Vec<BillingAnomaly>, sqlx::Error> {
+    sqlx::query_as!(
+        BillingAnomaly,
+        "SELECT id, station_id, connector_id, transaction_id, overlapping_transaction_id, \
+         detected_at FROM billing_anomalies WHERE station_id = $1 ORDER BY detected_at DESC, id \
+         DESC",
+        station_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub struct NewChargerCertificate<'a> {
+    pub station_id: &'a str,
+    pub serial_number: &'a str,
+    pub certificate: &'a str,
+    pub issued_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+pub async fn insert_charger_certificate(
+    certificate: &NewChargerCertificate<'_>,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO charger_certificates (station_id, serial_number, certificate, issued_at, \
+         expires_at) VALUES ($1, $2, $3, $4, $5)",
+        certificate.station_id,
+        certificate.serial_number,
+        certificate.certificate,
+        certificate.issued_at,
+        certificate.expires_at,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Salted hash of the AuthorizationKey of a charger
+#[derive(Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ChargerCredentials {
+    pub auth_key_salt: String,
+    pub auth_key_hash: String,
+}
+
+pub async fn charger_credentials(
+    station_id: &str,
+) -> Result<Option<ChargerCredentials>, sqlx::Error> {
+    sqlx::query_as!(
+        ChargerCredentials,
+        "SELECT auth_key_salt, auth_key_hash FROM charger_credentials WHERE station_id = $1",
+        station_id,
+    )
+    .fetch_optional(pool())
+    .await
+}
+
+pub async fn upsert_charger_credentials(
+    station_id: &str,
+    credentials: &ChargerCredentials,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO charger_credentials (station_id, auth_key_salt, auth_key_hash) VALUES ($1, \
+         $2, $3) ON CONFLICT (station_id) DO UPDATE SET auth_key_salt = EXCLUDED.auth_key_salt, \
+         auth_key_hash = EXCLUDED.auth_key_hash, updated_at = now()",
+        station_id,
+        credentials.auth_key_salt,
+        credentials.auth_key_hash,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Returns whether the charger had credentials
+pub async fn delete_charger_credentials(station_id: &str) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM charger_credentials WHERE station_id = $1",
+        station_id,
+    )
+    .execute(pool())
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// A cluster of chargers managed as a unit
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct ChargerGroup {
+    pub id: i32,
+    pub name: String,
+    /// Chargers directly in the group, not those of its subgroups
+    pub station_ids: Vec<String>,
+    pub subgroup_ids: Vec<i32>,
+    pub created_at: DateTime<Utc>,
+}
+
+pub async fn charger_groups() -> Result<Vec<ChargerGroup>, sqlx::Error> {
+    sqlx::query_as!(
+        ChargerGroup,
+        "SELECT g.id, g.name, ARRAY(SELECT station_id FROM charger_group_chargers WHERE group_id \
+         = g.id ORDER BY station_id) AS \"station_ids!\", ARRAY(SELECT subgroup_id FROM \
+         charger_group_subgroups WHERE group_id = g.id ORDER BY subgroup_id) AS \
+         \"subgroup_ids!\", g.created_at FROM charger_groups g ORDER BY g.name",
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn insert_charger_group(name: &str) -> Result<ChargerGroup, sqlx::Error> {
+    sqlx::query_as!(
+        ChargerGroup,
+        "INSERT INTO charger_groups (name) VALUES ($1) RETURNING id, name, ARRAY[]::text[] AS \
+         \"station_ids!\", ARRAY[]::integer[] AS \"subgroup_ids!\", created_at",
+        name,
+    )
+    .fetch_one(pool())
+    .await
+}
+
+/// Returns whether the group existed. Its subgroups are kept
+pub async fn delete_charger_group(group_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!("DELETE FROM charger_groups WHERE id = $1", group_id)
+        .execute(pool())
+        .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+pub async fn add_charger_to_group(group_id: i32, station_id: &str) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO charger_group_chargers (group_id, station_id) VALUES ($1, $2) ON CONFLICT DO \
+         NOTHING",
+        group_id,
+        station_id,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Returns whether the charger was in the group
+pub async fn remove_charger_from_group(
+    group_id: i32,
+    station_id: &str,
+) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM charger_group_chargers WHERE group_id = $1 AND station_id = $2",
+        group_id,
+        station_id,
+    )
+    .execute(pool())
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Nest a group in another. Returns `false` without nesting it when the group would end up
+/// containing itself
+pub async fn add_subgroup(group_id: i32, subgroup_id: i32) -> Result<bool, sqlx::Error> {
+    let mut transaction = pool().begin().await?;
+    // Serializes the nestings, two concurrent ones could otherwise create a cycle together
+    sqlx::query!("LOCK TABLE charger_group_subgroups IN SHARE ROW EXCLUSIVE MODE")
+        .execute(&mut *transaction)
+        .await?;
+    let creates_cycle = sqlx::query_scalar!(
+        "WITH RECURSIVE descendants (id) AS (SELECT $2::integer UNION SELECT s.subgroup_id FROM \
+         charger_group_subgroups s JOIN descendants d ON s.group_id = d.id) SELECT EXISTS (SELECT \
+         1 FROM descendants WHERE id = $1) AS \"creates_cycle!\"",
+        group_id,
+        subgroup_id,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    if creates_cycle {
+        return Ok(false);
+    }
+    sqlx::query!(
+        "INSERT INTO charger_group_subgroups (group_id, subgroup_id) VALUES ($1, $2) ON CONFLICT \
+         DO NOTHING",
+        group_id,
+        subgroup_id,
+    )
+    .execute(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(true)
+}
+
+/// Returns whether the group was nested in the other
+pub async fn remove_subgroup(group_id: i32, subgroup_id: i32) -> Result<bool, sqlx::Error> {
+    let result = sqlx::query!(
+        "DELETE FROM charger_group_subgroups WHERE group_id = $1 AND subgroup_id = $2",
+        group_id,
+        subgroup_id,
+    )
+    .execute(pool())
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// Chargers of the group and of all its nested subgroups. `None` when the group does not exist
+pub async fn charger_group_station_ids(group_id: i32) -> Result<Option<Vec<String>>, sqlx::Error> {
+    let mut transaction = pool().begin().await?;
+    let exists = sqlx::query_scalar!(
+        "SELECT EXISTS (SELECT 1 FROM charger_groups WHERE id = $1) AS \"exists!\"",
+        group_id,
+    )
+    .fetch_one(&mut *transaction)
+    .await?;
+    if !exists {
+        return Ok(None);
+    }
+    let station_ids = sqlx::query_scalar!(
+        "WITH RECURSIVE groups (id) AS (SELECT $1::integer UNION SELECT s.subgroup_id FROM \
+         charger_group_subgroups s JOIN groups g ON s.group_id = g.id) SELECT DISTINCT \
+         c.station_id FROM charger_group_chargers c JOIN groups g ON c.group_id = g.id ORDER BY \
+         c.station_id",
+        group_id,
+    )
+    .fetch_all(&mut *transaction)
+    .await?;
+    transaction.commit().await?;
+    Ok(Some(station_ids))
+}
+
+/// Configuration key intended for the chargers of a group
+#[derive(serde::Serialize, Debug, Clone, PartialEq, sqlx::FromRow)]
+pub struct GroupConfigurationKey {
+    pub key: String,
+    pub value: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub async fn group_configuration(group_id: i32) -> Result<Vec<GroupConfigurationKey>, sqlx::Error> {
+    sqlx::query_as!(
+        GroupConfigurationKey,
+        "SELECT key, value, updated_at FROM charger_group_configuration WHERE group_id = $1 ORDER \
+         BY key",
+        group_id,
+    )
+    .fetch_all(pool())
+    .await
+}
+
+pub async fn upsert_group_configuration(
+    group_id: i32,
+    key: &str,
+    value: &str,
+) -> Result<(), sqlx::Error> {
+    sqlx::query!(
+        "INSERT INTO charger_group_configuration (group_id, key, value) VALUES ($1, $2, $3) ON \
+         CONFLICT (group_id, key) DO UPDATE SET value = EXCLUDED.value, updated_at = now()",
+        group_id,
+        key,
+        value,
+    )
+    .execute(pool())
+    .await?;
+    Ok(())
+}
+
+/// Configuration intended for the charger by its groups and the groups they are nested in. When
+/// several groups set a key, the latest value wins
+pub async fn intended_configuration(
+    station_id: &str,
+) -> Result<Vec<GroupConfigurationKey>, sqlx::Error> {
+    sqlx::query_as!(
+        GroupConfigurationKey,
+        "WITH RECURSIVE groups (id) AS (SELECT group_id FROM charger_group_chargers WHERE \
+         station_id = $1 UNION SELECT s.group_id FROM charger_group_subgroups s JOIN groups g ON \
+         s.subgroup_id = g.id) SELECT DISTINCT ON (c.key) c.key, c.value, c.updated_at FROM \
+         charger_group_configuration c JOIN groups g ON c.group_id = g.id ORDER BY c.key, \
+         c.updated_at DESC",
+        station_id,
+    )
+    .fetch_all(pool())
+    .await
+}
@@ -0,0 +1,162 @@
+use std::{fmt, sync::LazyLock, time::Duration};
+
+use chrono::{DateTime, Utc};
+use dashmap::DashMap;
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::types::ConfigurationStatus;
+use tracing::{info, warn, Instrument, Span};
+use uuid::Uuid;
+
+use crate::{
+    commands::{self, OcppError},
+    configuration, StationId,
+};
+
+/// Moovolt vendor configuration key of the verbosity of the charger logs
+const LOG_LEVEL_KEY: &str = "LogLevel";
+
+/// Chargers whose LogLevel was raised to Debug, until it is restored
+static DEBUG_MODES: LazyLock<DashMap<StationId, DebugMode>> = LazyLock::new(Default::default);
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct DebugMode {
+    pub started_at: DateTime<Utc>,
+    /// When the original LogLevel is restored
+    pub ends_at: DateTime<Utc>,
+    pub original_log_level: String,
+    /// Identifies the timer of the debug mode, replaced when it is extended
+    #[serde(skip)]
+    timer_id: Uuid,
+}
+
+/// Failure to put a charger in debug mode
+#[derive(Debug)]
+pub enum DebugModeError {
+    Ocpp(OcppError),
+    /// The charger does not report a LogLevel, so it could not be restored
+    UnknownLogLevel,
+    /// The charger did not accept the Debug LogLevel
+    Refused(ConfigurationStatus),
+}
+
+impl fmt::Display for DebugModeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            DebugModeError::Ocpp(err) => write!(f, "{err}"),
+            DebugModeError::UnknownLogLevel => {
+                write!(f, "Charger does not report its {LOG_LEVEL_KEY}")
+            },
+            DebugModeError::Refused(status) => {
+                write!(f, "Charger answered {status:?} to {LOG_LEVEL_KEY} Debug")
+            },
+        }
+    }
+}
+
+impl From<OcppError> for DebugModeError {
+    fn from(err: OcppError) -> Self { DebugModeError::Ocpp(err) }
+}
+
+fn debug_mode_duration() -> Duration {
+    const DEBUG_MODE_DURATION_SECS: &str = dotenv!("DEBUG_MODE_DURATION_SECS");
+    Duration::from_secs(
+        DEBUG_MODE_DURATION_SECS
+            .parse()
+            .expect("DEBUG_MODE_DURATION_SECS must be a number of seconds"),
+    )
+}
+
+/// Debug mode of the charger, `None` when its LogLevel is not raised
+pub fn debug_mode(station_id: &StationId) -> Option<DebugMode> {
+    DEBUG_MODES
+        .get(station_id)
+        .map(|debug_mode| debug_mode.clone())
+}
+
+/// Set the LogLevel of the charger to Debug for `DEBUG_MODE_DURATION_SECS`, then restore the
+/// LogLevel it had. Starting the debug mode of a charger already in it extends it
+pub async fn start(station_id: &StationId) -> Result<DebugMode, DebugModeError> {
+    // The cached configuration of an offline charger would not be changed
+    if !commands::is_connected(station_id) {
+        return Err(OcppError::NotConnected.into());
+    }
+    let current = debug_mode(station_id);
+    let original_log_level = match &current {
+        Some(current) => current.original_log_level.clone(),
+        None => configuration::configuration(station_id)
+            .await?
+            .and_then(|keys| {
+                keys.into_iter()
+                    .find(|key_value| key_value.key == LOG_LEVEL_KEY)
+            })
+            .and_then(|key_value| key_value.value)
+            .ok_or(DebugModeError::UnknownLogLevel)?,
+    };
+    let response = configuration::change(station_id, LOG_LEVEL_KEY, "Debug").await?;
+    if !matches!(
+        response.status,
+        ConfigurationStatus::Accepted | ConfigurationStatus::RebootRequired
+    ) {
+        return Err(DebugModeError::Refused(response.status));
+    }
+    let duration = debug_mode_duration();
+    let now = Utc::now();
+    let timer_id = Uuid::new_v4();
+    let debug_mode = DebugMode {
+        started_at: current.map_or(now, |current| current.started_at),
+        ends_at: now + duration,
+        original_log_level,
+        timer_id,
+    };
+    DEBUG_MODES.insert(station_id.clone(), debug_mode.clone());
+    info!("{station_id} is in debug mode until {}", debug_mode.ends_at);
+    let station_id = station_id.clone();
+    tokio::spawn(
+        async move {
+            tokio::time::sleep(duration).await;
+            // Extended or stopped in the meantime otherwise
+            if debug_mode_of_timer(&station_id, timer_id)
+                && let Err(err) = stop(&station_id).await
+            {
+                warn!("Failed to restore the {LOG_LEVEL_KEY} of {station_id}: {err}");
+            }
+        }
+        .instrument(Span::current()),
+    );
+    Ok(debug_mode)
+}
+
+fn debug_mode_of_timer(station_id: &StationId, timer_id: Uuid) -> bool {
+    DEBUG_MODES
+        .get(station_id)
+        .is_some_and(|debug_mode| debug_mode.timer_id == timer_id)
+}
+
+/// Restore the LogLevel the charger had before its debug mode. Returns `None` when the charger is
+/// not in debug mode. The debug mode is kept when the charger could not be reached, to restore the
+/// LogLevel once it connects again
+pub async fn stop(station_id: &StationId) -> Result<Option<DebugMode>, OcppError> {
+    let Some(debug_mode) = debug_mode(station_id) else {
+        return Ok(None);
+    };
+    let response =
+        configuration::change(station_id, LOG_LEVEL_KEY, &debug_mode.original_log_level).await?;
+    if response.status == ConfigurationStatus::Rejected {
+        warn!(
+            "{station_id} rejected the restore of its {LOG_LEVEL_KEY} to {}",
+            debug_mode.original_log_level
+        );
+    }
+    DEBUG_MODES.remove(station_id);
+    info!("{station_id} left debug mode");
+    Ok(Some(debug_mode))
+}
+
+/// Restore the LogLevel of a charger whose debug mode ended while it was offline
+pub async fn restore_expired(station_id: StationId) {
+    if debug_mode(&station_id).is_some_and(|debug_mode| debug_mode.ends_at <= Utc::now())
+        && let Err(err) = stop(&station_id).await
+    {
+        warn!("Failed to restore the {LOG_LEVEL_KEY} of {station_id}: {err}");
+    }
+}
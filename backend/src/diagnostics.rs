@@ -0,0 +1,55 @@
+use rust_ocpp::v1_6::messages::get_diagnostics::{GetDiagnosticsRequest, GetDiagnosticsResponse};
+use tracing::error;
+
+use crate::{
+    commands::{self, OcppError},
+    OcppActionEnum, StationId,
+};
+
+/// Extensions of the diagnostics files the server accepts
+const DIAGNOSTICS_EXTENSIONS: [&str; 3] = [".log", ".zip", ".gz"];
+
+/// Check the name the charger gives to its diagnostics upload, as it ends up in the file handling
+/// of the upload location
+fn validate_file_name(file_name: &str) -> Result<(), String> {
+    if file_name.is_empty() {
+        return Err("Diagnostics file name is empty".to_string());
+    }
+    if file_name.chars().count() > 255 {
+        return Err("Diagnostics file name is longer than 255 characters".to_string());
+    }
+    if file_name.contains("..") || file_name.contains(['/', '\\']) {
+        return Err(format!("Diagnostics file name {file_name:?} is a path"));
+    }
+    let lowercase = file_name.to_lowercase();
+    if !DIAGNOSTICS_EXTENSIONS
+        .iter()
+        .any(|extension| lowercase.ends_with(extension))
+    {
+        return Err(format!(
+            "Diagnostics file name {file_name:?} has none of the extensions {}",
+            DIAGNOSTICS_EXTENSIONS.join(", ")
+        ));
+    }
+    Ok(())
+}
+
+/// Ask the charger to upload its diagnostics, see OCPP 1.6 GetDiagnostics. A response with an
+/// invalid file name fails like a `GenericError` CallError
+pub async fn get_diagnostics(
+    station_id: &StationId,
+    request: &GetDiagnosticsRequest,
+) -> Result<GetDiagnosticsResponse, OcppError> {
+    let response: GetDiagnosticsResponse =
+        commands::send_call(station_id, OcppActionEnum::GetDiagnostics, request).await?;
+    if let Some(file_name) = &response.file_name
+        && let Err(reason) = validate_file_name(file_name)
+    {
+        error!("Invalid GetDiagnostics response from {station_id}: {reason}");
+        return Err(OcppError::CallError {
+            error_code: "GenericError".to_string(),
+            error_description: reason,
+        });
+    }
+    Ok(response)
+}
@@ -0,0 +1,92 @@
+use std::{collections::HashSet, str::FromStr, sync::LazyLock};
+
+use dotenvy_macro::dotenv;
+
+use crate::OcppActionEnum;
+
+/// Feature profiles of OCPP 1.6, see section 3.1 of the specification
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum OcppFeatureProfile {
+    Core,
+    FirmwareManagement,
+    LocalAuthListManagement,
+    RemoteTrigger,
+    Reservation,
+    SmartCharging,
+}
+
+impl FromStr for OcppFeatureProfile {
+    type Err = String;
+
+    fn from_str(str: &str) -> Result<Self, Self::Err> {
+        match str {
+            "Core" => Ok(Self::Core),
+            "FirmwareManagement" => Ok(Self::FirmwareManagement),
+            "LocalAuthListManagement" => Ok(Self::LocalAuthListManagement),
+            "RemoteTrigger" => Ok(Self::RemoteTrigger),
+            "Reservation" => Ok(Self::Reservation),
+            "SmartCharging" => Ok(Self::SmartCharging),
+            _ => Err(format!("Unknown OCPP feature profile: {str}")),
+        }
+    }
+}
+
+/// Profiles listed in `OCPP_FEATURE_PROFILES`, separated by commas. Core is always enabled, no
+/// charger can work without it
+static ENABLED_PROFILES: LazyLock<HashSet<OcppFeatureProfile>> = LazyLock::new(|| {
+    const OCPP_FEATURE_PROFILES: &str = dotenv!("OCPP_FEATURE_PROFILES");
+    OCPP_FEATURE_PROFILES
+        .split(',')
+        .map(str::trim)
+        .filter(|profile| !profile.is_empty())
+        .map(|profile| {
+            profile
+                .parse()
+                .expect("OCPP_FEATURE_PROFILES must be a list of OCPP 1.6 feature profiles")
+        })
+        .chain([OcppFeatureProfile::Core])
+        .collect()
+});
+
+/// Enabled feature profiles, sorted
+pub fn enabled_profiles() -> Vec<OcppFeatureProfile> {
+    let mut profiles: Vec<_> = ENABLED_PROFILES
+        .iter()
+        .copied()
+        .collect();
+    profiles.sort();
+    profiles
+}
+
+/// Feature profile of the action, `None` for the actions of the Security whitepaper, which is not
+/// one of the profiles of OCPP 1.6
+fn profile(action: &OcppActionEnum) -> Option<OcppFeatureProfile> {
+    use OcppActionEnum::*;
+    match action {
+        Authorize
+        | BootNotification
+        | ChangeAvailability
+        | ChangeConfiguration
+        | DataTransfer
+        | ClearCache
+        | GetConfiguration
+        | Heartbeat
+        | MeterValues
+        | RemoteStartTransaction
+        | RemoteStopTransaction
+        | Reset
+        | StatusNotification
+        | StartTransaction
+        | StopTransaction
+        | UnlockConnector => Some(OcppFeatureProfile::Core),
+        ClearChargingProfile => Some(OcppFeatureProfile::SmartCharging),
+        GetLocalListVersion | SendLocalList => Some(OcppFeatureProfile::LocalAuthListManagement),
+        GetDiagnostics | UpdateFirmware => Some(OcppFeatureProfile::FirmwareManagement),
+        SignCertificate | CertificateSigned => None,
+    }
+}
+
+/// Disabled feature profile of the action, `None` when the action can be handled
+pub fn disabled_profile(action: &OcppActionEnum) -> Option<OcppFeatureProfile> {
+    profile(action).filter(|profile| !ENABLED_PROFILES.contains(profile))
+}
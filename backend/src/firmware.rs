@@ -0,0 +1,39 @@
+use semver::{BuildMetadata, Prerelease, Version};
+
+/// Parse a firmware version reported by a charger into a semantic version. Vendors format them
+/// freely, so a prefix like `v` or `FW_` is skipped, `_` is accepted as a separator, missing parts
+/// are zero, a fourth part becomes build metadata and a suffix like `-rc1` the pre-release
+pub fn normalize_firmware_version(version: &str) -> Option<Version> {
+    let version = version
+        .trim()
+        .trim_start_matches(|c: char| !c.is_ascii_digit());
+    if let Ok(version) = Version::parse(version) {
+        return Some(version);
+    }
+    let core_length = version
+        .find(|c: char| !(c.is_ascii_digit() || c == '.' || c == '_'))
+        .unwrap_or(version.len());
+    let (core, suffix) = version.split_at(core_length);
+    let parts = core
+        .trim_end_matches(['.', '_'])
+        .split(['.', '_'])
+        .map(|part| part.parse().ok())
+        .collect::<Option<Vec<u64>>>()?;
+    let (&major, rest) = parts.split_first()?;
+    if rest.len() > 3 {
+        return None;
+    }
+    let mut normalized = Version::new(
+        major,
+        rest.first().copied().unwrap_or(0),
+        rest.get(1).copied().unwrap_or(0),
+    );
+    if let Some(build) = rest.get(2) {
+        normalized.build = BuildMetadata::new(&build.to_string()).ok()?;
+    }
+    let suffix = suffix.trim_start_matches(['-', '_', '.', '+']);
+    if !suffix.is_empty() {
+        normalized.pre = Prerelease::new(&suffix.replace('_', ".")).ok()?;
+    }
+    Some(normalized)
+}
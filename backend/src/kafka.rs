@@ -0,0 +1,73 @@
+use std::{sync::LazyLock, time::Duration};
+
+use dotenvy_macro::dotenv;
+use rdkafka::{
+    producer::{FutureProducer, FutureRecord},
+    ClientConfig,
+};
+use serde::Serialize;
+use tracing::{error, warn, Instrument, Span};
+
+use crate::db;
+
+const SESSION_STARTED_TOPIC: &str = "ocpp.session.started";
+const SESSION_STOPPED_TOPIC: &str = "ocpp.session.stopped";
+/// Time an event may wait in the producer queue when it is full
+const QUEUE_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// `None` when `KAFKA_BOOTSTRAP_SERVERS` is empty or the producer could not be created, the events
+/// are then dropped
+static PRODUCER: LazyLock<Option<FutureProducer>> = LazyLock::new(|| {
+    const KAFKA_BOOTSTRAP_SERVERS: &str = dotenv!("KAFKA_BOOTSTRAP_SERVERS");
+    if KAFKA_BOOTSTRAP_SERVERS.is_empty() {
+        warn!("KAFKA_BOOTSTRAP_SERVERS is empty, the session events are not published");
+        return None;
+    }
+    ClientConfig::new()
+        .set("bootstrap.servers", KAFKA_BOOTSTRAP_SERVERS)
+        .create()
+        .inspect_err(|err| error!("Failed to create the Kafka producer: {err:?}"))
+        .ok()
+});
+
+#[derive(Serialize)]
+struct SessionStarted<'a> {
+    transaction_id: i32,
+    #[serde(flatten)]
+    transaction: &'a db::NewTransaction<'a>,
+}
+
+/// Publish a stored StartTransaction for the billing, analytics and CRM systems
+pub fn publish_session_started(transaction_id: i32, transaction: &db::NewTransaction) {
+    let event = SessionStarted { transaction_id, transaction };
+    publish(SESSION_STARTED_TOPIC, transaction.station_id, &event);
+}
+
+/// Publish a stored StopTransaction for the billing, analytics and CRM systems
+pub fn publish_session_stopped(transaction: &db::CompletedTransaction) {
+    publish(SESSION_STOPPED_TOPIC, &transaction.station_id, transaction);
+}
+
+/// Send the event in the background, keyed by station so the events of a charger stay ordered. A
+/// Kafka failure is only logged, the OCPP response never waits for it
+fn publish(topic: &'static str, station_id: &str, event: &impl Serialize) {
+    let Some(producer) = PRODUCER.as_ref() else {
+        return;
+    };
+    let payload = serde_json::to_string(event).unwrap();
+    let station_id = station_id.to_string();
+    tokio::spawn(
+        async move {
+            let record = FutureRecord::to(topic)
+                .key(&station_id)
+                .payload(&payload);
+            if let Err((err, _)) = producer
+                .send(record, QUEUE_TIMEOUT)
+                .await
+            {
+                error!("Failed to publish to {topic} the event of {station_id}: {err:?}");
+            }
+        }
+        .instrument(Span::current()),
+    );
+}
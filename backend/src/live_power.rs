@@ -0,0 +1,138 @@
+use std::sync::LazyLock;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use dashmap::DashMap;
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::{
+    messages::meter_values::MeterValuesRequest,
+    types::{Measurand, MeterValue, UnitOfMeasure},
+};
+
+use crate::{connectors::ConnectorId, StationId};
+
+/// Latest power reported by each connector, so that dashboards get it without querying the
+/// meter readings
+static LATEST_POWER: LazyLock<DashMap<(StationId, ConnectorId), PowerReading>> =
+    LazyLock::new(Default::default);
+
+#[derive(Debug, Clone, PartialEq)]
+struct PowerReading {
+    power_w: f64,
+    current_a: Option<f64>,
+    voltage_v: Option<f64>,
+    timestamp: DateTime<Utc>,
+}
+
+/// Power of a connector as last reported, as returned by the REST API
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct RealTimePower {
+    pub power_w: f64,
+    /// Highest current of the phases
+    pub current_a: Option<f64>,
+    /// Voltage averaged over the phases
+    pub voltage_v: Option<f64>,
+    pub measurand_timestamp: DateTime<Utc>,
+    pub age_secs: i64,
+    /// Whether the reading is older than `STALE_METER_THRESHOLD_SECS`
+    pub stale: bool,
+}
+
+fn stale_meter_threshold() -> TimeDelta {
+    const STALE_METER_THRESHOLD_SECS: &str = dotenv!("STALE_METER_THRESHOLD_SECS");
+    TimeDelta::seconds(
+        STALE_METER_THRESHOLD_SECS
+            .parse()
+            .expect("STALE_METER_THRESHOLD_SECS must be a number of seconds"),
+    )
+}
+
+/// Keep the latest Power.Active.Import of the MeterValues, with the current and voltage sampled
+/// along with it. Meter values sent late, e.g. queued while the charger was offline, do not
+/// replace a more recent reading
+pub fn update(station_id: &StationId, meter_values: &MeterValuesRequest) {
+    let Some(reading) = meter_values
+        .meter_value
+        .iter()
+        .filter_map(power_reading)
+        .max_by_key(|reading| reading.timestamp)
+    else {
+        return;
+    };
+    LATEST_POWER
+        .entry((station_id.clone(), meter_values.connector_id))
+        .and_modify(|latest| {
+            if reading.timestamp >= latest.timestamp {
+                *latest = reading.clone();
+            }
+        })
+        .or_insert(reading);
+}
+
+/// Power last reported by the connector, `None` when it never reported any
+pub fn real_time_power(station_id: &StationId, connector_id: ConnectorId) -> Option<RealTimePower> {
+    let reading = LATEST_POWER
+        .get(&(station_id.clone(), connector_id))?
+        .clone();
+    // A charger clock ahead of the server would make the age negative
+    let age = (Utc::now() - reading.timestamp).max(TimeDelta::zero());
+    Some(RealTimePower {
+        power_w: reading.power_w,
+        current_a: reading.current_a,
+        voltage_v: reading.voltage_v,
+        measurand_timestamp: reading.timestamp,
+        age_secs: age.num_seconds(),
+        stale: age > stale_meter_threshold(),
+    })
+}
+
+fn power_reading(meter_value: &MeterValue) -> Option<PowerReading> {
+    // The phases of a three-phase charger add up to its power
+    let power_w = sampled(meter_value, Measurand::PowerActiveImport)?
+        .into_iter()
+        .sum();
+    let current_a = sampled(meter_value, Measurand::CurrentImport)
+        .and_then(|currents| currents.into_iter().reduce(f64::max));
+    let voltage_v = sampled(meter_value, Measurand::Voltage)
+        .map(|voltages| voltages.iter().sum::<f64>() / voltages.len() as f64);
+    Some(PowerReading {
+        power_w,
+        current_a,
+        voltage_v,
+        timestamp: meter_value.timestamp,
+    })
+}
+
+/// Values of the measurand in W, A or V, the total one when the charger reports it along with the
+/// phases. `None` when the meter value does not sample the measurand
+fn sampled(meter_value: &MeterValue, measurand: Measurand) -> Option<Vec<f64>> {
+    let values: Vec<_> = meter_value
+        .sampled_value
+        .iter()
+        .filter(|sampled_value| sampled_value.measurand.as_ref() == Some(&measurand))
+        .filter_map(|sampled_value| {
+            let value: f64 = sampled_value.value.parse().ok()?;
+            let value = match sampled_value.unit {
+                Some(UnitOfMeasure::Kw) => value * 1000.0,
+                _ => value,
+            };
+            Some((sampled_value.phase.is_none(), value))
+        })
+        .collect();
+    if values.is_empty() {
+        return None;
+    }
+    let total: Vec<_> = values
+        .iter()
+        .filter(|(total, _)| *total)
+        .map(|(_, value)| *value)
+        .collect();
+    if !total.is_empty() {
+        return Some(total);
+    }
+    Some(
+        values
+            .into_iter()
+            .map(|(_, value)| value)
+            .collect(),
+    )
+}
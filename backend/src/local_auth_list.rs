@@ -0,0 +1,194 @@
+use rust_ocpp::v1_6::{
+    messages::send_local_list::SendLocalListRequest,
+    types::{AuthorizationData, AuthorizationStatus, UpdateStatus, UpdateType},
+};
+use tracing::{error, warn};
+
+use crate::{db, mask::mask_id_tag, StationId};
+
+fn status_name(status: &AuthorizationStatus) -> &'static str {
+    match status {
+        AuthorizationStatus::Accepted => "Accepted",
+        AuthorizationStatus::Blocked => "Blocked",
+        AuthorizationStatus::Expired => "Expired",
+        AuthorizationStatus::Invalid => "Invalid",
+        AuthorizationStatus::ConcurrentTx => "ConcurrentTx",
+    }
+}
+
+/// Version of the Local Authorization List of the station, see OCPP 1.6 GetLocalListVersion
+pub async fn version(station_id: &StationId) -> Option<i32> {
+    let _permit = db::ocpp_permit("reading the local list version").await?;
+    match db::local_auth_list_version(station_id).await {
+        Ok(list_version) => Some(list_version),
+        Err(err) => {
+            error!("Failed to read local list version of {station_id}: {err:?}");
+            None
+        },
+    }
+}
+
+/// Replace or update the Local Authorization List of the station, see OCPP 1.6 SendLocalList
+pub async fn update(station_id: &StationId, send_local_list: SendLocalListRequest) -> UpdateStatus {
+    let full = send_local_list.update_type == UpdateType::Full;
+    let Some(entries) = list_entries(
+        station_id,
+        full,
+        send_local_list
+            .local_authorization_list
+            .unwrap_or_default(),
+    ) else {
+        return UpdateStatus::Failed;
+    };
+    let Some(_permit) = db::ocpp_permit("updating the local list").await else {
+        return UpdateStatus::Failed;
+    };
+    match db::update_local_auth_list(station_id, send_local_list.list_version, full, &entries).await
+    {
+        Ok(true) => UpdateStatus::Accepted,
+        Ok(false) => {
+            warn!(
+                "Differential local list version {} of {station_id} is older than the current one",
+                send_local_list.list_version
+            );
+            UpdateStatus::VersionMismatch
+        },
+        Err(err) => {
+            error!("Failed to update local list of {station_id}: {err:?}");
+            UpdateStatus::Failed
+        },
+    }
+}
+
+/// Entries of a list update, `None` when a Full list has an idTag without idTagInfo. A Full list
+/// only holds authorized idTags, removing an idTag is a Differential update
+fn list_entries(
+    station_id: &StationId,
+    full: bool,
+    authorizations: Vec<AuthorizationData>,
+) -> Option<Vec<db::LocalAuthListEntry>> {
+    let mut entries = Vec::new();
+    for authorization in authorizations {
+        if full && authorization.id_tag_info.is_none() {
+            warn!(
+                "Full local list of {station_id} has idTag {} without idTagInfo",
+                mask_id_tag(&authorization.id_tag)
+            );
+            return None;
+        }
+        let id_tag_info = authorization.id_tag_info;
+        entries.push(db::LocalAuthListEntry {
+            id_tag: authorization.id_tag,
+            status: id_tag_info
+                .as_ref()
+                .map(|info| status_name(&info.status).to_string()),
+            expiry_date: id_tag_info
+                .as_ref()
+                .and_then(|info| info.expiry_date),
+            parent_id_tag: id_tag_info.and_then(|info| info.parent_id_tag),
+        });
+    }
+    Some(entries)
+}
+
+/// Whether an update to `list_version` applies over the current list. A Full update replaces the
+/// list whatever its version, a Differential one must not be older than the current list
+pub fn applies_over(full: bool, list_version: i32, current_version: Option<i32>) -> bool {
+    full || current_version.is_none_or(|current_version| list_version >= current_version)
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_ocpp::v1_6::types::IdTagInfo;
+
+    use super::*;
+
+    fn authorization(id_tag: &str, status: Option<AuthorizationStatus>) -> AuthorizationData {
+        AuthorizationData {
+            id_tag: id_tag.to_string(),
+            id_tag_info: status.map(|status| IdTagInfo {
+                expiry_date: None,
+                parent_id_tag: Some("FLEET-1".to_string()),
+                status,
+            }),
+        }
+    }
+
+    fn entry(id_tag: &str, status: Option<&str>) -> db::LocalAuthListEntry {
+        db::LocalAuthListEntry {
+            id_tag: id_tag.to_string(),
+            status: status.map(str::to_string),
+            expiry_date: None,
+            parent_id_tag: status.map(|_| "FLEET-1".to_string()),
+        }
+    }
+
+    #[test]
+    fn full_update() {
+        let entries = list_entries(
+            &"LOCAL-LIST".to_string(),
+            true,
+            vec![
+                authorization("B4F62CEF", Some(AuthorizationStatus::Accepted)),
+                authorization("7C3A91D2", Some(AuthorizationStatus::Blocked)),
+            ],
+        );
+        assert_eq!(
+            entries,
+            Some(vec![
+                entry("B4F62CEF", Some("Accepted")),
+                entry("7C3A91D2", Some("Blocked")),
+            ])
+        );
+    }
+
+    #[test]
+    fn differential_update_removes_the_id_tags_without_id_tag_info() {
+        let entries = list_entries(
+            &"LOCAL-LIST".to_string(),
+            false,
+            vec![
+                authorization("B4F62CEF", Some(AuthorizationStatus::Accepted)),
+                authorization("7C3A91D2", None),
+            ],
+        );
+        assert_eq!(
+            entries,
+            Some(vec![
+                entry("B4F62CEF", Some("Accepted")),
+                entry("7C3A91D2", None),
+            ])
+        );
+    }
+
+    #[tokio::test]
+    async fn full_update_without_id_tag_info_fails() {
+        let send_local_list = SendLocalListRequest {
+            list_version: 2,
+            local_authorization_list: Some(vec![
+                authorization("B4F62CEF", Some(AuthorizationStatus::Accepted)),
+                authorization("7C3A91D2", None),
+            ]),
+            update_type: UpdateType::Full,
+        };
+        // Fails before the list is stored
+        assert_eq!(
+            update(&"LOCAL-LIST".to_string(), send_local_list).await,
+            UpdateStatus::Failed
+        );
+    }
+
+    #[test]
+    fn differential_update_of_an_older_version_is_a_mismatch() {
+        assert!(!applies_over(false, 2, Some(3)));
+        assert!(applies_over(false, 3, Some(3)));
+        assert!(applies_over(false, 4, Some(3)));
+        assert!(applies_over(false, 1, None));
+    }
+
+    #[test]
+    fn full_update_applies_whatever_its_version() {
+        assert!(applies_over(true, 2, Some(3)));
+        assert!(applies_over(true, 1, None));
+    }
+}
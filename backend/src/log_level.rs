@@ -0,0 +1,52 @@
+use std::sync::OnceLock;
+
+use tracing::{info, level_filters::LevelFilter};
+use tracing_subscriber::{
+    fmt, layer::SubscriberExt, reload, util::SubscriberInitExt, EnvFilter, Registry,
+};
+
+/// Handle to replace the filter of the logs while the server runs, set by `init`
+static FILTER_HANDLE: OnceLock<reload::Handle<EnvFilter, Registry>> = OnceLock::new();
+
+/// Log with the filter of `RUST_LOG`, e.g. `info,moovolt_backend_csms=debug,sqlx=warn`, or at the
+/// info level when it is not set
+pub fn init() {
+    let filter = EnvFilter::builder()
+        .with_default_directive(LevelFilter::INFO.into())
+        .from_env_lossy();
+    let (filter, handle) = reload::Layer::new(filter);
+    tracing_subscriber::registry()
+        .with(filter)
+        .with(fmt::layer())
+        .init();
+    FILTER_HANDLE
+        .set(handle)
+        .expect("Logging is initialized once");
+}
+
+/// Current filter of the logs, in the syntax of `RUST_LOG`
+pub fn filter() -> String {
+    FILTER_HANDLE
+        .get()
+        .and_then(|handle| {
+            handle
+                .with_current(|filter| filter.to_string())
+                .ok()
+        })
+        .unwrap_or_default()
+}
+
+/// Replace the filter of the logs until the next restart, without changing `RUST_LOG`
+pub fn set_filter(directives: &str) -> Result<(), String> {
+    let filter = EnvFilter::builder()
+        .parse(directives)
+        .map_err(|err| format!("Invalid filter {directives:?}: {err}"))?;
+    let handle = FILTER_HANDLE
+        .get()
+        .ok_or("Logging is not initialized")?;
+    handle
+        .reload(filter)
+        .map_err(|err| err.to_string())?;
+    info!("Log filter set to {directives:?}");
+    Ok(())
+}
@@ -1,44 +1,162 @@
-use std::{net::SocketAddr, panic, str::FromStr};
+use std::{
+    net::{IpAddr, SocketAddr},
+    panic,
+    str::FromStr,
+    time::Duration,
+};
 
 use axum::{
-    extract::{ws::Message as AxumWSMessage, ConnectInfo},
+    extract::{ws::Message as AxumWSMessage, Path},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
     routing::get,
     Router,
 };
 use axum_extra::TypedHeader;
-use chrono::Utc;
+use chrono::{DateTime, SecondsFormat, TimeDelta, TimeZone, Utc};
+use chrono_tz::Tz;
 use dotenvy_macro::dotenv;
-use futures::StreamExt;
+use futures::{Sink, SinkExt, StreamExt};
+use metrics_exporter_prometheus::{PrometheusBuilder, PrometheusHandle};
 use owo_colors::OwoColorize;
-use rust_ocpp::v1_6::messages::{
-    authorize::{AuthorizeRequest, AuthorizeResponse},
-    boot_notification::{BootNotificationRequest, BootNotificationResponse},
-    change_availability::ChangeAvailabilityRequest,
-    change_configuration::{ChangeConfigurationRequest, ChangeConfigurationResponse},
-    clear_cache::{ClearCacheRequest, ClearCacheResponse},
-    data_transfer::{DataTransferRequest, DataTransferResponse},
-    get_configuration::{GetConfigurationRequest, GetConfigurationResponse},
-    heart_beat::{HeartbeatRequest, HeartbeatResponse},
-    meter_values::{MeterValuesRequest, MeterValuesResponse},
-    remote_start_transaction::{RemoteStartTransactionRequest, RemoteStartTransactionResponse},
-    remote_stop_transaction::{RemoteStopTransactionRequest, RemoteStopTransactionResponse},
-    reset::{ResetRequest, ResetResponse},
-    start_transaction::{StartTransactionRequest, StartTransactionResponse},
-    status_notification::{StatusNotificationRequest, StatusNotificationResponse},
-    stop_transaction::{StopTransactionRequest, StopTransactionResponse},
-    unlock_connector::{UnlockConnectorRequest, UnlockConnectorResponse},
+use rust_ocpp::v1_6::{
+    messages::{
+        authorize::{AuthorizeRequest, AuthorizeResponse},
+        boot_notification::{BootNotificationRequest, BootNotificationResponse},
+        change_availability::{ChangeAvailabilityRequest, ChangeAvailabilityResponse},
+        change_configuration::{ChangeConfigurationRequest, ChangeConfigurationResponse},
+        clear_cache::{ClearCacheRequest, ClearCacheResponse},
+        clear_charging_profile::{ClearChargingProfileRequest, ClearChargingProfileResponse},
+        data_transfer::{DataTransferRequest, DataTransferResponse},
+        get_configuration::{GetConfigurationRequest, GetConfigurationResponse},
+        get_diagnostics::{GetDiagnosticsRequest, GetDiagnosticsResponse},
+        get_local_list_version::{GetLocalListVersionRequest, GetLocalListVersionResponse},
+        heart_beat::{HeartbeatRequest, HeartbeatResponse},
+        meter_values::{MeterValuesRequest, MeterValuesResponse},
+        remote_start_transaction::{RemoteStartTransactionRequest, RemoteStartTransactionResponse},
+        remote_stop_transaction::{RemoteStopTransactionRequest, RemoteStopTransactionResponse},
+        reset::{ResetRequest, ResetResponse},
+        send_local_list::{SendLocalListRequest, SendLocalListResponse},
+        start_transaction::{StartTransactionRequest, StartTransactionResponse},
+        status_notification::{StatusNotificationRequest, StatusNotificationResponse},
+        stop_transaction::{StopTransactionRequest, StopTransactionResponse},
+        unlock_connector::{UnlockConnectorRequest, UnlockConnectorResponse},
+        update_firmware::{UpdateFirmwareRequest, UpdateFirmwareResponse},
+    },
+    types::{ChargePointStatus, Measurand, UnitOfMeasure},
 };
 use strum_macros::Display;
-use tokio::{net, sync::OnceCell};
-use tracing::{debug, error, info, warn, Level};
+use tokio::{
+    net,
+    sync::{mpsc, OnceCell},
+    time::{Instant, MissedTickBehavior},
+};
+use tower_http::trace::TraceLayer;
+use tracing::{debug, error, field, info, info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+use crate::{
+    alerts::AlertEvent,
+    call_direction::MessageSender,
+    certificates::{
+        CertificateSignedRequest, CertificateSignedResponse, GenericStatus, SignCertificateRequest,
+        SignCertificateResponse,
+    },
+    client_ip::ClientIp,
+    connectors::ConnectorStatus,
+    mask::Masked,
+    ocpp_version::OcppVersion,
+};
 
-type OcppMessageTypeId = usize;
+#[cfg(test)]
+mod action_payload_tests;
+mod admin_auth;
+mod alerts;
+mod api;
+mod auth;
+mod call_direction;
+mod availability_overrides;
+mod billing_anomalies;
+mod capabilities;
+mod certificates;
+mod charger_auth;
+mod charger_groups;
+mod client_ip;
+mod commands;
+mod configuration;
+mod connectors;
+mod cost_breakdown;
+mod dashboard;
+mod db;
+mod debug_mode;
+mod diagnostics;
+mod feature_profiles;
+mod firmware;
+#[cfg(feature = "kafka")]
+mod kafka;
+mod live_power;
+mod local_auth_list;
+mod log_level;
+mod maintenance;
+mod mask;
+mod meter_stats;
+#[cfg(test)]
+mod mock_charger;
+mod ocpp_version;
+mod outbound;
+mod rate_limit;
+mod remote_start;
+mod remote_stop;
+mod server_configuration;
+#[cfg(feature = "soap")]
+mod soap;
+mod status_notifications;
+mod stop_reasons;
+mod tcp;
+mod transactions;
+mod uptime;
+#[cfg(feature = "web-ui")]
+mod web_ui;
+#[cfg(test)]
+mod router_tests;
+#[cfg(test)]
+mod tracing_tests;
+#[cfg(test)]
+mod wire_format_tests;
+
+type StationId = String;
+type OcppMessageTypeId = MessageTypeId;
 type OcppMessageId = String;
 type OcppErrorCode = String;
 type OcppErrorDescription = String;
 type OcppErrorDetails = serde_json::Value;
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+/// OCPP message type. Any other value than Call (2), CallResult (3) or CallError (4) fails to
+/// parse, so malformed frames never reach the handlers
+#[derive(serde::Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(transparent)]
+pub struct MessageTypeId(u8);
+
+impl MessageTypeId {
+    pub const CALL: Self = Self(2);
+    pub const CALL_ERROR: Self = Self(4);
+    pub const CALL_RESULT: Self = Self(3);
+}
+
+impl<'de> serde::Deserialize<'de> for MessageTypeId {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let message_type_id = u8::deserialize(deserializer)?;
+        match Self(message_type_id) {
+            id @ (Self::CALL | Self::CALL_RESULT | Self::CALL_ERROR) => Ok(id),
+            _ => Err(serde::de::Error::invalid_value(
+                serde::de::Unexpected::Unsigned(message_type_id.into()),
+                &"2 (Call), 3 (CallResult) or 4 (CallError)",
+            )),
+        }
+    }
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
 #[serde(untagged)]
 pub enum OcppActionEnum {
     // OCPP 1.6 JSON
@@ -59,6 +177,17 @@ pub enum OcppActionEnum {
     StartTransaction,
     StopTransaction,
     UnlockConnector,
+    // Smart Charging
+    ClearChargingProfile,
+    // Local Auth List Management
+    GetLocalListVersion,
+    SendLocalList,
+    // Firmware Management
+    GetDiagnostics,
+    UpdateFirmware,
+    // Security (OCPP 1.6 Security Whitepaper)
+    SignCertificate,
+    CertificateSigned,
 }
 
 impl FromStr for OcppActionEnum {
@@ -82,6 +211,13 @@ impl FromStr for OcppActionEnum {
             "StartTransaction" => Ok(Self::StartTransaction),
             "StopTransaction" => Ok(Self::StopTransaction),
             "UnlockConnector" => Ok(Self::UnlockConnector),
+            "ClearChargingProfile" => Ok(Self::ClearChargingProfile),
+            "GetLocalListVersion" => Ok(Self::GetLocalListVersion),
+            "SendLocalList" => Ok(Self::SendLocalList),
+            "GetDiagnostics" => Ok(Self::GetDiagnostics),
+            "UpdateFirmware" => Ok(Self::UpdateFirmware),
+            "SignCertificate" => Ok(Self::SignCertificate),
+            "CertificateSigned" => Ok(Self::CertificateSigned),
             _ => Err(format!("Unknown OCPP action: {str}")),
         }
     }
@@ -105,7 +241,7 @@ pub enum BootNotificationKind {
 #[serde(untagged)]
 pub enum ChangeAvailabilityKind {
     Request(ChangeAvailabilityRequest),
-    Response(ChangeAvailabilityRequest),
+    Response(ChangeAvailabilityResponse),
 }
 
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
@@ -199,7 +335,59 @@ pub enum UnlockConnectorKind {
     Response(UnlockConnectorResponse),
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
+#[serde(untagged)]
+pub enum ClearChargingProfileKind {
+    Request(ClearChargingProfileRequest),
+    Response(ClearChargingProfileResponse),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
+#[serde(untagged)]
+pub enum GetLocalListVersionKind {
+    Request(GetLocalListVersionRequest),
+    Response(GetLocalListVersionResponse),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
+#[serde(untagged)]
+pub enum SendLocalListKind {
+    Request(SendLocalListRequest),
+    Response(SendLocalListResponse),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
+#[serde(untagged)]
+pub enum GetDiagnosticsKind {
+    Request(GetDiagnosticsRequest),
+    Response(GetDiagnosticsResponse),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
+#[serde(untagged)]
+pub enum UpdateFirmwareKind {
+    Request(UpdateFirmwareRequest),
+    Response(UpdateFirmwareResponse),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
+#[serde(untagged)]
+pub enum SignCertificateKind {
+    Request(SignCertificateRequest),
+    Response(SignCertificateResponse),
+}
+
+#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq, Display)]
+#[serde(untagged)]
+pub enum CertificateSignedKind {
+    Request(CertificateSignedRequest),
+    Response(CertificateSignedResponse),
+}
+
+/// Payload of a Call or CallResult. It is parsed according to the action of its Call, see
+/// [`OcppPayload::deserialize_request`], as the untagged variants cannot be told apart by their
+/// fields alone
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum OcppPayload {
     // OCPP 1.6 JSON
@@ -220,9 +408,142 @@ pub enum OcppPayload {
     StatusNotification(StatusNotificationKind),         // Charger → Server
     StopTransaction(StopTransactionKind),               // Charger → Server
     UnlockConnector(UnlockConnectorKind),               // Server → Charger
+    // Smart Charging
+    ClearChargingProfile(ClearChargingProfileKind), // Server → Charger
+    // Local Auth List Management
+    GetLocalListVersion(GetLocalListVersionKind), // Server → Charger
+    SendLocalList(SendLocalListKind),             // Server → Charger
+    // Firmware Management
+    GetDiagnostics(GetDiagnosticsKind), // Server → Charger
+    UpdateFirmware(UpdateFirmwareKind), // Server → Charger
+    // Security (OCPP 1.6 Security Whitepaper)
+    SignCertificate(SignCertificateKind),     // Charger → Server
+    CertificateSigned(CertificateSignedKind), // Server → Charger
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+impl std::fmt::Display for OcppPayload {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result { Masked(self).fmt(f) }
+}
+
+impl OcppPayload {
+    /// Parse the payload of a Call as the request of its action. Fields the request does not have
+    /// are rejected, as by the JSON schemas of OCPP 1.6, so the payload of another action fails
+    /// with e.g. "Expected BootNotificationRequest but got unknown field 'meterStop'"
+    pub fn deserialize_request<'de, D>(
+        action: &OcppActionEnum,
+        deserializer: D,
+    ) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        fn request<'de, T, D>(action: &OcppActionEnum, deserializer: D) -> Result<T, D::Error>
+        where
+            T: serde::Deserialize<'de>,
+            D: serde::Deserializer<'de>,
+        {
+            let mut unknown_field = None;
+            let request = serde_ignored::deserialize(deserializer, |path| {
+                unknown_field.get_or_insert_with(|| path.to_string());
+            })
+            .map_err(|err| {
+                serde::de::Error::custom(format!("Expected {action}Request but got {err}"))
+            })?;
+            match unknown_field {
+                Some(field) => Err(serde::de::Error::custom(format!(
+                    "Expected {action}Request but got unknown field '{field}'"
+                ))),
+                None => Ok(request),
+            }
+        }
+        use OcppActionEnum::*;
+        Ok(match action {
+            Authorize => Self::Authorize(AuthorizeKind::Request(request(action, deserializer)?)),
+            BootNotification => Self::BootNotification(BootNotificationKind::Request(request(
+                action,
+                deserializer,
+            )?)),
+            ChangeAvailability => Self::ChangeAvailability(ChangeAvailabilityKind::Request(
+                request(action, deserializer)?,
+            )),
+            ChangeConfiguration => Self::ChangeConfiguration(ChangeConfigurationKind::Request(
+                request(action, deserializer)?,
+            )),
+            ClearCache => Self::ClearCache(ClearCacheKind::Request(request(action, deserializer)?)),
+            DataTransfer => {
+                Self::DataTransfer(DataTransferKind::Request(request(action, deserializer)?))
+            },
+            GetConfiguration => Self::GetConfiguration(GetConfigurationKind::Request(request(
+                action,
+                deserializer,
+            )?)),
+            Heartbeat => Self::Heartbeat(HeartbeatKind::Request(request(action, deserializer)?)),
+            MeterValues => {
+                Self::MeterValues(MeterValuesKind::Request(request(action, deserializer)?))
+            },
+            RemoteStartTransaction => Self::RemoteStartTransaction(
+                RemoteStartTransactionKind::Request(request(action, deserializer)?),
+            ),
+            RemoteStopTransaction => Self::RemoteStopTransaction(
+                RemoteStopTransactionKind::Request(request(action, deserializer)?),
+            ),
+            Reset => Self::Reset(ResetKind::Request(request(action, deserializer)?)),
+            StartTransaction => Self::StartTransaction(StartTransactionKind::Request(request(
+                action,
+                deserializer,
+            )?)),
+            StatusNotification => Self::StatusNotification(StatusNotificationKind::Request(
+                request(action, deserializer)?,
+            )),
+            StopTransaction => {
+                Self::StopTransaction(StopTransactionKind::Request(request(action, deserializer)?))
+            },
+            UnlockConnector => {
+                Self::UnlockConnector(UnlockConnectorKind::Request(request(action, deserializer)?))
+            },
+            ClearChargingProfile => Self::ClearChargingProfile(ClearChargingProfileKind::Request(
+                request(action, deserializer)?,
+            )),
+            GetLocalListVersion => Self::GetLocalListVersion(GetLocalListVersionKind::Request(
+                request(action, deserializer)?,
+            )),
+            SendLocalList => {
+                Self::SendLocalList(SendLocalListKind::Request(request(action, deserializer)?))
+            },
+            GetDiagnostics => {
+                Self::GetDiagnostics(GetDiagnosticsKind::Request(request(action, deserializer)?))
+            },
+            UpdateFirmware => {
+                Self::UpdateFirmware(UpdateFirmwareKind::Request(request(action, deserializer)?))
+            },
+            SignCertificate => {
+                Self::SignCertificate(SignCertificateKind::Request(request(action, deserializer)?))
+            },
+            CertificateSigned => Self::CertificateSigned(CertificateSignedKind::Request(request(
+                action,
+                deserializer,
+            )?)),
+        })
+    }
+
+    /// Connector the request of a charger is about, `None` when it is about no connector
+    pub fn connector_id(&self) -> Option<connectors::ConnectorId> {
+        match self {
+            Self::ChangeAvailability(ChangeAvailabilityKind::Request(request)) => {
+                Some(request.connector_id)
+            },
+            Self::MeterValues(MeterValuesKind::Request(request)) => Some(request.connector_id),
+            Self::StartTransaction(StartTransactionKind::Request(request)) => {
+                Some(request.connector_id)
+            },
+            Self::StatusNotification(StatusNotificationKind::Request(request)) => {
+                Some(request.connector_id)
+            },
+            _ => None,
+        }
+    }
+}
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 /// Call: [<MessageTypeId>, "<MessageId>", "<Action>", {<Payload>}]
 pub struct OcppCall {
@@ -232,7 +553,7 @@ pub struct OcppCall {
     pub payload: OcppPayload,
 }
 
-#[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
 #[serde(rename_all = "PascalCase")]
 /// CallResult: [<MessageTypeId>, "<MessageId>", {<Payload>}]
 pub struct OcppCallResult {
@@ -253,15 +574,61 @@ pub struct OcppCallError {
     pub error_details: OcppErrorDetails,
 }
 
+impl OcppCallError {
+    fn new(message_id: OcppMessageId, error_code: &str, error_description: String) -> Self {
+        Self {
+            message_type_id: MessageTypeId::CALL_ERROR,
+            message_id,
+            error_code: error_code.to_string(),
+            error_description,
+            error_details: serde_json::json!({}),
+        }
+    }
+
+    /// The action of the Call is unknown
+    pub fn not_implemented(message_id: OcppMessageId, action: &str) -> Self {
+        Self::new(
+            message_id,
+            "NotImplemented",
+            format!("{action} is not implemented"),
+        )
+    }
+
+    /// The action of the Call is known but not handled by this server
+    pub fn not_supported(message_id: OcppMessageId, detail: String) -> Self {
+        Self::new(message_id, "NotSupported", detail)
+    }
+
+    /// The Call does not conform to OCPP-J or to the schema of its action
+    pub fn formation_violation(message_id: OcppMessageId, detail: String) -> Self {
+        Self::new(message_id, "FormationViolation", detail)
+    }
+
+    /// A field of the Call has a value the server does not accept, e.g. an unknown connector
+    pub fn property_constraint_violation(message_id: OcppMessageId, detail: String) -> Self {
+        Self::new(message_id, "PropertyConstraintViolation", detail)
+    }
+
+    /// The server failed to process a valid Call
+    pub fn internal_error(message_id: OcppMessageId, detail: String) -> Self {
+        Self::new(message_id, "InternalError", detail)
+    }
+
+    /// Any other error, e.g. a MessageId used twice
+    pub fn generic_error(message_id: OcppMessageId, detail: String) -> Self {
+        Self::new(message_id, "GenericError", detail)
+    }
+}
+
 #[derive(serde::Serialize, serde::Deserialize, Debug, Clone, PartialEq)]
 #[serde(untagged)]
 pub enum OcppMessageType {
     /// OCPP Call
-    Call(usize, String, String, serde_json::Value),
+    Call(MessageTypeId, String, String, serde_json::Value),
     /// OCPP Result
-    CallResult(usize, String, serde_json::Value),
+    CallResult(MessageTypeId, String, serde_json::Value),
     /// OCPP Error
-    CallError(usize, String, String, String, serde_json::Value),
+    CallError(MessageTypeId, String, String, String, serde_json::Value),
 }
 
 static TIME_NOW: OnceCell<String> = OnceCell::const_new();
@@ -275,27 +642,50 @@ async fn main() {
     }
     let _time_now = TIME_NOW.get_or_init(time_now).await;
 
-    tracing_subscriber::fmt()
-        .with_max_level(Level::DEBUG)
-        .init();
+    log_level::init();
+
+    // Prometheus metrics, rendered on the /metrics route
+    let metrics_handle = PrometheusBuilder::new()
+        .install_recorder()
+        .expect("Failed to install Prometheus recorder");
 
     // Get some useful errors before the application ends with panic
-    panic::set_hook(Box::new(|err| {
-        tracing::error!("\n\nPanic: {err:#?}\n\n");
-    }));
+    panic::set_hook(Box::new(panic_hook));
 
     // The server will listen on
     const ADDR: &str = dotenv!("ADDR");
     const PORT: &str = dotenv!("PORT");
     let tcp_listener = net::TcpListener::bind(format!("{ADDR}:{PORT}"))
         .await
-        .expect(&format!("Failed to bind to address: {ADDR}"));
+        .unwrap_or_else(|_| panic!("Failed to bind to address: {ADDR}"));
     info!("Server listening on {ADDR}:{PORT}");
 
-    // Create the Axum router
-    let router = Router::new()
-        .route("/ocpp16j/:station_id", get(upgrade_to_ws))
-        .route("/", get(healthcheck_route));
+    // Connect to the database and apply the migrations
+    const DATABASE_URL: &str = dotenv!("DATABASE_URL");
+    db::init(DATABASE_URL).await;
+    match auth::load_cache().await {
+        Ok(id_tag_count) => info!("Loaded {id_tag_count} idTags into the authorization cache"),
+        Err(err) => error!("Failed to load the authorization cache: {err:?}"),
+    }
+    match maintenance::load().await {
+        Ok(count) => info!("Loaded {count} chargers in maintenance"),
+        Err(err) => error!("Failed to load the chargers in maintenance: {err:?}"),
+    }
+    match transactions::reconcile().await {
+        Ok(count) => info!("Reconciled {count} active transactions from database"),
+        Err(err) => error!("Failed to reconcile the active transactions: {err:?}"),
+    }
+    tokio::spawn(auth::sync_cache());
+    tokio::spawn(transactions::stop_inactive());
+    tokio::spawn(watch_late_heartbeats());
+
+    let router = app(metrics_handle);
+
+    // Chargers without a WebSocket stack connect over raw TCP, when a port is configured
+    const TCP_OCPP_PORT: &str = dotenv!("TCP_OCPP_PORT");
+    if !TCP_OCPP_PORT.is_empty() {
+        tokio::spawn(tcp::serve(format!("{ADDR}:{TCP_OCPP_PORT}")));
+    }
 
     // Start the Axum server
     axum::serve(
@@ -306,12 +696,94 @@ async fn main() {
     .expect("Failed to start server");
 }
 
+// Routes of the server. The charger WebSocket, the REST API and the metrics have prefixes of their
+// own, so that none of them shadows another
+fn app(metrics_handle: PrometheusHandle) -> Router {
+    let router = Router::new()
+        .route(
+            "/ocpp16j/:station_id",
+            get(upgrade_to_ws)
+                .route_layer(axum::middleware::from_fn(charger_auth::require_basic_auth)),
+        )
+        .nest("/api", api::router())
+        .route(
+            "/metrics",
+            get(move || async move { metrics_handle.render() }),
+        );
+    // The dashboard takes over `/` when it is served
+    #[cfg(feature = "web-ui")]
+    let router = router.merge(web_ui::router());
+    #[cfg(not(feature = "web-ui"))]
+    let router = router.route("/", get(healthcheck_route));
+    #[cfg(feature = "soap")]
+    let router = router.route(
+        "/ocpp15s/:station_id",
+        axum::routing::post(soap::handle_soap_request)
+            .route_layer(axum::middleware::from_fn(charger_auth::require_basic_auth)),
+    );
+    // Last, so that every route is traced
+    router.layer(TraceLayer::new_for_http().make_span_with(http_request_span))
+}
+
+// Span of every HTTP request. The WebSocket connection span, and with it the OCPP message handling,
+// is a child of the span of the upgrade request
+fn http_request_span(request: &axum::http::Request<axum::body::Body>) -> Span {
+    info_span!(
+        "http_request",
+        method = %request.method(),
+        uri = %request.uri(),
+        // Recorded by the OCPP routes once extracted from the path
+        station_id = field::Empty,
+    )
+}
+
+// Emit panics as tracing events, so they end up in the same log stream as every other event
+// along with where they happened and which Tokio task panicked
+fn panic_hook(panic_info: &panic::PanicHookInfo) {
+    let payload = panic_info.payload();
+    let message = payload
+        .downcast_ref::<&str>()
+        .copied()
+        .or_else(|| {
+            payload
+                .downcast_ref::<String>()
+                .map(String::as_str)
+        })
+        .unwrap_or("Box<dyn Any>");
+    let location = panic_info.location();
+    let thread = std::thread::current();
+    error!(
+        panic.message = message,
+        panic.file = location.map(|location| location.file()),
+        panic.line = location.map(|location| location.line()),
+        panic.column = location.map(|location| location.column()),
+        thread.name = thread.name(),
+        task.id = tokio::task::try_id().map(|id| id.to_string()),
+        "Panic"
+    );
+}
+
 // Upgrade from a HTTP connection to a WebSocket connection
 async fn upgrade_to_ws(
     ws: axum::extract::WebSocketUpgrade,
+    Path(station_id): Path<StationId>,
     user_agent: Option<TypedHeader<headers::UserAgent>>,
-    ConnectInfo(addr): ConnectInfo<SocketAddr>,
-) -> impl axum::response::IntoResponse {
+    ClientIp(client_ip): ClientIp,
+    headers: HeaderMap,
+) -> axum::response::Response {
+    Span::current().record("station_id", station_id.as_str());
+    // Tells apart the connections of chargers sharing an IP, in every log line of the connection
+    let connection_id = Uuid::new_v4();
+    let span = info_span!("connection", %connection_id, %client_ip);
+    let _entered = span.clone().entered();
+    // Too many connections from a single IP could indicate an attack
+    let Some(connection_guard) = rate_limit::acquire(client_ip) else {
+        warn!(
+            "Rejected connection of {station_id} from {}: too many connections from this IP",
+            rate_limit::IpPrefix::from(client_ip)
+        );
+        return StatusCode::TOO_MANY_REQUESTS.into_response();
+    };
     // Check if the user agent is a valid client
     match user_agent {
         Some(TypedHeader(agent)) => {
@@ -323,104 +795,411 @@ async fn upgrade_to_ws(
         },
         None => warn!("User agent is not present. Continue without specific platform check"),
     }
-    ws.on_upgrade(move |socket| handle_socket(socket, addr))
+    // Chargers offering no supported subprotocol were always served as OCPP 1.6 ones
+    let version = OcppVersion::negotiate(&headers).unwrap_or_else(|| {
+        warn!("{station_id} offered no supported OCPP subprotocol, assuming OCPP 1.6");
+        OcppVersion::V16
+    });
+    ws.protocols([version.protocol()])
+        .max_message_size(MAX_MESSAGE_LENGTH)
+        .on_upgrade(move |socket| {
+            async move {
+                handle_socket(socket, client_ip, station_id, connection_id, version).await;
+                drop(connection_guard);
+            }
+            .instrument(span)
+        })
+        .into_response()
 }
 
-async fn handle_socket(mut socket: axum::extract::ws::WebSocket, addr: SocketAddr) {
-    info!(
-        "{} {addr}",
-        "New WebSocket connection:"
-            .green()
-            .bold()
-    );
+async fn handle_socket(
+    socket: axum::extract::ws::WebSocket,
+    client_ip: IpAddr,
+    station_id: StationId,
+    connection_id: Uuid,
+    version: OcppVersion,
+) {
+    let protocol_version = socket
+        .protocol()
+        .and_then(|protocol| protocol.to_str().ok())
+        .map(str::to_string);
+    // Server-initiated Calls are queued on this channel and passed to the writer below
+    let (connection, mut outbound_receiver) = ChargerConnection::open(
+        "WebSocket",
+        &station_id,
+        connection_id,
+        client_ip,
+        version,
+        protocol_version,
+    )
+    .await;
+    // Responses and Calls are written to the socket by a task of their own
+    let (socket_writer, mut socket) = socket.split();
+    let mut outbound = outbound::spawn_writer(socket_writer);
 
-    while let Some(Ok(msg)) = socket.next().await {
+    loop {
+        let msg = tokio::select! {
+            msg = socket.next() => match msg {
+                Some(Ok(msg)) => msg,
+                _ => break,
+            },
+            Some(call) = outbound_receiver.recv() => {
+                if let Err(err) = outbound.send(AxumWSMessage::Text(call)).await {
+                    error!("Failed to send OCPP Call to {station_id}: {err:?}");
+                    break;
+                }
+                continue;
+            },
+        };
         match msg {
             AxumWSMessage::Text(text) => {
-                let message = text.clone();
+                let message = match serde_json::from_str::<serde_json::Value>(&text) {
+                    Ok(message) => Masked(message).to_string(),
+                    Err(_) => text.clone(),
+                };
                 info!(
                     "\n\t{0}\n\t{1}\n\t\t{message}\n{2} {3}\n\n",
                     "INCOMING CALL".truecolor(255, 255, 255),
                     "FROM CHARGER".truecolor(180, 180, 180),
                     " ADDR ".on_truecolor(0, 115, 0),
-                    addr.truecolor(0, 215, 0)
+                    client_ip.truecolor(0, 215, 0)
                 );
-                handle_ocpp_messages(text, &mut socket).await;
+                if let Err(err) = version
+                    .handle_messages(text, &mut outbound, &station_id)
+                    .await
+                {
+                    error!("Failed to answer OCPP message of {station_id}: {err:?}");
+                    break;
+                }
             },
             AxumWSMessage::Binary(_) => warn!("Unexpected binary message"),
             AxumWSMessage::Close(_) => info!("WebSocket connection closed"),
             _ => (),
         }
     }
+    connection.close().await;
+}
+
+// The bookkeeping of a charger connected over WebSocket or raw TCP, from its connection to its
+// disconnection
+struct ChargerConnection {
+    station_id: StationId,
+    connection_id: Uuid,
+    session_id: Option<i64>,
 }
 
-// Handle the incoming WebSocket connections and their OCPP Messages
-async fn handle_ocpp_messages(message: String, socket: &mut axum::extract::ws::WebSocket) {
+impl ChargerConnection {
+    // Record the connection and register the charger, whose server-initiated Calls are received
+    // on the returned channel
+    async fn open(
+        transport: &str,
+        station_id: &StationId,
+        connection_id: Uuid,
+        client_ip: IpAddr,
+        version: OcppVersion,
+        protocol_version: Option<String>,
+    ) -> (Self, mpsc::UnboundedReceiver<String>) {
+        // Idle on the open connection, the charger does not reconnect while it waits
+        rate_limit::throttle_reconnect(station_id).await;
+        info!(
+            "{} {client_ip} ({station_id})",
+            format!("New {transport} connection:").green().bold()
+        );
+        let session_id =
+            open_charger_session(station_id, connection_id, client_ip, protocol_version).await;
+        let (outbound_sender, outbound_receiver) = mpsc::unbounded_channel();
+        commands::register_charger(station_id, connection_id, version, outbound_sender);
+        // The stops requested while the charger was offline
+        tokio::spawn(remote_stop::send_queued(station_id.clone()).instrument(Span::current()));
+        // Nor could the debug mode end while it was offline
+        tokio::spawn(debug_mode::restore_expired(station_id.clone()).instrument(Span::current()));
+        let connection = Self {
+            station_id: station_id.clone(),
+            connection_id,
+            session_id,
+        };
+        (connection, outbound_receiver)
+    }
+
+    // Unregister the charger and close its session once the connection is gone
+    async fn close(self) {
+        let Self {
+            station_id,
+            connection_id,
+            session_id,
+        } = self;
+        commands::unregister_charger(&station_id, connection_id);
+        alerts::fire(&station_id, AlertEvent::ChargerDisconnected);
+        if let Some(session_id) = session_id
+            && let Some(_permit) = db::ocpp_permit("closing the charger session").await
+            && let Err(err) = db::close_charger_session(session_id).await
+        {
+            error!("Failed to close session of {station_id}: {err:?}");
+        }
+    }
+}
+
+// Record the connection of the charger along with the negotiated OCPP protocol version
+async fn open_charger_session(
+    station_id: &StationId,
+    connection_id: Uuid,
+    client_ip: IpAddr,
+    protocol_version: Option<String>,
+) -> Option<i64> {
+    let _permit = db::ocpp_permit("opening the charger session").await?;
+    match db::last_protocol_version(station_id).await {
+        Ok(Some(last_protocol_version)) if last_protocol_version != protocol_version => warn!(
+            "Charger {station_id} connected with protocol {protocol_version:?} but used \
+             {last_protocol_version:?} in its last session"
+        ),
+        Ok(_) => (),
+        Err(err) => error!("Failed to get last protocol version of {station_id}: {err:?}"),
+    }
+    match db::open_charger_session(
+        station_id,
+        connection_id,
+        &client_ip.to_string(),
+        protocol_version.as_deref(),
+    )
+    .await
+    {
+        Ok(session_id) => Some(session_id),
+        Err(err) => {
+            error!("Failed to open session of {station_id}: {err:?}");
+            None
+        },
+    }
+}
+
+// Handle the OCPP Messages of the incoming WebSocket and raw TCP connections
+async fn handle_ocpp_messages<S>(
+    message: String,
+    socket: &mut S,
+    station_id: &StationId,
+) -> Result<(), S::Error>
+where
+    S: Sink<AxumWSMessage> + Unpin,
+    S::Error: std::fmt::Debug,
+{
     // Try to parse the JSON message
     match serde_json::from_str(&message) {
-        Ok(ocpp_message) => match ocpp_message {
-            OcppMessageType::Call(message_type_id, message_id, action, payload) => {
-                let action = match OcppActionEnum::from_str(&action) {
-                    Ok(action) => {
-                        debug!(
-                            "\n{0}\n {1}",
-                            " PARSED OCPP CALL "
-                                .on_truecolor(0, 0, 0)
-                                .bold(),
-                            format!(" {:?} ", action).on_truecolor(139, 0, 139)
-                        );
-                        action
-                    },
-                    Err(err) => {
-                        error!("Failed to parse OCPP Call Action: {err:?}");
-                        return;
-                    },
-                };
-                handle_ocpp_call(message_type_id, message_id, action, payload, socket).await;
-            },
-            OcppMessageType::CallResult(message_type_id, message_id, payload) => {
-                handle_ocpp_call_result(message_type_id, message_id, payload, socket).await;
-            },
-            OcppMessageType::CallError(
+        Ok(ocpp_message) => {
+            let (OcppMessageType::Call(_, message_id, ..)
+            | OcppMessageType::CallResult(_, message_id, _)
+            | OcppMessageType::CallError(_, message_id, ..)) = &ocpp_message;
+            if let Err(ocpp_call_error) = validate_message_id(message_id) {
+                warn!(
+                    "Dropped OCPP message of {station_id}: {}",
+                    ocpp_call_error.error_description
+                );
+                // A CallError only answers a Call, never a CallResult or another CallError
+                if matches!(ocpp_message, OcppMessageType::Call(..)) {
+                    send_call_error(socket, ocpp_call_error).await?;
+                }
+                return Ok(());
+            }
+            handle_ocpp_message(ocpp_message, socket, station_id).await?;
+        },
+        Err(err) => warn!("Failed to parse OCPP message: {err:?}"),
+    }
+    Ok(())
+}
+
+// Interval of the Heartbeats sent by the chargers, in seconds, given in the BootNotification
+// response
+const HEARTBEAT_INTERVAL_SECS: u32 = 300;
+
+// Largest gap between the time of two Heartbeats and the interval before warning about it
+const MAX_HEARTBEAT_DRIFT: TimeDelta = TimeDelta::seconds(5);
+
+// How often the chargers are checked for an overdue Heartbeat
+const LATE_HEARTBEAT_CHECK_INTERVAL: Duration = Duration::from_secs(10);
+
+// Longest message accepted from a charger, over WebSocket or raw TCP
+const MAX_MESSAGE_LENGTH: usize = 64 * 1024;
+
+// Longest MessageId allowed by OCPP-J 1.6
+const MAX_MESSAGE_ID_LENGTH: usize = 36;
+
+// The MessageIds of the Calls are kept until answered, so long ones would waste memory
+fn validate_message_id(message_id: &str) -> Result<(), OcppCallError> {
+    let length = message_id.chars().count();
+    if length <= MAX_MESSAGE_ID_LENGTH {
+        return Ok(());
+    }
+    Err(OcppCallError::formation_violation(
+        message_id.to_string(),
+        format!(
+            "MessageId is {length} characters long, at most {MAX_MESSAGE_ID_LENGTH} are allowed"
+        ),
+    ))
+}
+
+// Dispatch a parsed OCPP message to the handler of its type
+async fn handle_ocpp_message<S>(
+    ocpp_message: OcppMessageType,
+    socket: &mut S,
+    station_id: &StationId,
+) -> Result<(), S::Error>
+where
+    S: Sink<AxumWSMessage> + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    match ocpp_message {
+        OcppMessageType::Call(message_type_id, message_id, action, payload) => {
+            if !commands::record_call_message_id(station_id, &message_id) {
+                if action != "Heartbeat" {
+                    warn!(
+                        "Charger {station_id} sent the MessageId {message_id} twice in its session"
+                    );
+                    return send_call_error(
+                        socket,
+                        OcppCallError::generic_error(
+                            message_id,
+                            "Duplicate message ID in session".to_string(),
+                        ),
+                    )
+                    .await;
+                }
+                // Some firmware sends every Heartbeat with the same MessageId. Answering them
+                // keeps the charger online, and the violation is counted to find that firmware
+                warn!(
+                    "Charger {station_id} sent the MessageId {message_id} of a Heartbeat twice in \
+                     its session, which OCPP 1.6 forbids"
+                );
+                metrics::counter!(
+                    "ocpp_spec_violations_total",
+                    "station_id" => station_id.clone(),
+                    "violation_type" => "duplicate_heartbeat_message_id"
+                )
+                .increment(1);
+            }
+            let action = match OcppActionEnum::from_str(&action) {
+                Ok(action) => {
+                    debug!(
+                        "\n{0}\n {1}",
+                        " PARSED OCPP CALL "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        format!(" {:?} ", action).on_truecolor(139, 0, 139)
+                    );
+                    action
+                },
+                Err(err) => {
+                    error!("Failed to parse OCPP Call Action: {err:?}");
+                    return Ok(());
+                },
+            };
+            handle_ocpp_call(
+                message_type_id,
+                message_id,
+                action,
+                payload,
+                socket,
+                station_id,
+            )
+            .await?;
+        },
+        OcppMessageType::CallResult(message_type_id, message_id, payload) => {
+            handle_ocpp_call_result(message_type_id, message_id, payload, socket, station_id).await;
+        },
+        OcppMessageType::CallError(
+            message_type_id,
+            message_id,
+            error_code,
+            error_description,
+            error_details,
+        ) => {
+            handle_ocpp_call_error(
                 message_type_id,
                 message_id,
                 error_code,
                 error_description,
                 error_details,
-            ) => {
-                handle_ocpp_call_error(
-                    message_type_id,
-                    message_id,
-                    error_code,
-                    error_description,
-                    error_details,
-                    socket,
-                )
-                .await;
-            },
-        },
-        Err(err) => {
-            warn!("Failed to parse OCPP message: {err:?}");
-            return;
+                socket,
+                station_id,
+            )
+            .await;
         },
     }
+    Ok(())
 }
 
-// Handle the incoming OCPP Call messages
-async fn handle_ocpp_call(
+// Handle the incoming OCPP Call messages. The responses are sent to the charger WebSocket, or to
+// the channel of the SOAP endpoint. The payload stays out of the span, it can hold idTags
+#[tracing::instrument(skip_all, fields(%message_id, ?action))]
+async fn handle_ocpp_call<S>(
     _: OcppMessageTypeId,
     message_id: OcppMessageId,
     action: OcppActionEnum,
-    payload: serde_json::Value,
-    socket: &mut axum::extract::ws::WebSocket,
-) {
-    let payload = match serde_json::from_value::<OcppPayload>(payload) {
+    mut payload: serde_json::Value,
+    socket: &mut S,
+    station_id: &StationId,
+) -> Result<(), S::Error>
+where
+    S: Sink<AxumWSMessage> + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    if !call_direction::validate_call_direction(&action, MessageSender::Charger) {
+        warn!("Rejected {action} Call from {station_id}, only a server sends it");
+        return send_call_error(
+            socket,
+            OcppCallError::not_supported(
+                message_id,
+                format!("{action} is sent by the server, not by a charger"),
+            ),
+        )
+        .await;
+    }
+    if let Some(profile) = feature_profiles::disabled_profile(&action) {
+        warn!("Rejected {action} Call from {station_id}, the {profile:?} profile is disabled");
+        return send_call_error(
+            socket,
+            OcppCallError::not_supported(
+                message_id,
+                format!("{action} is in the disabled {profile:?} feature profile"),
+            ),
+        )
+        .await;
+    }
+    // Unknown measurands or units would make the whole MeterValues payload fail to parse
+    if action == OcppActionEnum::MeterValues {
+        reject_unknown_sampled_values(&mut payload, station_id).await;
+    }
+    let payload = match OcppPayload::deserialize_request(&action, payload) {
         Ok(ocpp_payload) => ocpp_payload,
         Err(err) => {
-            error!("Failed to parse OCPP Payload: {err:?}");
-            return;
+            warn!("Invalid OCPP {action} Call from {station_id}: {err}");
+            return send_call_error(
+                socket,
+                OcppCallError::formation_violation(message_id, err.to_string()),
+            )
+            .await;
         },
     };
+    // The match below leaves the Call unanswered when the payload is a request of another action
+    if !action_matches_payload(&action, &payload) {
+        warn!("OCPP {action} Call from {station_id} has a payload of another action: {payload}");
+        return send_call_error(
+            socket,
+            OcppCallError::formation_violation(
+                message_id,
+                format!("Payload is not a {action} request"),
+            ),
+        )
+        .await;
+    }
+    // Connector IDs above the connectors of the charger would be stored along with its real ones
+    if let Some(connector_id) = payload.connector_id()
+        && let Err(detail) = capabilities::validate_connector_id(station_id, connector_id)
+    {
+        warn!("Rejected {action} Call from {station_id}: {detail}");
+        return send_call_error(
+            socket,
+            OcppCallError::property_constraint_violation(message_id, detail),
+        )
+        .await;
+    }
     // Handle the OCPP Call Action
     use OcppActionEnum::*;
     match action {
@@ -428,21 +1207,31 @@ async fn handle_ocpp_call(
             match payload {
                 OcppPayload::Authorize(AuthorizeKind::Request(authorize)) => {
                     info!(
-                        "\n{0}\n {1}\n{authorize:?}",
+                        "\n{0}\n {1}\n{2}",
                         " CALL ".on_truecolor(0, 0, 0).bold(),
-                        " REQUEST ".on_truecolor(0, 99, 255)
+                        " REQUEST ".on_truecolor(0, 99, 255),
+                        Masked(&authorize)
                     );
+                    let mut id_tag_info = if rate_limit::allow_authorize(station_id) {
+                        auth::authorize(&authorize.id_tag).await
+                    } else {
+                        // Even for a valid idTag, so that guessing one tells nothing
+                        rust_ocpp::v1_6::types::IdTagInfo {
+                            status: rust_ocpp::v1_6::types::AuthorizationStatus::Invalid,
+                            expiry_date: None,
+                            parent_id_tag: None,
+                        }
+                    };
+                    alerts::record_authorization(station_id, &id_tag_info.status);
+                    if maintenance::is_in_maintenance(station_id) {
+                        info!("Blocked Authorize on {station_id}, in maintenance");
+                        id_tag_info.status = rust_ocpp::v1_6::types::AuthorizationStatus::Blocked;
+                    }
                     let response = OcppCallResult {
-                        message_type_id: 3,
+                        message_type_id: MessageTypeId::CALL_RESULT,
                         message_id,
                         payload: OcppPayload::Authorize(AuthorizeKind::Response(
-                            AuthorizeResponse {
-                                id_tag_info: rust_ocpp::v1_6::types::IdTagInfo {
-                                    status: rust_ocpp::v1_6::types::AuthorizationStatus::Accepted,
-                                    expiry_date: None,
-                                    parent_id_tag: None,
-                                },
-                            },
+                            AuthorizeResponse { id_tag_info },
                         )),
                     };
                     let response_json = serde_json::to_string(&response).unwrap();
@@ -455,15 +1244,15 @@ async fn handle_ocpp_call(
                     );
                     socket
                         .send(axum::extract::ws::Message::Text(response_json))
-                        .await
-                        .unwrap();
+                        .await?;
                 },
-                _ => (),
+                _ => error!("Invalid OCPP Authorize payload"),
             }
         },
         BootNotification => {
             match payload {
                 OcppPayload::BootNotification(BootNotificationKind::Request(boot_notification)) => {
+                    record_initial_latency(station_id, Utc::now()).await;
                     if boot_notification.charge_point_serial_number
                         == Some("NKYK430037668".to_string())
                     {
@@ -472,18 +1261,32 @@ async fn handle_ocpp_call(
                             " CALL ".on_truecolor(0, 0, 0).bold(),
                             " REQUEST ".on_truecolor(0, 99, 255)
                         );
+                        record_boot_notification(station_id, &boot_notification).await;
+                        commands::set_capabilities(
+                            station_id,
+                            capabilities::capabilities_from_boot(&boot_notification),
+                        );
+                        let timezone = charger_timezone(station_id).await;
+                        let current_time = Utc::now();
                         let response = OcppCallResult {
-                            message_type_id: 3,
+                            message_type_id: MessageTypeId::CALL_RESULT,
                             message_id,
                             payload: OcppPayload::BootNotification(BootNotificationKind::Response(
                                 BootNotificationResponse {
                                     status: rust_ocpp::v1_6::types::RegistrationStatus::Accepted,
-                                    current_time: Utc::now(),
-                                    interval: 300,
+                                    current_time,
+                                    interval: HEARTBEAT_INTERVAL_SECS,
                                 },
                             )),
                         };
-                        let response_json = serde_json::to_string(&response).unwrap();
+                        // Some firmware sets its clock from it, in local time
+                        let response_json = match timezone {
+                            Some(timezone) => with_ocpp_current_time(
+                                &response,
+                                current_time.with_timezone(&timezone),
+                            ),
+                            None => with_ocpp_current_time(&response, current_time),
+                        };
                         info!(
                             "\n{0}\n {1}\n{response_json:?}",
                             " CALL RESULT "
@@ -493,8 +1296,18 @@ async fn handle_ocpp_call(
                         );
                         socket
                             .send(axum::extract::ws::Message::Text(response_json))
-                            .await
-                            .unwrap();
+                            .await?;
+                        // The charger forgot the availabilities set before it rebooted
+                        tokio::spawn(
+                            availability_overrides::reapply(station_id.clone())
+                                .instrument(Span::current()),
+                        );
+                        // Nor would it have the configuration of its groups if it was offline when
+                        // it changed
+                        tokio::spawn(
+                            charger_groups::reapply_configuration(station_id.clone())
+                                .instrument(Span::current()),
+                        );
                     } else {
                         error!(
                             "Invalid Charger Serial Number. BootNotification: \
@@ -520,7 +1333,7 @@ async fn handle_ocpp_call(
                         " REQUEST ".on_truecolor(0, 99, 255)
                     );
                     let response = OcppCallResult {
-                        message_type_id: 3,
+                        message_type_id: MessageTypeId::CALL_RESULT,
                         message_id,
                         payload: OcppPayload::DataTransfer(DataTransferKind::Response(
                             DataTransferResponse {
@@ -539,13 +1352,40 @@ async fn handle_ocpp_call(
                     );
                     socket
                         .send(axum::extract::ws::Message::Text(response_json))
-                        .await
-                        .unwrap();
+                        .await?;
                 },
-                _ => (),
+                _ => error!("Invalid OCPP DataTransfer payload"),
             }
         },
         GetConfiguration => {
+            match payload {
+                OcppPayload::GetConfiguration(GetConfigurationKind::Request(get_configuration)) => {
+                    info!(
+                        "\n{0}\n {1}\n{get_configuration:?}",
+                        " CALL ".on_truecolor(0, 0, 0).bold(),
+                        " REQUEST ".on_truecolor(0, 99, 255)
+                    );
+                    let response = OcppCallResult {
+                        message_type_id: MessageTypeId::CALL_RESULT,
+                        message_id,
+                        payload: OcppPayload::GetConfiguration(GetConfigurationKind::Response(
+                            server_configuration::get_configuration(station_id, &get_configuration),
+                        )),
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    info!(
+                        "\n{0}\n {1}\n{response_json:?}",
+                        " CALL RESULT "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        " RESPONSE ".on_truecolor(0, 125, 0)
+                    );
+                    socket
+                        .send(axum::extract::ws::Message::Text(response_json))
+                        .await?;
+                },
+                _ => error!("Invalid OCPP GetConfiguration payload"),
+            }
         },
         Heartbeat => {
             match payload {
@@ -555,14 +1395,16 @@ async fn handle_ocpp_call(
                         " CALL ".on_truecolor(0, 0, 0).bold(),
                         " REQUEST ".on_truecolor(0, 99, 255)
                     );
+                    let current_time = Utc::now();
+                    check_heartbeat_drift(station_id, current_time);
                     let response = OcppCallResult {
-                        message_type_id: 3,
+                        message_type_id: MessageTypeId::CALL_RESULT,
                         message_id,
                         payload: OcppPayload::Heartbeat(HeartbeatKind::Response(
-                            HeartbeatResponse { current_time: Utc::now() },
+                            HeartbeatResponse { current_time },
                         )),
                     };
-                    let response_json = serde_json::to_string(&response).unwrap();
+                    let response_json = with_ocpp_current_time(&response, current_time);
                     info!(
                         "\n{0}\n {1}\n{response_json:?}",
                         " CALL RESULT "
@@ -572,13 +1414,51 @@ async fn handle_ocpp_call(
                     );
                     socket
                         .send(axum::extract::ws::Message::Text(response_json))
-                        .await
-                        .unwrap();
+                        .await?;
                 },
-                _ => (),
+                _ => error!("Invalid OCPP Heartbeat payload"),
             }
         },
         MeterValues => {
+            match payload {
+                OcppPayload::MeterValues(MeterValuesKind::Request(meter_values)) => {
+                    info!(
+                        "\n{0}\n {1}\n{meter_values:?}",
+                        " CALL ".on_truecolor(0, 0, 0).bold(),
+                        " REQUEST ".on_truecolor(0, 99, 255)
+                    );
+                    if let Some(timestamp) = meter_values
+                        .meter_value
+                        .iter()
+                        .map(|meter_value| meter_value.timestamp)
+                        .max()
+                    {
+                        check_clock_skew(station_id, timestamp).await;
+                    }
+                    transactions::update_meter(station_id, &meter_values);
+                    live_power::update(station_id, &meter_values);
+                    record_meter_values(station_id, &meter_values).await;
+                    let response = OcppCallResult {
+                        message_type_id: MessageTypeId::CALL_RESULT,
+                        message_id,
+                        payload: OcppPayload::MeterValues(MeterValuesKind::Response(
+                            MeterValuesResponse {},
+                        )),
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    info!(
+                        "\n{0}\n {1}\n{response_json:?}",
+                        " CALL RESULT "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        " RESPONSE ".on_truecolor(0, 125, 0)
+                    );
+                    socket
+                        .send(axum::extract::ws::Message::Text(response_json))
+                        .await?;
+                },
+                _ => error!("Invalid OCPP MeterValues payload"),
+            }
         },
         RemoteStartTransaction => {
         },
@@ -596,31 +1476,187 @@ async fn handle_ocpp_call(
                         " CALL ".on_truecolor(0, 0, 0).bold(),
                         " REQUEST ".on_truecolor(0, 99, 255)
                     );
+                    connectors::update_status(
+                        station_id,
+                        status_notification.connector_id,
+                        status_notification.status.clone(),
+                    );
+                    let response = OcppCallResult {
+                        message_type_id: MessageTypeId::CALL_RESULT,
+                        message_id,
+                        payload: OcppPayload::StatusNotification(
+                            StatusNotificationKind::Response(StatusNotificationResponse {}),
+                        ),
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    info!(
+                        "\n{0}\n {1}\n{response_json:?}",
+                        " CALL RESULT "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        " RESPONSE ".on_truecolor(0, 125, 0)
+                    );
+                    socket
+                        .send(axum::extract::ws::Message::Text(response_json))
+                        .await?;
+                    status_notifications::debounce(station_id, status_notification);
                 },
-                _ => (),
+                _ => error!("Invalid OCPP StatusNotification payload"),
             }
         },
         StartTransaction => {
+            match payload {
+                OcppPayload::StartTransaction(StartTransactionKind::Request(start_transaction)) => {
+                    info!(
+                        "\n{0}\n {1}\n{2}",
+                        " CALL ".on_truecolor(0, 0, 0).bold(),
+                        " REQUEST ".on_truecolor(0, 99, 255),
+                        Masked(&start_transaction)
+                    );
+                    // A second transaction on a charging connector is a firmware bug, answered
+                    // with the running transaction instead of a new one. The response must carry a
+                    // transaction ID, a new one would have no stored transaction for the
+                    // MeterValues and StopTransaction the charger sends with it. Without a running
+                    // transaction, the Charging status is stale and the transaction is started
+                    if connectors::status(station_id, start_transaction.connector_id)
+                        == Some(ChargePointStatus::Charging)
+                        && let Some(transaction) = transactions::active_transaction(
+                            station_id,
+                            start_transaction.connector_id,
+                        )
+                    {
+                        let transaction_id = transaction.transaction_id;
+                        warn!(
+                            "Rejected StartTransaction on charging connector {} of {station_id}, \
+                             running transaction {transaction_id}: {}",
+                            start_transaction.connector_id,
+                            Masked(&start_transaction)
+                        );
+                        let response = OcppCallResult {
+                            message_type_id: MessageTypeId::CALL_RESULT,
+                            message_id,
+                            payload: OcppPayload::StartTransaction(StartTransactionKind::Response(
+                                StartTransactionResponse {
+                                    id_tag_info: rust_ocpp::v1_6::types::IdTagInfo {
+                                        status:
+                                            rust_ocpp::v1_6::types::AuthorizationStatus::ConcurrentTx,
+                                        expiry_date: None,
+                                        parent_id_tag: None,
+                                    },
+                                    transaction_id,
+                                },
+                            )),
+                        };
+                        let response_json = serde_json::to_string(&response).unwrap();
+                        info!(
+                            "\n{0}\n {1}\n{response_json:?}",
+                            " CALL RESULT "
+                                .on_truecolor(0, 0, 0)
+                                .bold(),
+                            " RESPONSE ".on_truecolor(0, 125, 0)
+                        );
+                        socket
+                            .send(axum::extract::ws::Message::Text(response_json))
+                            .await?;
+                        return Ok(());
+                    }
+                    let started_at = Instant::now();
+                    check_clock_skew(station_id, start_transaction.timestamp).await;
+                    let mut id_tag_info = auth::authorize(&start_transaction.id_tag).await;
+                    if maintenance::is_in_maintenance(station_id) {
+                        info!("Blocked StartTransaction on {station_id}, in maintenance");
+                        id_tag_info.status = rust_ocpp::v1_6::types::AuthorizationStatus::Blocked;
+                    }
+                    // Transactions of tags that are not accepted are still recorded for audit
+                    let status = match id_tag_info.status {
+                        rust_ocpp::v1_6::types::AuthorizationStatus::Accepted => "active",
+                        _ => "blocked",
+                    };
+                    // Without a response the charger retries the StartTransaction later
+                    let Some(_permit) = db::ocpp_permit("storing the transaction").await else {
+                        return Ok(());
+                    };
+                    let new_transaction = db::NewTransaction {
+                        station_id,
+                        connector_id: start_transaction.connector_id as i32,
+                        id_tag: &start_transaction.id_tag,
+                        status,
+                        meter_start: start_transaction.meter_start,
+                        start_time: start_transaction.timestamp,
+                    };
+                    let transaction_id = match db::insert_transaction(&new_transaction).await {
+                        Ok(transaction_id) => transaction_id,
+                        Err(err) => {
+                            error!("Failed to store transaction of {station_id}: {err:?}");
+                            return Ok(());
+                        },
+                    };
+                    #[cfg(feature = "kafka")]
+                    kafka::publish_session_started(transaction_id, &new_transaction);
+                    if status == "active" {
+                        transactions::start(
+                            station_id,
+                            start_transaction.connector_id,
+                            transaction_id,
+                            &start_transaction.id_tag,
+                            start_transaction.timestamp,
+                            start_transaction.meter_start,
+                        );
+                    }
+                    // From the request to the transaction stored, the authorization included
+                    metrics::histogram!("ocpp_start_transaction_duration_seconds")
+                        .record(started_at.elapsed().as_secs_f64());
+                    let response = OcppCallResult {
+                        message_type_id: MessageTypeId::CALL_RESULT,
+                        message_id,
+                        payload: OcppPayload::StartTransaction(StartTransactionKind::Response(
+                            StartTransactionResponse { id_tag_info, transaction_id },
+                        )),
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    info!(
+                        "\n{0}\n {1}\n{response_json:?}",
+                        " CALL RESULT "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        " RESPONSE ".on_truecolor(0, 125, 0)
+                    );
+                    socket
+                        .send(axum::extract::ws::Message::Text(response_json))
+                        .await?;
+                },
+                _ => error!("Invalid OCPP StartTransaction payload"),
+            }
         },
         StopTransaction => {
             match payload {
                 OcppPayload::StopTransaction(StopTransactionKind::Request(stop_transaction)) => {
                     info!(
-                        "\n{0}\n {1}\n{stop_transaction:?}",
+                        "\n{0}\n {1}\n{2}",
                         " CALL ".on_truecolor(0, 0, 0).bold(),
-                        " REQUEST ".on_truecolor(0, 99, 255)
+                        " REQUEST ".on_truecolor(0, 99, 255),
+                        Masked(&stop_transaction)
                     );
+                    let source = transactions::stop(stop_transaction.transaction_id);
+                    remote_stop::cancel(stop_transaction.transaction_id);
+                    let start_id_tag = complete_transaction(
+                        &stop_transaction,
+                        source == Some(transactions::TransactionSource::Reconciled),
+                    )
+                    .await;
+                    // Only sent back when the transaction was stopped with an idTag
+                    let id_tag_info = match (&stop_transaction.id_tag, start_id_tag) {
+                        (Some(id_tag), Some(start_id_tag)) => {
+                            Some(auth::authorize_stop(id_tag, &start_id_tag).await)
+                        },
+                        (Some(id_tag), None) => Some(auth::authorize(id_tag).await),
+                        (None, _) => None,
+                    };
                     let response = OcppCallResult {
-                        message_type_id: 3,
+                        message_type_id: MessageTypeId::CALL_RESULT,
                         message_id,
                         payload: OcppPayload::StopTransaction(StopTransactionKind::Response(
-                            StopTransactionResponse {
-                                id_tag_info: Some(rust_ocpp::v1_6::types::IdTagInfo {
-                                    status: rust_ocpp::v1_6::types::AuthorizationStatus::Accepted,
-                                    expiry_date: None,
-                                    parent_id_tag: None,
-                                }),
-                            },
+                            StopTransactionResponse { id_tag_info },
                         )),
                     };
                     let response_json = serde_json::to_string(&response).unwrap();
@@ -633,62 +1669,838 @@ async fn handle_ocpp_call(
                     );
                     socket
                         .send(axum::extract::ws::Message::Text(response_json))
-                        .await
-                        .unwrap();
+                        .await?;
                 },
-                _ => (),
+                _ => error!("Invalid OCPP StopTransaction payload"),
             }
         },
         UnlockConnector => {
         },
+        ClearChargingProfile => {
+        },
+        GetLocalListVersion => {
+            match payload {
+                OcppPayload::GetLocalListVersion(GetLocalListVersionKind::Request(
+                    get_local_list_version,
+                )) => {
+                    info!(
+                        "\n{0}\n {1}\n{get_local_list_version:?}",
+                        " CALL ".on_truecolor(0, 0, 0).bold(),
+                        " REQUEST ".on_truecolor(0, 99, 255)
+                    );
+                    let Some(list_version) = local_auth_list::version(station_id).await else {
+                        return send_call_error(
+                            socket,
+                            OcppCallError::internal_error(
+                                message_id,
+                                "Local list version is not available".to_string(),
+                            ),
+                        )
+                        .await;
+                    };
+                    let response = OcppCallResult {
+                        message_type_id: MessageTypeId::CALL_RESULT,
+                        message_id,
+                        payload: OcppPayload::GetLocalListVersion(
+                            GetLocalListVersionKind::Response(GetLocalListVersionResponse {
+                                list_version,
+                            }),
+                        ),
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    info!(
+                        "\n{0}\n {1}\n{response_json:?}",
+                        " CALL RESULT "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        " RESPONSE ".on_truecolor(0, 125, 0)
+                    );
+                    socket
+                        .send(axum::extract::ws::Message::Text(response_json))
+                        .await?;
+                },
+                _ => error!("Invalid OCPP GetLocalListVersion payload"),
+            }
+        },
+        SendLocalList => {
+            match payload {
+                OcppPayload::SendLocalList(SendLocalListKind::Request(send_local_list)) => {
+                    info!(
+                        "\n{0}\n {1}\n{2}",
+                        " CALL ".on_truecolor(0, 0, 0).bold(),
+                        " REQUEST ".on_truecolor(0, 99, 255),
+                        Masked(&send_local_list)
+                    );
+                    let status = local_auth_list::update(station_id, send_local_list).await;
+                    let response = OcppCallResult {
+                        message_type_id: MessageTypeId::CALL_RESULT,
+                        message_id,
+                        payload: OcppPayload::SendLocalList(SendLocalListKind::Response(
+                            SendLocalListResponse { status },
+                        )),
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    info!(
+                        "\n{0}\n {1}\n{response_json:?}",
+                        " CALL RESULT "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        " RESPONSE ".on_truecolor(0, 125, 0)
+                    );
+                    socket
+                        .send(axum::extract::ws::Message::Text(response_json))
+                        .await?;
+                },
+                _ => error!("Invalid OCPP SendLocalList payload"),
+            }
+        },
+        GetDiagnostics => {
+        },
+        UpdateFirmware => {
+        },
+        SignCertificate => {
+            match payload {
+                OcppPayload::SignCertificate(SignCertificateKind::Request(sign_certificate)) => {
+                    info!(
+                        "\n{0}\n {1}\n{sign_certificate:?}",
+                        " CALL ".on_truecolor(0, 0, 0).bold(),
+                        " REQUEST ".on_truecolor(0, 99, 255)
+                    );
+                    let issued_certificate = certificates::sign(station_id, &sign_certificate)
+                        .inspect_err(|err| warn!("Rejected CSR of {station_id}: {err}"))
+                        .ok();
+                    let status = match issued_certificate {
+                        Some(_) => GenericStatus::Accepted,
+                        None => GenericStatus::Rejected,
+                    };
+                    let response = OcppCallResult {
+                        message_type_id: MessageTypeId::CALL_RESULT,
+                        message_id,
+                        payload: OcppPayload::SignCertificate(SignCertificateKind::Response(
+                            SignCertificateResponse { status },
+                        )),
+                    };
+                    let response_json = serde_json::to_string(&response).unwrap();
+                    info!(
+                        "\n{0}\n {1}\n{response_json:?}",
+                        " CALL RESULT "
+                            .on_truecolor(0, 0, 0)
+                            .bold(),
+                        " RESPONSE ".on_truecolor(0, 125, 0)
+                    );
+                    socket
+                        .send(axum::extract::ws::Message::Text(response_json))
+                        .await?;
+                    // Sent once the charger has the response, as it waits for it
+                    if let Some(issued_certificate) = issued_certificate {
+                        tokio::spawn(
+                            certificates::install(issued_certificate).instrument(Span::current()),
+                        );
+                    }
+                },
+                _ => error!("Invalid OCPP SignCertificate payload"),
+            }
+        },
+        CertificateSigned => {
+        },
     }
+    Ok(())
 }
 
-// Handle the incoming OCPP CallResult messages
-async fn handle_ocpp_call_result(
-    _: OcppMessageTypeId,
-    _: OcppMessageId,
-    payload: serde_json::Value,
-    _: &mut axum::extract::ws::WebSocket,
+// Whether the payload is a request of the action of the Call
+fn action_matches_payload(action: &OcppActionEnum, payload: &OcppPayload) -> bool {
+    use OcppActionEnum::*;
+    matches!(
+        (action, payload),
+        (Authorize, OcppPayload::Authorize(AuthorizeKind::Request(_)))
+            | (
+                BootNotification,
+                OcppPayload::BootNotification(BootNotificationKind::Request(_))
+            )
+            | (
+                ChangeAvailability,
+                OcppPayload::ChangeAvailability(ChangeAvailabilityKind::Request(_))
+            )
+            | (
+                ChangeConfiguration,
+                OcppPayload::ChangeConfiguration(ChangeConfigurationKind::Request(_))
+            )
+            | (
+                ClearCache,
+                OcppPayload::ClearCache(ClearCacheKind::Request(_))
+            )
+            | (
+                DataTransfer,
+                OcppPayload::DataTransfer(DataTransferKind::Request(_))
+            )
+            | (
+                GetConfiguration,
+                OcppPayload::GetConfiguration(GetConfigurationKind::Request(_))
+            )
+            | (Heartbeat, OcppPayload::Heartbeat(HeartbeatKind::Request(_)))
+            | (
+                MeterValues,
+                OcppPayload::MeterValues(MeterValuesKind::Request(_))
+            )
+            | (
+                RemoteStartTransaction,
+                OcppPayload::RemoteStartTransaction(RemoteStartTransactionKind::Request(_))
+            )
+            | (
+                RemoteStopTransaction,
+                OcppPayload::RemoteStopTransaction(RemoteStopTransactionKind::Request(_))
+            )
+            | (Reset, OcppPayload::Reset(ResetKind::Request(_)))
+            | (
+                StartTransaction,
+                OcppPayload::StartTransaction(StartTransactionKind::Request(_))
+            )
+            | (
+                StatusNotification,
+                OcppPayload::StatusNotification(StatusNotificationKind::Request(_))
+            )
+            | (
+                StopTransaction,
+                OcppPayload::StopTransaction(StopTransactionKind::Request(_))
+            )
+            | (
+                UnlockConnector,
+                OcppPayload::UnlockConnector(UnlockConnectorKind::Request(_))
+            )
+            | (
+                ClearChargingProfile,
+                OcppPayload::ClearChargingProfile(ClearChargingProfileKind::Request(_))
+            )
+            | (
+                GetLocalListVersion,
+                OcppPayload::GetLocalListVersion(GetLocalListVersionKind::Request(_))
+            )
+            | (
+                SendLocalList,
+                OcppPayload::SendLocalList(SendLocalListKind::Request(_))
+            )
+            | (
+                GetDiagnostics,
+                OcppPayload::GetDiagnostics(GetDiagnosticsKind::Request(_))
+            )
+            | (
+                UpdateFirmware,
+                OcppPayload::UpdateFirmware(UpdateFirmwareKind::Request(_))
+            )
+            | (
+                SignCertificate,
+                OcppPayload::SignCertificate(SignCertificateKind::Request(_))
+            )
+            | (
+                CertificateSigned,
+                OcppPayload::CertificateSigned(CertificateSignedKind::Request(_))
+            )
+    )
+}
+
+// Reply to a Call with a CallError
+async fn send_call_error<S>(
+    socket: &mut S,
+    ocpp_call_error: OcppCallError,
+) -> Result<(), S::Error>
+where
+    S: Sink<AxumWSMessage> + Unpin,
+    S::Error: std::fmt::Debug,
+{
+    let ocpp_call_error_json = serde_json::to_string(&ocpp_call_error).unwrap();
+    info!("Sending OCPP CallError: {ocpp_call_error_json}");
+    socket
+        .send(AxumWSMessage::Text(ocpp_call_error_json))
+        .await
+}
+
+// Timezone configured for the charger, `None` when there is none or it is not a known IANA timezone
+async fn charger_timezone(station_id: &StationId) -> Option<Tz> {
+    let _permit = db::ocpp_permit("loading the charger timezone").await?;
+    let timezone = match db::charger_timezone(station_id).await {
+        Ok(timezone) => timezone?,
+        Err(err) => {
+            error!("Failed to load timezone of {station_id}: {err:?}");
+            return None;
+        },
+    };
+    timezone
+        .parse()
+        .inspect_err(|_| warn!("Unknown timezone {timezone:?} of {station_id}"))
+        .ok()
+}
+
+// Store the charger and detect firmware changes since its previous boot, which may be an OTA
+// update completion or an unauthorized firmware change
+async fn record_boot_notification(
+    station_id: &StationId,
+    boot_notification: &BootNotificationRequest,
 ) {
-    match serde_json::from_value::<OcppPayload>(payload) {
-        Ok(ocpp_payload) => {
-            info!("Parsed OCPP Payload: {ocpp_payload:?}");
+    let Some(_permit) = db::ocpp_permit("storing the charger").await else {
+        return;
+    };
+    let firmware_version_semver = boot_notification
+        .firmware_version
+        .as_deref()
+        .and_then(|firmware_version| {
+            let normalized = firmware::normalize_firmware_version(firmware_version);
+            // Logged so that the normalization can learn the formats it misses
+            if normalized.is_none() {
+                warn!("Failed to normalize firmware version {firmware_version:?} of {station_id}");
+            }
+            normalized
+        })
+        .map(|version| version.to_string());
+    let previous_firmware = match db::upsert_charger(
+        station_id,
+        boot_notification,
+        firmware_version_semver.as_deref(),
+    )
+    .await
+    {
+        Ok(previous_firmware) => previous_firmware,
+        Err(err) => {
+            error!("Failed to store charger {station_id}: {err:?}");
+            return;
         },
+    };
+    let new_firmware = boot_notification
+        .firmware_version
+        .as_deref();
+    // First boot of the charger, there is nothing to compare against
+    let Some(old_firmware) = previous_firmware else {
+        return;
+    };
+    if old_firmware.as_deref() != new_firmware {
+        warn!(
+            "Firmware of {station_id} changed from {old_firmware:?} to {new_firmware:?} since its \
+             last boot"
+        );
+        if let Err(err) =
+            db::insert_firmware_change_event(station_id, old_firmware.as_deref(), new_firmware)
+                .await
+        {
+            error!("Failed to store firmware change of {station_id}: {err:?}");
+        }
+    }
+}
+
+// Measure how long the charger took to send its first BootNotification after connecting. Over 5 s
+// usually means a poor mobile connection, which may need a longer heartbeat interval. Suggested
+// alert rule: ocpp_initial_latency_milliseconds{quantile="0.95"} > 5000
+async fn record_initial_latency(station_id: &StationId, received_at: DateTime<Utc>) {
+    let Some(_permit) = db::ocpp_permit("recording the initial latency").await else {
+        return;
+    };
+    let initial_latency_ms = match db::record_initial_latency(station_id, received_at).await {
+        Ok(Some(initial_latency_ms)) => initial_latency_ms,
+        // Not the first BootNotification of the connection
+        Ok(None) => return,
         Err(err) => {
-            warn!("Failed to parse OCPP Payload: {err:?}");
+            error!("Failed to store initial latency of {station_id}: {err:?}");
+            return;
         },
+    };
+    metrics::histogram!("ocpp_initial_latency_milliseconds", "station_id" => station_id.clone())
+        .record(initial_latency_ms as f64);
+    if initial_latency_ms > 5000 {
+        warn!(
+            station_id,
+            initial_latency_ms, "Charger took long to send its first BootNotification"
+        );
+    }
+}
+
+// Compare the time between two Heartbeats of the connection with the interval they are sent at. A
+// steady drift means the clock of the server or of the charger drifts, or that the charger does not
+// use the interval given in the BootNotification response
+fn check_heartbeat_drift(station_id: &StationId, server_time: DateTime<Utc>) {
+    let Some((heartbeat_count, elapsed, heartbeat_interval)) =
+        commands::record_heartbeat(station_id, server_time)
+    else {
+        return;
+    };
+    let drift = elapsed - heartbeat_interval;
+    metrics::histogram!("ocpp_heartbeat_drift_milliseconds", "station_id" => station_id.clone())
+        .record(drift.num_milliseconds() as f64);
+    if drift.abs() > MAX_HEARTBEAT_DRIFT {
+        warn!(
+            station_id,
+            heartbeat_count,
+            drift_ms = drift.num_milliseconds(),
+            "Heartbeat came {} s after the previous one instead of {} s",
+            elapsed.num_seconds(),
+            heartbeat_interval.num_seconds()
+        );
+    }
+}
+
+// Time a Heartbeat may be overdue before warning that the charger is late, 80% of the Heartbeat
+// interval of the charger when `OCPP_HEARTBEAT_EARLY_WARNING_SECS` is empty
+fn heartbeat_early_warning(heartbeat_interval: TimeDelta) -> TimeDelta {
+    const OCPP_HEARTBEAT_EARLY_WARNING_SECS: &str = dotenv!("OCPP_HEARTBEAT_EARLY_WARNING_SECS");
+    if OCPP_HEARTBEAT_EARLY_WARNING_SECS.is_empty() {
+        return heartbeat_interval * 4 / 5;
+    }
+    TimeDelta::seconds(
+        OCPP_HEARTBEAT_EARLY_WARNING_SECS
+            .parse()
+            .expect("OCPP_HEARTBEAT_EARLY_WARNING_SECS must be a number of seconds"),
+    )
+}
+
+// Warn about the chargers whose Heartbeat is overdue, once per Heartbeat. A charger that misses its
+// Heartbeats often drops its connection next, so it can be investigated or restarted before
+async fn watch_late_heartbeats() {
+    let mut interval = tokio::time::interval(LATE_HEARTBEAT_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        let now = Utc::now();
+        for (station_id, last_heartbeat, heartbeat_interval) in
+            commands::late_heartbeats(now, heartbeat_early_warning)
+        {
+            warn!(
+                %station_id,
+                "No Heartbeat from the charger for {} s, it sends one every {} s",
+                (now - last_heartbeat).num_seconds(),
+                heartbeat_interval.num_seconds()
+            );
+            metrics::counter!("ocpp_heartbeat_late_total", "station_id" => station_id).increment(1);
+        }
+    }
+}
+
+// Store a StatusNotification that was not debounced, and open a fault and alert when the connector
+// is faulted
+async fn process_status_notification(
+    station_id: &StationId,
+    status_notification: &StatusNotificationRequest,
+) {
+    let connector_id = status_notification.connector_id;
+    let faulted = status_notification.status == ChargePointStatus::Faulted;
+    let status_notification = db::StatusNotification {
+        station_id: station_id.clone(),
+        connector_id: status_notification.connector_id as i32,
+        status: ConnectorStatus(status_notification.status.clone()).to_string(),
+        error_code: format!("{:?}", status_notification.error_code),
+        vendor_error_code: status_notification
+            .vendor_error_code
+            .clone(),
+        info: status_notification.info.clone(),
+        // The timestamp is optional, fall back to the time it was received at
+        timestamp: status_notification
+            .timestamp
+            .unwrap_or_else(Utc::now),
+    };
+    record_status_notification(&status_notification).await;
+    if faulted {
+        let fault_id = record_connector_fault(&status_notification).await;
+        alerts::fire(
+            station_id,
+            AlertEvent::ConnectorFaulted {
+                connector_id,
+                error_code: status_notification.error_code,
+                vendor_error_code: status_notification.vendor_error_code,
+                info: status_notification.info,
+                faulted_at: status_notification.timestamp,
+                fault_id,
+            },
+        );
+    }
+}
+
+// Keep the history of the connector states, for fault analysis
+async fn record_status_notification(status_notification: &db::StatusNotification) {
+    let Some(_permit) = db::ocpp_permit("storing the status notification").await else {
+        return;
+    };
+    if let Err(err) = db::insert_status_notification(status_notification).await {
+        error!(
+            "Failed to store status notification of {}: {err:?}",
+            status_notification.station_id
+        );
+    }
+}
+
+// Open a fault of the connector until it is resolved after maintenance. Returns its ID, `None` when
+// it could not be stored
+async fn record_connector_fault(status_notification: &db::StatusNotification) -> Option<i64> {
+    let _permit = db::ocpp_permit("storing the connector fault").await?;
+    match db::record_connector_fault(status_notification).await {
+        Ok(fault) => Some(fault.id),
+        Err(err) => {
+            error!(
+                "Failed to store fault of {}: {err:?}",
+                status_notification.station_id
+            );
+            None
+        },
+    }
+}
+
+// Store the numeric sampled values, for the metering statistics of the transactions
+async fn record_meter_values(station_id: &StationId, meter_values: &MeterValuesRequest) {
+    let Some(_permit) = db::ocpp_permit("storing the meter values").await else {
+        return;
+    };
+    for meter_value in &meter_values.meter_value {
+        for sampled_value in &meter_value.sampled_value {
+            // Signed data is not a number
+            let Ok(value) = sampled_value.value.parse::<f64>() else {
+                continue;
+            };
+            let reading = db::MeterReading {
+                station_id: station_id.clone(),
+                connector_id: meter_values.connector_id as i32,
+                transaction_id: meter_values.transaction_id,
+                // OCPP defaults of the optional fields
+                measurand: ocpp_name(&sampled_value.measurand)
+                    .unwrap_or_else(|| "Energy.Active.Import.Register".to_string()),
+                unit: ocpp_name(&sampled_value.unit).unwrap_or_else(|| "Wh".to_string()),
+                phase: ocpp_name(&sampled_value.phase),
+                value,
+                timestamp: meter_value.timestamp,
+            };
+            if let Err(err) = db::insert_meter_reading(&reading).await {
+                error!("Failed to store meter reading of {station_id}: {err:?}");
+            }
+        }
+    }
+}
+
+// Name of an OCPP enum value as it is serialized, e.g. "Energy.Active.Import.Register"
+fn ocpp_name<T: serde::Serialize>(value: &Option<T>) -> Option<String> {
+    serde_json::to_value(value.as_ref()?)
+        .ok()?
+        .as_str()
+        .map(str::to_string)
+}
+
+// Record the end of the transaction and estimate its cost from the tariff applicable when it
+// started. Returns the idTag that started the transaction
+async fn complete_transaction(
+    stop_transaction: &StopTransactionRequest,
+    reconciled: bool,
+) -> Option<String> {
+    let transaction_id = stop_transaction.transaction_id;
+    let stop_reason = stop_transaction
+        .reason
+        .as_ref()
+        .map(|reason| format!("{reason:?}"));
+    // meterStop is in Wh, but some chargers report it in the unit of their register. The register
+    // reading of the transaction data, when sent, tells its unit
+    let meter_stop = stop_transaction
+        .transaction_data
+        .as_deref()
+        .and_then(transactions::energy_register_wh)
+        .map_or(stop_transaction.meter_stop, |meter_stop| {
+            meter_stop.round() as i32
+        });
+    let _permit = db::ocpp_permit("completing the transaction").await?;
+    let export_energy_wh = export_energy_wh(stop_transaction).await;
+    let mut transaction = match db::complete_transaction(
+        transaction_id,
+        meter_stop,
+        export_energy_wh,
+        stop_transaction.timestamp,
+        stop_reason.as_deref(),
+    )
+    .await
+    {
+        Ok(Some(transaction)) => transaction,
+        Ok(None) => {
+            warn!("Transaction {transaction_id} is unknown or was already stopped");
+            return None;
+        },
+        Err(err) => {
+            error!("Failed to complete transaction {transaction_id}: {err:?}");
+            return None;
+        },
+    };
+    alerts::fire(
+        &transaction.station_id,
+        AlertEvent::TransactionStopped {
+            transaction_id,
+            duration_secs: (stop_transaction.timestamp - transaction.start_time).num_seconds(),
+        },
+    );
+    if reconciled {
+        check_reconciled_meter_stop(&transaction).await;
+    }
+    billing_anomalies::check_session_overlaps(transaction_id);
+    estimate_cost(&mut transaction).await;
+    #[cfg(feature = "kafka")]
+    kafka::publish_session_stopped(&transaction);
+    Some(transaction.id_tag)
+}
+
+// A transaction reconciled at startup missed the meter values sent while the server was down, and
+// the charger may have reset its register meanwhile. Its meterStop is checked against the readings
+// stored before, the energy of the transaction is not to be trusted when it is lower
+async fn check_reconciled_meter_stop(transaction: &db::CompletedTransaction) {
+    let transaction_id = transaction.id;
+    let last_reading_wh = match db::import_register_readings_wh(transaction_id).await {
+        Ok(readings) => readings.last().map(|(_, wh)| *wh),
+        Err(err) => {
+            error!("Failed to get meter readings of transaction {transaction_id}: {err:?}");
+            return;
+        },
+    };
+    let min_meter_stop = last_reading_wh.map_or(transaction.meter_start as f64, |wh| {
+        wh.max(transaction.meter_start as f64)
+    });
+    if (transaction.meter_stop as f64) < min_meter_stop {
+        warn!(
+            "meterStop {} Wh of reconciled transaction {transaction_id} is below its meterStart \
+             or last reading, {min_meter_stop} Wh, its energy is unreliable",
+            transaction.meter_stop
+        );
+    }
+}
+
+// Energy fed back to the grid during the transaction by a bi-directional (V2G) charger, from the
+// export register readings of its MeterValues and of the transaction data. 0 for the chargers that
+// do not report the export register
+async fn export_energy_wh(stop_transaction: &StopTransactionRequest) -> i64 {
+    let transaction_id = stop_transaction.transaction_id;
+    let readings = match db::export_register_readings_wh(transaction_id).await {
+        Ok(readings) => readings,
+        Err(err) => {
+            error!(
+                "Failed to get export register readings of transaction {transaction_id}: {err:?}"
+            );
+            None
+        },
+    };
+    let export_stop = stop_transaction
+        .transaction_data
+        .as_deref()
+        .and_then(transactions::export_register_wh)
+        .or(readings.map(|(_, last)| last));
+    match (readings, export_stop) {
+        (Some((export_start, _)), Some(export_stop)) => (export_stop - export_start)
+            .max(0.0)
+            .round() as i64,
+        _ => 0,
+    }
+}
+
+async fn estimate_cost(transaction: &mut db::CompletedTransaction) {
+    let transaction_id = transaction.id;
+    let breakdown = match cost_breakdown::estimate(transaction).await {
+        Ok(breakdown) => breakdown,
+        Err(err) => {
+            error!("Failed to get tariffs of transaction {transaction_id}: {err:?}");
+            return;
+        },
+    };
+    let Some(currency) = breakdown
+        .first()
+        .map(|period| period.currency.clone())
+    else {
+        warn!(
+            "No tariff of {} applies to transaction {transaction_id}",
+            transaction.station_id
+        );
+        return;
+    };
+    if breakdown
+        .iter()
+        .any(|period| period.currency != currency)
+    {
+        warn!(
+            "Tariffs of transaction {transaction_id} are in several currencies, its cost is the \
+             one in {currency}"
+        );
+    }
+    let cost = breakdown
+        .iter()
+        .filter(|period| period.currency == currency)
+        .map(|period| period.cost)
+        .sum();
+    if let Err(err) = db::insert_cost_breakdown(transaction_id, &breakdown).await {
+        error!("Failed to store cost breakdown of transaction {transaction_id}: {err:?}");
+        return;
+    }
+    if let Err(err) = db::set_transaction_cost(transaction_id, cost, &currency).await {
+        error!("Failed to store cost of transaction {transaction_id}: {err:?}");
+        return;
+    }
+    transaction.cost = Some(cost);
+    transaction.currency = Some(currency);
+}
+
+// Format a timestamp as OCPP 1.6 expects it, with milliseconds: YYYY-MM-DDTHH:MM:SS.mmmZ, or with
+// the offset of a timezone other than UTC. Chrono serializes up to nanoseconds, which some charger
+// firmware fails to parse
+fn ocpp_datetime<Tz: TimeZone>(date_time: DateTime<Tz>) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    date_time.to_rfc3339_opts(SecondsFormat::Millis, true)
+}
+
+// Serialize a CallResult with its currentTime field in the OCPP dateTime format
+fn with_ocpp_current_time<Tz: TimeZone>(
+    response: &OcppCallResult,
+    current_time: DateTime<Tz>,
+) -> String
+where
+    Tz::Offset: std::fmt::Display,
+{
+    let mut response_json = serde_json::to_value(response).unwrap();
+    response_json["Payload"]["currentTime"] = ocpp_datetime(current_time).into();
+    response_json.to_string()
+}
+
+// Compare a timestamp sent by the charger with the server clock. A skewed charger clock causes
+// billing and audit issues
+async fn check_clock_skew(station_id: &StationId, message_timestamp: DateTime<Utc>) {
+    const MAX_CLOCK_SKEW_SECS: &str = dotenv!("MAX_CLOCK_SKEW_SECS");
+    let max_clock_skew_secs: i64 = MAX_CLOCK_SKEW_SECS
+        .parse()
+        .expect("MAX_CLOCK_SKEW_SECS must be a number of seconds");
+    let server_timestamp = Utc::now();
+    let skew_secs = (message_timestamp - server_timestamp).num_seconds();
+    metrics::gauge!("ocpp_clock_skew_seconds", "station_id" => station_id.clone())
+        .set(skew_secs as f64);
+    if skew_secs.abs() > max_clock_skew_secs {
+        warn!(
+            station_id,
+            %message_timestamp,
+            %server_timestamp,
+            skew_secs,
+            "Charger clock is skewed from the server clock"
+        );
+    }
+    let Some(_permit) = db::ocpp_permit("storing the clock skew").await else {
+        return;
+    };
+    if let Err(err) = db::update_clock_skew(station_id, skew_secs).await {
+        error!("Failed to store clock skew of {station_id}: {err:?}");
+    }
+}
+
+// Split off the sampled values of a MeterValues payload whose measurand or unit is not part of
+// OCPP 1.6. They are kept in the database for debugging instead of being silently discarded
+async fn reject_unknown_sampled_values(payload: &mut serde_json::Value, station_id: &StationId) {
+    let connector_id = payload["connectorId"]
+        .as_i64()
+        .map(|id| id as i32);
+    let transaction_id = payload["transactionId"]
+        .as_i64()
+        .map(|id| id as i32);
+    // Indexing a value that is not an object to modify it panics. Payloads of the wrong shape are
+    // left to the typed deserialization, which answers them with a FormationViolation
+    let Some(meter_values) = payload
+        .as_object_mut()
+        .and_then(|payload| payload.get_mut("meterValue"))
+        .and_then(serde_json::Value::as_array_mut)
+    else {
+        return;
+    };
+    for meter_value in meter_values {
+        let Some(sampled_values) = meter_value
+            .as_object_mut()
+            .and_then(|meter_value| meter_value.get_mut("sampledValue"))
+            .and_then(serde_json::Value::as_array_mut)
+        else {
+            continue;
+        };
+        let (known, unknown): (Vec<_>, Vec<_>) =
+            sampled_values
+                .drain(..)
+                .partition(|sampled_value| {
+                    is_known::<Measurand>(&sampled_value["measurand"])
+                        && is_known::<UnitOfMeasure>(&sampled_value["unit"])
+                });
+        *sampled_values = known;
+        for sampled_value in unknown {
+            let reading = db::MeterReadingRaw {
+                station_id: station_id.clone(),
+                connector_id,
+                transaction_id,
+                measurand: sampled_value["measurand"]
+                    .as_str()
+                    .map(str::to_string),
+                unit: sampled_value["unit"]
+                    .as_str()
+                    .map(str::to_string),
+                sampled_value,
+            };
+            warn!(
+                "Unknown measurand or unit from {station_id}: {}",
+                reading.sampled_value
+            );
+            metrics::counter!("ocpp_unknown_measurand_total", "station_id" => station_id.clone())
+                .increment(1);
+            let Some(_permit) = db::ocpp_permit("storing the raw meter reading").await else {
+                continue;
+            };
+            if let Err(err) = db::insert_meter_reading_raw(&reading).await {
+                error!("Failed to store raw meter reading: {err:?}");
+            }
+        }
+    }
+}
+
+// An absent optional field falls back to its OCPP default, so only present values are checked
+fn is_known<T: serde::de::DeserializeOwned>(value: &serde_json::Value) -> bool {
+    value.is_null() || serde_json::from_value::<T>(value.clone()).is_ok()
+}
+
+// Handle the incoming OCPP CallResult messages
+#[tracing::instrument(skip_all, fields(%message_id))]
+async fn handle_ocpp_call_result<S>(
+    _: OcppMessageTypeId,
+    message_id: OcppMessageId,
+    payload: serde_json::Value,
+    _: &mut S,
+    station_id: &StationId,
+) {
+    // Hand the response over to the server-initiated Call waiting for it, which parses it as the
+    // response of its action
+    if !commands::complete_call(station_id, &message_id, Ok(payload)) {
+        warn!("CallResult from {station_id} answers no pending Call");
     }
 }
 
 // Handle the incoming OCPP CallError messages
-async fn handle_ocpp_call_error(
-    message_type_id: OcppMessageTypeId,
+#[tracing::instrument(skip_all, fields(%message_id, %error_code))]
+async fn handle_ocpp_call_error<S>(
+    _: OcppMessageTypeId,
     message_id: OcppMessageId,
     error_code: String,
     error_description: String,
     error_details: serde_json::Value,
-    socket: &mut axum::extract::ws::WebSocket,
+    _: &mut S,
+    station_id: &StationId,
 ) {
-    let ocpp_call_error = OcppCallError {
-        message_type_id,
-        message_id,
-        error_code,
-        error_description,
-        error_details,
-    };
-    let ocpp_call_error_json = serde_json::to_string(&ocpp_call_error).unwrap();
-    info!("Sending OCPP CallError: {ocpp_call_error_json}");
-    socket
-        .send(axum::extract::ws::Message::Text(ocpp_call_error_json))
-        .await
-        .unwrap();
+    // The charger rejected a server-initiated Call
+    if commands::complete_call(
+        station_id,
+        &message_id,
+        Err(commands::OcppError::CallError {
+            error_code: error_code.clone(),
+            error_description: error_description.clone(),
+        }),
+    ) {
+        warn!("Charger {station_id} responded with {error_code}: {error_description}");
+        return;
+    }
+    // A CallError is never answered, even one that answers no Call
+    warn!(
+        "CallError from {station_id} answers no pending Call: {error_code}: {error_description} \
+         {error_details}"
+    );
 }
 
+#[cfg(not(feature = "web-ui"))]
 async fn healthcheck_route() -> impl axum::response::IntoResponse {
     if let Some(time) = TIME_NOW.get() {
         axum::response::Html::from(format!("<h1>Server working. Started at: {time}</h1>"))
     } else {
-        axum::response::Html::from(format!("<h1>Server has not started yet</h1>"))
+        axum::response::Html::from("<h1>Server has not started yet</h1>".to_string())
     }
 }
@@ -0,0 +1,43 @@
+use std::{
+    collections::HashSet,
+    sync::{LazyLock, RwLock},
+};
+
+use crate::{db, StationId};
+
+/// Chargers whose `maintenance_mode` is set in the database, checked on every Authorize and
+/// StartTransaction without a query
+static IN_MAINTENANCE: LazyLock<RwLock<HashSet<StationId>>> = LazyLock::new(Default::default);
+
+/// Load the chargers in maintenance from the database
+pub async fn load() -> Result<usize, sqlx::Error> {
+    let station_ids: HashSet<_> = db::chargers_in_maintenance()
+        .await?
+        .into_iter()
+        .collect();
+    let count = station_ids.len();
+    *IN_MAINTENANCE.write().unwrap() = station_ids;
+    Ok(count)
+}
+
+/// Whether new sessions are rejected on the charger
+pub fn is_in_maintenance(station_id: &str) -> bool {
+    IN_MAINTENANCE
+        .read()
+        .unwrap()
+        .contains(station_id)
+}
+
+/// Put the charger in or out of maintenance. Returns whether the charger exists
+pub async fn set(station_id: &StationId, maintenance_mode: bool) -> Result<bool, sqlx::Error> {
+    if !db::set_maintenance_mode(station_id, maintenance_mode).await? {
+        return Ok(false);
+    }
+    let mut in_maintenance = IN_MAINTENANCE.write().unwrap();
+    if maintenance_mode {
+        in_maintenance.insert(station_id.clone());
+    } else {
+        in_maintenance.remove(station_id);
+    }
+    Ok(true)
+}
@@ -0,0 +1,53 @@
+use std::fmt;
+
+/// idTag values are RFID card numbers, which may be personal data under GDPR. They are masked in
+/// the logs of release builds, or of any build with the `mask_id_tags` feature enabled
+const MASK_ID_TAGS: bool = cfg!(any(feature = "mask_id_tags", not(debug_assertions)));
+
+/// JSON keys holding an idTag in the OCPP 1.6 payloads
+const ID_TAG_KEYS: [&str; 2] = ["idTag", "parentIdTag"];
+
+/// Mask an idTag keeping only its last 4 characters, e.g. `"****A1B2"`
+pub fn mask_id_tag(id_tag: &str) -> String {
+    if !MASK_ID_TAGS {
+        return id_tag.to_string();
+    }
+    let char_count = id_tag.chars().count();
+    if char_count <= 4 {
+        return "****".to_string();
+    }
+    let last_4_chars: String = id_tag
+        .chars()
+        .skip(char_count - 4)
+        .collect();
+    format!("****{last_4_chars}")
+}
+
+/// Mask every idTag found in a JSON value, at any depth
+pub fn mask_id_tags(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(object) => {
+            for (key, value) in object.iter_mut() {
+                match value {
+                    serde_json::Value::String(id_tag) if ID_TAG_KEYS.contains(&key.as_str()) => {
+                        *id_tag = mask_id_tag(id_tag);
+                    },
+                    _ => mask_id_tags(value),
+                }
+            }
+        },
+        serde_json::Value::Array(values) => values.iter_mut().for_each(mask_id_tags),
+        _ => (),
+    }
+}
+
+/// Displays an OCPP message or payload as JSON with its idTag values masked
+pub struct Masked<T>(pub T);
+
+impl<T: serde::Serialize> fmt::Display for Masked<T> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut value = serde_json::to_value(&self.0).map_err(|_| fmt::Error)?;
+        mask_id_tags(&mut value);
+        write!(f, "{value}")
+    }
+}
@@ -0,0 +1,41 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    sync::{LazyLock, RwLock},
+};
+
+use crate::db::{self, MeasurandStats};
+
+/// Statistics of each measurand, by measurand
+pub type MeterStats = BTreeMap<String, MeasurandStats>;
+
+/// Meter statistics of the completed transactions. No new readings arrive once a transaction
+/// ended, so they never go stale
+static METER_STATS_CACHE: LazyLock<RwLock<HashMap<i32, MeterStats>>> =
+    LazyLock::new(Default::default);
+
+/// Min, max, average and count of the readings of each measurand during the transaction. Returns
+/// `None` when the transaction does not exist
+pub async fn meter_stats(transaction_id: i32) -> Result<Option<MeterStats>, sqlx::Error> {
+    if let Some(stats) = METER_STATS_CACHE
+        .read()
+        .unwrap()
+        .get(&transaction_id)
+    {
+        return Ok(Some(stats.clone()));
+    }
+    let Some(transaction) = db::session_summary(transaction_id).await? else {
+        return Ok(None);
+    };
+    let stats: MeterStats = db::meter_stats(transaction_id)
+        .await?
+        .into_iter()
+        .map(|stats| (stats.measurand.clone(), stats))
+        .collect();
+    if transaction.stop_time.is_some() {
+        METER_STATS_CACHE
+            .write()
+            .unwrap()
+            .insert(transaction_id, stats.clone());
+    }
+    Ok(Some(stats))
+}
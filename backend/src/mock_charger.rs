@@ -0,0 +1,782 @@
+//! Charger side of OCPP 1.6, to test the handlers without a network connection. The mock registers
+//! itself like a connected charger: the server-initiated Calls reach it through the registry of
+//! the commands, and its own Calls go straight to the message handlers
+
+use std::{
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{
+    body::{self, Body},
+    extract::ws::Message as AxumWSMessage,
+    http::{header, Method, Request, StatusCode},
+};
+use chrono::{DateTime, TimeDelta, Utc};
+use futures::{channel::mpsc as futures_mpsc, StreamExt};
+use serde_json::json;
+use tokio::{sync::mpsc, task::JoinHandle};
+use tower::ServiceExt;
+use uuid::Uuid;
+
+use crate::{
+    api, commands, configuration, connectors, ocpp_version::OcppVersion, transactions,
+    MessageTypeId, OcppActionEnum, OcppCallError, OcppMessageType, StationId,
+};
+
+type Matcher = Box<dyn Fn(&serde_json::Value) -> bool + Send>;
+
+/// Response of the mock to the first Call of the action whose payload matches, `None` to leave
+/// the Call unanswered
+struct Expectation {
+    action: OcppActionEnum,
+    matcher: Matcher,
+    response: Option<Result<serde_json::Value, (String, String)>>,
+}
+
+/// Answer of the server to a Call of the mock
+#[derive(Debug, Clone, PartialEq)]
+pub enum CallResponse {
+    CallResult(serde_json::Value),
+    CallError(OcppCallError),
+}
+
+pub struct MockCharger {
+    station_id: StationId,
+    connection_id: Uuid,
+    expectations: Arc<Mutex<Vec<Expectation>>>,
+    responder: JoinHandle<()>,
+}
+
+/// Response of an expected Call, see [`MockCharger::expect_call`]
+#[must_use = "the Call is only expected once its response is set"]
+pub struct ResponseBuilder<'a> {
+    charger: &'a MockCharger,
+    action: OcppActionEnum,
+    matcher: Matcher,
+}
+
+impl ResponseBuilder<'_> {
+    /// Answer with a CallResult of the payload
+    pub fn respond_with(self, payload: serde_json::Value) { self.expect(Some(Ok(payload))); }
+
+    /// Answer with a CallError
+    pub fn respond_with_error(self, error_code: &str, error_description: &str) {
+        self.expect(Some(Err((
+            error_code.to_string(),
+            error_description.to_string(),
+        ))));
+    }
+
+    /// Never answer, like a charger that went silent
+    pub fn never_respond(self) { self.expect(None); }
+
+    fn expect(self, response: Option<Result<serde_json::Value, (String, String)>>) {
+        self.charger
+            .expectations
+            .lock()
+            .unwrap()
+            .push(Expectation {
+                action: self.action,
+                matcher: self.matcher,
+                response,
+            });
+    }
+}
+
+impl MockCharger {
+    /// Register a connected OCPP 1.6 charger. Every test needs a station ID of its own, as the
+    /// registry is shared by the tests
+    pub fn connect(station_id: &str) -> Self {
+        let station_id = station_id.to_string();
+        let connection_id = Uuid::new_v4();
+        let expectations: Arc<Mutex<Vec<Expectation>>> = Default::default();
+        let (sender, receiver) = mpsc::unbounded_channel();
+        commands::register_charger(&station_id, connection_id, OcppVersion::V16, sender);
+        let responder = tokio::spawn(respond(station_id.clone(), expectations.clone(), receiver));
+        Self {
+            station_id,
+            connection_id,
+            expectations,
+            responder,
+        }
+    }
+
+    /// Expect a Call of the action whose payload matches. A Call that was not expected is answered
+    /// with a NotImplemented CallError
+    pub fn expect_call(
+        &self,
+        action: OcppActionEnum,
+        matcher: impl Fn(&serde_json::Value) -> bool + Send + 'static,
+    ) -> ResponseBuilder<'_> {
+        ResponseBuilder {
+            charger: self,
+            action,
+            matcher: Box::new(matcher),
+        }
+    }
+
+    /// Send a Call to the server and return its CallResult or CallError
+    pub async fn send_call(
+        &self,
+        action: OcppActionEnum,
+        payload: serde_json::Value,
+    ) -> CallResponse {
+        self.send_call_with_message_id(&Uuid::new_v4().to_string(), action, payload)
+            .await
+    }
+
+    /// Send a Call with the given MessageId, see [`MockCharger::send_call`]
+    pub async fn send_call_with_message_id(
+        &self,
+        message_id: &str,
+        action: OcppActionEnum,
+        payload: serde_json::Value,
+    ) -> CallResponse {
+        let call = OcppMessageType::Call(
+            MessageTypeId::CALL,
+            message_id.to_string(),
+            action.to_string(),
+            payload,
+        );
+        let (mut sender, mut receiver) = futures_mpsc::unbounded();
+        crate::handle_ocpp_messages(
+            serde_json::to_string(&call).unwrap(),
+            &mut sender,
+            &self.station_id,
+        )
+        .await
+        .unwrap();
+        drop(sender);
+        let Some(AxumWSMessage::Text(response)) = receiver.next().await else {
+            panic!("Server did not answer the {action} Call");
+        };
+        // The server answers with the objects of OcppCallResult and OcppCallError
+        if let Ok(call_error) = serde_json::from_str(&response) {
+            return CallResponse::CallError(call_error);
+        }
+        let mut call_result: serde_json::Value = serde_json::from_str(&response).unwrap();
+        CallResponse::CallResult(call_result["Payload"].take())
+    }
+}
+
+impl Drop for MockCharger {
+    fn drop(&mut self) {
+        self.responder.abort();
+        commands::unregister_charger(&self.station_id, self.connection_id);
+    }
+}
+
+/// Answer the Calls sent by the server, handing the responses to the handlers like a connection
+/// would
+async fn respond(
+    station_id: StationId,
+    expectations: Arc<Mutex<Vec<Expectation>>>,
+    mut receiver: mpsc::UnboundedReceiver<String>,
+) {
+    while let Some(call) = receiver.recv().await {
+        let Ok(OcppMessageType::Call(_, message_id, action, payload)) = serde_json::from_str(&call)
+        else {
+            panic!("Server sent an invalid Call: {call}");
+        };
+        let response = {
+            let mut expectations = expectations.lock().unwrap();
+            let expected = expectations
+                .iter()
+                .position(|expectation| {
+                    expectation.action.to_string() == action && (expectation.matcher)(&payload)
+                });
+            match expected {
+                Some(index) => expectations.remove(index).response,
+                None => Some(Err((
+                    "NotImplemented".to_string(),
+                    format!("Unexpected {action} Call"),
+                ))),
+            }
+        };
+        let Some(response) = response else {
+            continue;
+        };
+        let response = match response {
+            Ok(payload) => {
+                OcppMessageType::CallResult(MessageTypeId::CALL_RESULT, message_id, payload)
+            },
+            Err((error_code, error_description)) => OcppMessageType::CallError(
+                MessageTypeId::CALL_ERROR,
+                message_id,
+                error_code,
+                error_description,
+                json!({}),
+            ),
+        };
+        // Responses are not answered, the sink is never written to
+        let mut sink = futures::sink::drain();
+        crate::handle_ocpp_messages(
+            serde_json::to_string(&response).unwrap(),
+            &mut sink,
+            &station_id,
+        )
+        .await
+        .unwrap();
+    }
+}
+
+/// POST the body to the REST API, returning the status and JSON body of the response
+async fn post(uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    send_request(Method::POST, uri, body).await
+}
+
+/// PUT the body to the REST API, see [`post`]
+async fn put(uri: &str, body: serde_json::Value) -> (StatusCode, serde_json::Value) {
+    send_request(Method::PUT, uri, body).await
+}
+
+/// DELETE a resource of the REST API, see [`post`]
+async fn delete(uri: &str) -> (StatusCode, serde_json::Value) {
+    send_request(Method::DELETE, uri, serde_json::Value::Null).await
+}
+
+async fn send_request(
+    method: Method,
+    uri: &str,
+    body: serde_json::Value,
+) -> (StatusCode, serde_json::Value) {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body.to_string()))
+        .unwrap();
+    let response = api::router()
+        .oneshot(request)
+        .await
+        .unwrap();
+    let status = response.status();
+    let body = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    // No Content responses have no body
+    let body = if body.is_empty() {
+        serde_json::Value::Null
+    } else {
+        serde_json::from_slice(&body).unwrap()
+    };
+    (status, body)
+}
+
+#[tokio::test]
+async fn remote_start_transaction_is_accepted() {
+    let charger = MockCharger::connect("MOCK-REMOTE-START-ACCEPTED");
+    charger
+        .expect_call(OcppActionEnum::RemoteStartTransaction, |payload| {
+            *payload == json!({ "connectorId": 1, "idTag": "B4F62CEF" })
+        })
+        .respond_with(json!({ "status": "Accepted" }));
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-START-ACCEPTED/remote-start",
+        json!({ "connector_id": 1, "id_tag": "B4F62CEF" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "status": "Accepted" }));
+}
+
+#[tokio::test]
+async fn remote_start_transaction_is_rejected() {
+    let charger = MockCharger::connect("MOCK-REMOTE-START-REJECTED");
+    charger
+        .expect_call(OcppActionEnum::RemoteStartTransaction, |_| true)
+        .respond_with(json!({ "status": "Rejected" }));
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-START-REJECTED/remote-start",
+        json!({ "id_tag": "B4F62CEF" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "status": "Rejected" }));
+}
+
+#[tokio::test]
+async fn remote_start_transaction_call_error() {
+    let charger = MockCharger::connect("MOCK-REMOTE-START-CALL-ERROR");
+    charger
+        .expect_call(OcppActionEnum::RemoteStartTransaction, |_| true)
+        .respond_with_error("InternalError", "Connector is faulted");
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-START-CALL-ERROR/remote-start",
+        json!({ "connector_id": 2, "id_tag": "B4F62CEF" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::BAD_GATEWAY);
+    assert_eq!(body["error"], "charger_error");
+}
+
+#[tokio::test]
+async fn concurrent_remote_start_transactions_on_a_connector() {
+    let charger = MockCharger::connect("MOCK-REMOTE-START-CONCURRENT");
+    for _ in 0..3 {
+        charger
+            .expect_call(OcppActionEnum::RemoteStartTransaction, |_| true)
+            .respond_with(json!({ "status": "Accepted" }));
+    }
+    let uri = "/chargers/MOCK-REMOTE-START-CONCURRENT/remote-start";
+    let connector_1 = json!({ "connector_id": 1, "id_tag": "B4F62CEF" });
+    let connector_2 = json!({ "connector_id": 2, "id_tag": "B4F62CEF" });
+    // The first start is waiting for the charger when the others are sent
+    let (first, second, other_connector) = tokio::join!(
+        post(uri, connector_1.clone()),
+        post(uri, connector_1.clone()),
+        post(uri, connector_2),
+    );
+    assert_eq!(first, (StatusCode::OK, json!({ "status": "Accepted" })));
+    assert_eq!(second.0, StatusCode::CONFLICT);
+    assert_eq!(second.1["error"], "conflict");
+    assert_eq!(
+        other_connector,
+        (StatusCode::OK, json!({ "status": "Accepted" }))
+    );
+    // Released once the charger answered
+    assert_eq!(
+        post(uri, connector_1).await,
+        (StatusCode::OK, json!({ "status": "Accepted" }))
+    );
+}
+
+#[tokio::test]
+async fn remote_start_transaction_of_disconnected_charger() {
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-START-DISCONNECTED/remote-start",
+        json!({ "id_tag": "B4F62CEF" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+    assert_eq!(body["error"], "charger_not_connected");
+}
+
+#[tokio::test]
+async fn remote_start_transaction_with_charging_profile() {
+    let charger = MockCharger::connect("MOCK-REMOTE-START-CHARGING-PROFILE");
+    let charging_profile = json!({
+        "chargingProfileId": 1,
+        "stackLevel": 0,
+        "chargingProfilePurpose": "TxProfile",
+        "chargingProfileKind": "Relative",
+        "chargingSchedule": {
+            "chargingRateUnit": "A",
+            "chargingSchedulePeriod": [{ "startPeriod": 0, "limit": 16.0 }],
+        },
+    });
+    charger
+        .expect_call(OcppActionEnum::RemoteStartTransaction, {
+            let charging_profile = charging_profile.clone();
+            move |payload| payload["chargingProfile"] == charging_profile
+        })
+        .respond_with(json!({ "status": "Accepted" }));
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-START-CHARGING-PROFILE/remote-start",
+        json!({ "connector_id": 1, "id_tag": "B4F62CEF", "charging_profile": charging_profile }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "status": "Accepted" }));
+}
+
+#[tokio::test]
+async fn remote_start_transaction_with_invalid_charging_profile() {
+    // Not expected by the charger, the profile must not reach it
+    let _charger = MockCharger::connect("MOCK-REMOTE-START-INVALID-PROFILE");
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-START-INVALID-PROFILE/remote-start",
+        json!({
+            "id_tag": "B4F62CEF",
+            "charging_profile": {
+                "chargingProfileId": 1,
+                "stackLevel": 0,
+                "chargingProfilePurpose": "TxDefaultProfile",
+                "chargingProfileKind": "Relative",
+                "chargingSchedule": {
+                    "chargingRateUnit": "A",
+                    "chargingSchedulePeriod": [{ "startPeriod": 0, "limit": 16.0 }],
+                },
+            },
+        }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["detail"], "chargingProfilePurpose must be TxProfile");
+}
+
+#[tokio::test]
+async fn remote_stop_transaction_is_accepted() {
+    let charger = MockCharger::connect("MOCK-REMOTE-STOP-ACCEPTED");
+    transactions::start(
+        &"MOCK-REMOTE-STOP-ACCEPTED".to_string(),
+        1,
+        -1801,
+        "B4F62CEF",
+        Utc::now(),
+        0,
+    );
+    charger
+        .expect_call(OcppActionEnum::RemoteStopTransaction, |payload| {
+            *payload == json!({ "transactionId": -1801 })
+        })
+        .respond_with(json!({ "status": "Accepted" }));
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-STOP-ACCEPTED/remote-stop",
+        json!({ "transaction_id": -1801 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "status": "Accepted" }));
+}
+
+#[tokio::test]
+async fn remote_stop_transaction_of_another_charger() {
+    let _charger = MockCharger::connect("MOCK-REMOTE-STOP-FORBIDDEN");
+    transactions::start(
+        &"MOCK-REMOTE-STOP-OWNER".to_string(),
+        1,
+        -1802,
+        "B4F62CEF",
+        Utc::now(),
+        0,
+    );
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-STOP-FORBIDDEN/remote-stop",
+        json!({ "transaction_id": -1802 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::FORBIDDEN);
+    assert_eq!(body["error"], "forbidden");
+}
+
+#[test]
+fn remote_stop_transaction_already_stopped() {
+    // Stopped transactions are only found in the database, the mock has none
+    let result = api::validate_remote_stop(
+        &"MOCK-REMOTE-STOP-STOPPED".to_string(),
+        -1803,
+        "MOCK-REMOTE-STOP-STOPPED",
+        "completed",
+    );
+    assert!(matches!(result, Err(api::ApiError::Conflict(_))));
+}
+
+#[tokio::test]
+async fn remote_stop_transaction_of_disconnected_charger() {
+    transactions::start(
+        &"MOCK-REMOTE-STOP-DISCONNECTED".to_string(),
+        1,
+        -1804,
+        "B4F62CEF",
+        Utc::now(),
+        0,
+    );
+    let (status, body) = post(
+        "/chargers/MOCK-REMOTE-STOP-DISCONNECTED/remote-stop",
+        json!({ "transaction_id": -1804 }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::ACCEPTED);
+    assert_eq!(body, json!({ "status": "Queued" }));
+}
+
+#[tokio::test]
+async fn clock_aligned_data_interval_of_zero_is_disabled() {
+    let charger = MockCharger::connect("MOCK-CLOCK-ALIGNED-DISABLED");
+    charger
+        .expect_call(OcppActionEnum::ChangeConfiguration, |payload| {
+            *payload == json!({ "key": "ClockAlignedDataInterval", "value": "0" })
+        })
+        .respond_with(json!({ "status": "Accepted" }));
+    let (status, body) = put(
+        "/chargers/MOCK-CLOCK-ALIGNED-DISABLED/configuration/ClockAlignedDataInterval",
+        json!({ "value": "0" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body, json!({ "status": "Accepted" }));
+    assert_eq!(configuration::clock_aligned_data_interval("0"), Ok(None));
+    assert_eq!(
+        configuration::clock_aligned_data_interval("900"),
+        Ok(Some(Duration::from_secs(900)))
+    );
+}
+
+#[tokio::test]
+async fn clock_aligned_data_interval_must_not_be_negative() {
+    // Not sent to the charger, which would answer NotImplemented
+    let _charger = MockCharger::connect("MOCK-CLOCK-ALIGNED-NEGATIVE");
+    let (status, body) = put(
+        "/chargers/MOCK-CLOCK-ALIGNED-NEGATIVE/configuration/ClockAlignedDataInterval",
+        json!({ "value": "-900" }),
+    )
+    .await;
+    assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+    assert_eq!(body["error"], "unprocessable");
+}
+
+#[tokio::test]
+async fn debug_mode_restores_the_log_level() {
+    let charger = MockCharger::connect("MOCK-DEBUG-MODE");
+    charger
+        .expect_call(OcppActionEnum::GetConfiguration, |_| true)
+        .respond_with(json!({
+            "configurationKey": [{ "key": "LogLevel", "readonly": false, "value": "Info" }],
+        }));
+    charger
+        .expect_call(OcppActionEnum::ChangeConfiguration, |payload| {
+            *payload == json!({ "key": "LogLevel", "value": "Debug" })
+        })
+        .respond_with(json!({ "status": "Accepted" }));
+    let (status, body) = post("/chargers/MOCK-DEBUG-MODE/debug-mode", json!({})).await;
+    assert_eq!(status, StatusCode::OK);
+    assert_eq!(body["original_log_level"], "Info");
+    charger
+        .expect_call(OcppActionEnum::ChangeConfiguration, |payload| {
+            *payload == json!({ "key": "LogLevel", "value": "Info" })
+        })
+        .respond_with(json!({ "status": "Accepted" }));
+    let (status, _) = delete("/chargers/MOCK-DEBUG-MODE/debug-mode").await;
+    assert_eq!(status, StatusCode::NO_CONTENT);
+    let (status, _) = delete("/chargers/MOCK-DEBUG-MODE/debug-mode").await;
+    assert_eq!(status, StatusCode::NOT_FOUND);
+}
+
+#[tokio::test]
+async fn concurrent_start_transactions_on_a_charging_connector() {
+    let station_id = "MOCK-CONCURRENT-START-TRANSACTIONS".to_string();
+    let charger = MockCharger::connect(&station_id);
+    transactions::start(&station_id, 1, -1421, "B4F62CEF", Utc::now(), 0);
+    connectors::update_status(
+        &station_id,
+        1,
+        rust_ocpp::v1_6::types::ChargePointStatus::Charging,
+    );
+    let start_transaction = |id_tag| {
+        charger.send_call(
+            OcppActionEnum::StartTransaction,
+            json!({
+                "connectorId": 1,
+                "idTag": id_tag,
+                "meterStart": 0,
+                "timestamp": Utc::now(),
+            }),
+        )
+    };
+    // Both are answered with the running transaction, none of them is stored
+    let responses = tokio::join!(start_transaction("B4F62CEF"), start_transaction("A3E51BDE"));
+    for response in [responses.0, responses.1] {
+        let CallResponse::CallResult(payload) = response else {
+            panic!("Expected a CallResult, got {response:?}");
+        };
+        assert_eq!(
+            payload,
+            json!({ "idTagInfo": { "status": "ConcurrentTx" }, "transactionId": -1421 })
+        );
+    }
+}
+
+#[tokio::test]
+async fn call_with_payload_of_another_action() {
+    let charger = MockCharger::connect("MOCK-PAYLOAD-OF-ANOTHER-ACTION");
+    let response = charger
+        .send_call(
+            OcppActionEnum::Authorize,
+            json!({ "connectorId": 1, "type": "Inoperative" }),
+        )
+        .await;
+    let CallResponse::CallError(call_error) = response else {
+        panic!("Expected a CallError, got {response:?}");
+    };
+    assert_eq!(call_error.error_code, "FormationViolation");
+}
+
+#[tokio::test]
+async fn meter_values_payload_that_is_not_an_object() {
+    let charger = MockCharger::connect("MOCK-METER-VALUES-NOT-AN-OBJECT");
+    for payload in [json!([]), json!({ "connectorId": 1, "meterValue": [1] })] {
+        let response = charger
+            .send_call(OcppActionEnum::MeterValues, payload)
+            .await;
+        let CallResponse::CallError(call_error) = response else {
+            panic!("Expected a CallError, got {response:?}");
+        };
+        assert_eq!(call_error.error_code, "FormationViolation");
+    }
+}
+
+#[tokio::test]
+async fn connector_id_above_the_connectors_of_the_charger() {
+    let charger = MockCharger::connect("MOCK-CONNECTOR-ID-ABOVE-CONNECTORS");
+    let response = charger
+        .send_call(
+            OcppActionEnum::StatusNotification,
+            json!({ "connectorId": 11, "errorCode": "NoError", "status": "Available" }),
+        )
+        .await;
+    let CallResponse::CallError(call_error) = response else {
+        panic!("Expected a CallError, got {response:?}");
+    };
+    assert_eq!(call_error.error_code, "PropertyConstraintViolation");
+}
+
+#[tokio::test]
+async fn duplicate_message_id_in_session() {
+    let charger = MockCharger::connect("MOCK-DUPLICATE-MESSAGE-ID");
+    // A payload the server rejects before looking up the idTag, the MessageId is still recorded
+    let response = charger
+        .send_call_with_message_id("authorize-1", OcppActionEnum::Authorize, json!({}))
+        .await;
+    assert!(matches!(response, CallResponse::CallError(_)));
+    let response = charger
+        .send_call_with_message_id("authorize-1", OcppActionEnum::Authorize, json!({}))
+        .await;
+    let CallResponse::CallError(call_error) = response else {
+        panic!("Expected a CallError, got {response:?}");
+    };
+    assert_eq!(call_error.error_code, "GenericError");
+    assert_eq!(
+        call_error.error_description,
+        "Duplicate message ID in session"
+    );
+}
+
+#[tokio::test]
+async fn duplicate_heartbeat_message_id_is_allowed() {
+    let charger = MockCharger::connect("MOCK-DUPLICATE-HEARTBEAT-MESSAGE-ID");
+    for _ in 0..2 {
+        let response = charger
+            .send_call_with_message_id("1", OcppActionEnum::Heartbeat, json!({}))
+            .await;
+        assert!(matches!(response, CallResponse::CallResult(_)));
+    }
+}
+
+#[tokio::test]
+async fn message_id_longer_than_36_characters() {
+    let charger = MockCharger::connect("MOCK-LONG-MESSAGE-ID");
+    let response = charger
+        .send_call_with_message_id(&"1".repeat(1000), OcppActionEnum::Heartbeat, json!({}))
+        .await;
+    let CallResponse::CallError(call_error) = response else {
+        panic!("Expected a CallError, got {response:?}");
+    };
+    assert_eq!(call_error.error_code, "FormationViolation");
+    assert_eq!(
+        call_error.error_description,
+        "MessageId is 1000 characters long, at most 36 are allowed"
+    );
+}
+
+#[tokio::test]
+async fn message_id_of_36_characters() {
+    let charger = MockCharger::connect("MOCK-MESSAGE-ID-OF-36-CHARACTERS");
+    // 72 bytes in UTF-8, the length is counted in characters
+    let response = charger
+        .send_call_with_message_id(&"é".repeat(36), OcppActionEnum::Heartbeat, json!({}))
+        .await;
+    assert!(matches!(response, CallResponse::CallResult(_)));
+}
+
+#[tokio::test]
+async fn call_result_with_a_long_message_id_is_not_answered() {
+    let call_result =
+        OcppMessageType::CallResult(MessageTypeId::CALL_RESULT, "1".repeat(1000), json!({}));
+    let (mut sender, mut receiver) = futures_mpsc::unbounded();
+    crate::handle_ocpp_messages(
+        serde_json::to_string(&call_result).unwrap(),
+        &mut sender,
+        &"MOCK-LONG-CALL-RESULT-MESSAGE-ID".to_string(),
+    )
+    .await
+    .unwrap();
+    drop(sender);
+    assert_eq!(receiver.next().await, None);
+}
+
+#[tokio::test]
+async fn response_to_a_closed_socket_fails() {
+    let call = OcppMessageType::Call(
+        MessageTypeId::CALL,
+        Uuid::new_v4().to_string(),
+        OcppActionEnum::Heartbeat.to_string(),
+        json!({}),
+    );
+    let (mut sender, receiver) = futures_mpsc::unbounded();
+    drop(receiver);
+    let result = crate::handle_ocpp_messages(
+        serde_json::to_string(&call).unwrap(),
+        &mut sender,
+        &"MOCK-CLOSED-SOCKET".to_string(),
+    )
+    .await;
+    assert!(result.is_err());
+}
+
+/// Whether the charger is reported as late at the given time, with no grace
+fn reported_late(station_id: &str, now: DateTime<Utc>) -> bool {
+    commands::late_heartbeats(now, |_| TimeDelta::zero())
+        .iter()
+        .any(|(late_station_id, ..)| late_station_id == station_id)
+}
+
+// A single test, as reporting the late chargers marks those of the other tests too
+#[tokio::test]
+async fn late_heartbeat_is_reported_once() {
+    const STATION_ID: &str = "MOCK-LATE-HEARTBEAT";
+    let charger = MockCharger::connect(STATION_ID);
+    let response = charger
+        .send_call(OcppActionEnum::Heartbeat, json!({}))
+        .await;
+    assert!(matches!(response, CallResponse::CallResult(_)));
+    // Every 300 s, as given in the BootNotification response
+    let now = Utc::now();
+    assert!(!reported_late(STATION_ID, now + TimeDelta::seconds(290)));
+    assert!(reported_late(STATION_ID, now + TimeDelta::seconds(310)));
+    assert!(!reported_late(STATION_ID, now + TimeDelta::seconds(320)));
+    // Until the next Heartbeat, which is late after the interval the charger accepted
+    charger
+        .expect_call(OcppActionEnum::ChangeConfiguration, |payload| {
+            *payload == json!({ "key": "HeartbeatInterval", "value": "60" })
+        })
+        .respond_with(json!({ "status": "Accepted" }));
+    configuration::change(&STATION_ID.to_string(), "HeartbeatInterval", "60")
+        .await
+        .unwrap();
+    charger
+        .send_call(OcppActionEnum::Heartbeat, json!({}))
+        .await;
+    let now = Utc::now();
+    assert!(!reported_late(STATION_ID, now + TimeDelta::seconds(50)));
+    assert!(reported_late(STATION_ID, now + TimeDelta::seconds(70)));
+    assert!(!reported_late(STATION_ID, now + TimeDelta::seconds(80)));
+}
+
+#[tokio::test]
+async fn call_timeout() {
+    tokio::time::pause();
+    let charger = MockCharger::connect("MOCK-CALL-TIMEOUT");
+    charger
+        .expect_call(OcppActionEnum::Reset, |_| true)
+        .never_respond();
+    let call = tokio::spawn(async {
+        commands::send_call::<_, serde_json::Value>(
+            &"MOCK-CALL-TIMEOUT".to_string(),
+            OcppActionEnum::Reset,
+            &json!({ "type": "Soft" }),
+        )
+        .await
+    });
+    // Let the Call be sent before the clock moves. OCPP_CALL_TIMEOUT_SECS is 30
+    tokio::task::yield_now().await;
+    tokio::time::advance(Duration::from_secs(29)).await;
+    assert!(!call.is_finished());
+    tokio::time::advance(Duration::from_secs(2)).await;
+    let response = call.await.unwrap();
+    assert!(
+        matches!(response, Err(commands::OcppError::Timeout)),
+        "Expected a timeout, got {response:?}"
+    );
+}
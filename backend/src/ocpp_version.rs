@@ -0,0 +1,123 @@
+use axum::{extract::ws::Message as AxumWSMessage, http::HeaderMap};
+use futures::Sink;
+use tracing::warn;
+
+use crate::{OcppCallError, OcppMessageType, StationId};
+
+/// OCPP version of a connection, negotiated through the WebSocket subprotocol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OcppVersion {
+    V16,
+    V201,
+}
+
+impl OcppVersion {
+    /// Versions accepted by the server, the preferred one first
+    const SUPPORTED: [OcppVersion; 2] = [OcppVersion::V16, OcppVersion::V201];
+
+    /// WebSocket subprotocol of the version
+    pub fn protocol(self) -> &'static str {
+        match self {
+            OcppVersion::V16 => "ocpp1.6",
+            OcppVersion::V201 => "ocpp2.0.1",
+        }
+    }
+
+    /// Preferred version among those offered by the charger in `Sec-WebSocket-Protocol`, `None`
+    /// when it offers none of them
+    pub fn negotiate(headers: &HeaderMap) -> Option<Self> {
+        let offered: Vec<&str> = headers
+            .get_all("sec-websocket-protocol")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect();
+        Self::SUPPORTED
+            .into_iter()
+            .find(|version| offered.contains(&version.protocol()))
+    }
+
+    /// Handle an OCPP-J message of a connection of this version. Fails when the response could not
+    /// be written to the socket
+    pub async fn handle_messages<S>(
+        self,
+        message: String,
+        socket: &mut S,
+        station_id: &StationId,
+    ) -> Result<(), S::Error>
+    where
+        S: Sink<AxumWSMessage> + Unpin,
+        S::Error: std::fmt::Debug,
+    {
+        match self {
+            OcppVersion::V16 => {
+                V16Handler
+                    .handle_messages(message, socket, station_id)
+                    .await
+            },
+            OcppVersion::V201 => {
+                V201Handler
+                    .handle_messages(message, socket, station_id)
+                    .await
+            },
+        }
+    }
+}
+
+/// Messages of a connection, parsed and answered according to its OCPP version
+pub trait VersionedHandler {
+    async fn handle_messages<S>(
+        &self,
+        message: String,
+        socket: &mut S,
+        station_id: &StationId,
+    ) -> Result<(), S::Error>
+    where
+        S: Sink<AxumWSMessage> + Unpin,
+        S::Error: std::fmt::Debug;
+}
+
+pub struct V16Handler;
+
+impl VersionedHandler for V16Handler {
+    async fn handle_messages<S>(
+        &self,
+        message: String,
+        socket: &mut S,
+        station_id: &StationId,
+    ) -> Result<(), S::Error>
+    where
+        S: Sink<AxumWSMessage> + Unpin,
+        S::Error: std::fmt::Debug,
+    {
+        crate::handle_ocpp_messages(message, socket, station_id).await
+    }
+}
+
+/// No OCPP 2.0.1 message is supported yet. The Calls are answered with a NotImplemented CallError,
+/// as OCPP-J requires for unknown actions, and the server sends no Call to these chargers
+pub struct V201Handler;
+
+impl VersionedHandler for V201Handler {
+    async fn handle_messages<S>(
+        &self,
+        message: String,
+        socket: &mut S,
+        station_id: &StationId,
+    ) -> Result<(), S::Error>
+    where
+        S: Sink<AxumWSMessage> + Unpin,
+        S::Error: std::fmt::Debug,
+    {
+        let Ok(OcppMessageType::Call(_, message_id, action, _)) = serde_json::from_str(&message)
+        else {
+            warn!("Dropped OCPP 2.0.1 message of {station_id}, only Calls are answered");
+            return Ok(());
+        };
+        warn!("OCPP 2.0.1 {action} Call from {station_id} is not supported");
+        let ocpp_call_error =
+            OcppCallError::not_implemented(message_id, &format!("OCPP 2.0.1 {action}"));
+        crate::send_call_error(socket, ocpp_call_error).await
+    }
+}
@@ -0,0 +1,71 @@
+use std::{
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use axum::extract::ws::{Message as AxumWSMessage, WebSocket};
+use dotenvy_macro::dotenv;
+use futures::{stream::SplitSink, Sink, SinkExt};
+use tokio::sync::mpsc::{self, error::TrySendError};
+use tracing::{error, warn, Instrument, Span};
+
+fn outbound_buffer_size() -> usize {
+    const OUTBOUND_BUFFER_SIZE: &str = dotenv!("OUTBOUND_BUFFER_SIZE");
+    OUTBOUND_BUFFER_SIZE
+        .parse()
+        .expect("OUTBOUND_BUFFER_SIZE must be a number of messages")
+}
+
+/// The writer task of the connection stopped, after the socket failed
+#[derive(Debug)]
+pub struct WriterClosed;
+
+/// Messages to write to a WebSocket. Sending only queues the message for the writer task of the
+/// connection, so a slow socket never holds up the OCPP handlers. A message is dropped when
+/// `OUTBOUND_BUFFER_SIZE` messages are already waiting
+#[derive(Debug, Clone)]
+pub struct OutboundSender(mpsc::Sender<AxumWSMessage>);
+
+/// Spawn the task writing the queued messages to the socket, in order
+pub fn spawn_writer(mut socket: SplitSink<WebSocket, AxumWSMessage>) -> OutboundSender {
+    let (sender, mut receiver) = mpsc::channel(outbound_buffer_size());
+    tokio::spawn(
+        async move {
+            while let Some(message) = receiver.recv().await {
+                if let Err(err) = socket.send(message).await {
+                    error!("Failed to write to the WebSocket: {err:?}");
+                    break;
+                }
+            }
+        }
+        .instrument(Span::current()),
+    );
+    OutboundSender(sender)
+}
+
+impl Sink<AxumWSMessage> for OutboundSender {
+    type Error = WriterClosed;
+
+    fn poll_ready(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn start_send(self: Pin<&mut Self>, message: AxumWSMessage) -> Result<(), Self::Error> {
+        match self.0.try_send(message) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => {
+                warn!("Outbound buffer of the WebSocket is full, dropping a message");
+                Ok(())
+            },
+            Err(TrySendError::Closed(_)) => Err(WriterClosed),
+        }
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+
+    fn poll_close(self: Pin<&mut Self>, _: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        Poll::Ready(Ok(()))
+    }
+}
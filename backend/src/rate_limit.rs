@@ -0,0 +1,369 @@
+use std::{
+    cmp::Reverse,
+    fmt,
+    net::{IpAddr, Ipv6Addr},
+    sync::{
+        atomic::{AtomicU32, Ordering},
+        LazyLock,
+    },
+    time::{Duration, Instant},
+};
+
+use dashmap::DashMap;
+use dotenvy_macro::dotenv;
+use tracing::warn;
+
+use crate::StationId;
+
+/// Open charger connections of each client IP
+static CONNECTIONS_PER_IP: LazyLock<DashMap<IpPrefix, AtomicU32>> = LazyLock::new(Default::default);
+
+fn max_connections_per_ip() -> u32 {
+    const MAX_CONNECTIONS_PER_IP: &str = dotenv!("MAX_CONNECTIONS_PER_IP");
+    MAX_CONNECTIONS_PER_IP
+        .parse()
+        .expect("MAX_CONNECTIONS_PER_IP must be a number of connections")
+}
+
+/// Address the connections are counted by. An IPv6 client usually owns a whole /64, so IPv6
+/// addresses are grouped by their /64 prefix
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct IpPrefix(IpAddr);
+
+impl From<IpAddr> for IpPrefix {
+    fn from(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V4(_) => Self(ip),
+            IpAddr::V6(ipv6) => match ipv6.to_ipv4_mapped() {
+                Some(ipv4) => Self(IpAddr::V4(ipv4)),
+                None => Self(IpAddr::V6(Ipv6Addr::from_bits(
+                    ipv6.to_bits() & !(u64::MAX as u128),
+                ))),
+            },
+        }
+    }
+}
+
+impl fmt::Display for IpPrefix {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.0 {
+            IpAddr::V4(ipv4) => write!(f, "{ipv4}"),
+            IpAddr::V6(ipv6) => write!(f, "{ipv6}/64"),
+        }
+    }
+}
+
+impl serde::Serialize for IpPrefix {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+/// Slot of an open connection, released when dropped
+#[derive(Debug)]
+pub struct ConnectionGuard(IpPrefix);
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        if let Some(connections) = CONNECTIONS_PER_IP.get(&self.0) {
+            connections.fetch_sub(1, Ordering::AcqRel);
+        }
+        CONNECTIONS_PER_IP.remove_if(&self.0, |_, connections| {
+            connections.load(Ordering::Acquire) == 0
+        });
+    }
+}
+
+/// Take a connection slot for the IP, or `None` when it already has `MAX_CONNECTIONS_PER_IP` open
+/// connections
+pub fn acquire(ip: IpAddr) -> Option<ConnectionGuard> {
+    let prefix = IpPrefix::from(ip);
+    let connections = CONNECTIONS_PER_IP
+        .entry(prefix)
+        .or_default();
+    connections
+        .fetch_update(Ordering::AcqRel, Ordering::Acquire, |count| {
+            (count < max_connections_per_ip()).then_some(count + 1)
+        })
+        .ok()
+        .map(|_| ConnectionGuard(prefix))
+}
+
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct IpConnections {
+    pub ip: IpPrefix,
+    pub connections: u32,
+    pub limit: u32,
+    /// Whether new connections of the IP are rejected
+    pub blocked: bool,
+}
+
+/// IPs using at least 80% of their connection limit, the most connected first
+pub fn near_limit() -> Vec<IpConnections> {
+    let limit = max_connections_per_ip();
+    let mut near_limit: Vec<_> = CONNECTIONS_PER_IP
+        .iter()
+        .filter_map(|entry| {
+            let connections = entry.value().load(Ordering::Acquire);
+            (connections * 5 >= limit * 4).then_some(IpConnections {
+                ip: *entry.key(),
+                connections,
+                limit,
+                blocked: connections >= limit,
+            })
+        })
+        .collect();
+    near_limit.sort_by_key(|ip| Reverse(ip.connections));
+    near_limit
+}
+
+/// Window the Authorize requests of a charger are counted in
+const AUTHORIZE_WINDOW: Duration = Duration::from_secs(60);
+
+/// Authorize requests of each charger in its current window, and when the window started
+static AUTHORIZE_REQUESTS: LazyLock<DashMap<StationId, (u32, Instant)>> =
+    LazyLock::new(Default::default);
+
+fn max_auth_requests_per_minute() -> u32 {
+    const MAX_AUTH_REQUESTS_PER_MINUTE: &str = dotenv!("MAX_AUTH_REQUESTS_PER_MINUTE");
+    MAX_AUTH_REQUESTS_PER_MINUTE
+        .parse()
+        .expect("MAX_AUTH_REQUESTS_PER_MINUTE must be a number of requests")
+}
+
+/// Count an Authorize of the charger. Returns `false` once the charger sent more than
+/// `MAX_AUTH_REQUESTS_PER_MINUTE` in the minute, as it might be guessing idTags
+pub fn allow_authorize(station_id: &StationId) -> bool {
+    allow_authorize_at(station_id, Instant::now())
+}
+
+fn allow_authorize_at(station_id: &StationId, now: Instant) -> bool {
+    let (count, new_window) = {
+        let mut requests = AUTHORIZE_REQUESTS
+            .entry(station_id.clone())
+            .or_insert((0, now));
+        let (count, window_start) = &mut *requests;
+        let new_window = *count == 0 || now.duration_since(*window_start) >= AUTHORIZE_WINDOW;
+        if new_window {
+            *count = 0;
+            *window_start = now;
+        }
+        *count += 1;
+        (*count, new_window)
+    };
+    // Forget the chargers that stopped sending Authorize, whenever a window starts rather than on
+    // every request
+    if new_window {
+        AUTHORIZE_REQUESTS
+            .retain(|_, (_, window_start)| now.duration_since(*window_start) < AUTHORIZE_WINDOW);
+    }
+    let max = max_auth_requests_per_minute();
+    // Logged once per window, the following requests are answered without a log of their own
+    if count == max + 1 {
+        warn!(
+            "Possible idTag brute force: {station_id} sent more than {max} Authorize in a minute, \
+             answering Invalid until the minute ends"
+        );
+    }
+    count <= max
+}
+
+/// Window the connections of a charger are counted in to detect a disconnect loop
+const RECONNECT_WINDOW: Duration = Duration::from_secs(60);
+
+/// Connections in `RECONNECT_WINDOW` above which the connections of a charger are delayed
+const MAX_RECONNECTS_PER_WINDOW: usize = 5;
+
+/// Recent connections of a charger
+#[derive(Debug)]
+struct Reconnects {
+    /// Connections of the charger in the last `RECONNECT_WINDOW`
+    recent: Vec<Instant>,
+    /// Connections delayed since the charger last paused for `RECONNECT_BACKOFF_RESET_SECS`, each
+    /// one doubles the delay of the next
+    throttled: u32,
+}
+
+static RECONNECTS: LazyLock<DashMap<StationId, Reconnects>> = LazyLock::new(Default::default);
+
+fn reconnect_backoff_reset() -> Duration {
+    const RECONNECT_BACKOFF_RESET_SECS: &str = dotenv!("RECONNECT_BACKOFF_RESET_SECS");
+    Duration::from_secs(
+        RECONNECT_BACKOFF_RESET_SECS
+            .parse()
+            .expect("RECONNECT_BACKOFF_RESET_SECS must be a number of seconds"),
+    )
+}
+
+/// Count a connection of the charger. Returns how long to delay it, and the connections of the
+/// charger in the last minute, once it connected more than `MAX_RECONNECTS_PER_WINDOW` times in
+/// a minute. The delay doubles with each connection until the charger stays away for
+/// `RECONNECT_BACKOFF_RESET_SECS`
+fn reconnect_delay(station_id: &StationId, now: Instant) -> Option<(Duration, usize)> {
+    // Forget the chargers with no connection in the window whose backoff has reset, they would
+    // start over anyway
+    let forgotten_after = RECONNECT_WINDOW.max(reconnect_backoff_reset());
+    RECONNECTS.retain(|_, reconnects| {
+        reconnects
+            .recent
+            .last()
+            .is_some_and(|last| now.duration_since(*last) < forgotten_after)
+    });
+    let mut reconnects = RECONNECTS
+        .entry(station_id.clone())
+        .or_insert_with(|| Reconnects { recent: Vec::new(), throttled: 0 });
+    if reconnects
+        .recent
+        .last()
+        .is_some_and(|last| now.duration_since(*last) >= reconnect_backoff_reset())
+    {
+        reconnects.throttled = 0;
+    }
+    reconnects
+        .recent
+        .retain(|connected_at| now.duration_since(*connected_at) < RECONNECT_WINDOW);
+    reconnects.recent.push(now);
+    if reconnects.throttled == 0 && reconnects.recent.len() <= MAX_RECONNECTS_PER_WINDOW {
+        return None;
+    }
+    // From 1 s up to 64 s
+    let delay = Duration::from_secs(1 << reconnects.throttled.min(6));
+    reconnects.throttled += 1;
+    Some((delay, reconnects.recent.len()))
+}
+
+/// Hold the connection of a charger in a disconnect loop idle before serving it, so that each of
+/// its reconnections waits longer
+pub async fn throttle_reconnect(station_id: &StationId) {
+    let Some((delay, recent_connections)) = reconnect_delay(station_id, Instant::now()) else {
+        return;
+    };
+    warn!(
+        "{station_id} connected {recent_connections} times in the last minute, delaying its \
+         connection by {}s",
+        delay.as_secs()
+    );
+    tokio::time::sleep(delay).await;
+}
+
+#[cfg(test)]
+mod tests {
+    use std::net::Ipv4Addr;
+
+    use tracing_subscriber::{prelude::*, Registry};
+
+    use super::*;
+    use crate::tracing_tests::CaptureLayer;
+
+    fn prefix(ip: &str) -> IpPrefix { IpPrefix::from(ip.parse::<IpAddr>().unwrap()) }
+
+    #[test]
+    fn ipv6_addresses_are_grouped_by_their_64_prefix() {
+        let ip_prefix = prefix("2001:db8:1:2:aaaa::1");
+        assert_eq!(ip_prefix, prefix("2001:db8:1:2:bbbb::2"));
+        assert_ne!(ip_prefix, prefix("2001:db8:1:3:aaaa::1"));
+        assert_eq!(ip_prefix.to_string(), "2001:db8:1:2::/64");
+    }
+
+    #[test]
+    fn ipv4_mapped_address_is_its_ipv4_address() {
+        let ip_prefix = prefix("::ffff:198.51.100.7");
+        assert_eq!(ip_prefix, prefix("198.51.100.7"));
+        assert_eq!(ip_prefix.to_string(), "198.51.100.7");
+    }
+
+    #[test]
+    fn ip_is_near_limit_from_80_percent_of_its_connections() {
+        let ip = IpAddr::V4(Ipv4Addr::new(203, 0, 113, 80));
+        let limit = max_connections_per_ip();
+        let near_limit_of_ip = || {
+            near_limit()
+                .into_iter()
+                .find(|connections| connections.ip == IpPrefix::from(ip))
+        };
+        // Just below 80% of the limit
+        let mut guards: Vec<_> = (0..(limit * 4).div_ceil(5) - 1)
+            .map(|_| acquire(ip).unwrap())
+            .collect();
+        assert!(near_limit_of_ip().is_none());
+        guards.push(acquire(ip).unwrap());
+        let connections = near_limit_of_ip().unwrap();
+        assert_eq!(connections.connections, guards.len() as u32);
+        assert!(!connections.blocked);
+        while guards.len() < limit as usize {
+            guards.push(acquire(ip).unwrap());
+        }
+        assert!(acquire(ip).is_none());
+        assert!(near_limit_of_ip().unwrap().blocked);
+        drop(guards);
+        assert!(near_limit_of_ip().is_none());
+    }
+
+    // A single test, as starting a window evicts the windows of the other chargers, which would
+    // reset the counts of a concurrent test
+    #[test]
+    fn authorize_windows() {
+        let station_id = "RATE-LIMIT-AUTHORIZE".to_string();
+        let quiet_station_id = "RATE-LIMIT-AUTHORIZE-QUIET".to_string();
+        let capture = CaptureLayer::default();
+        let _subscriber =
+            tracing::subscriber::set_default(Registry::default().with(capture.clone()));
+        let warnings = || {
+            capture
+                .events
+                .lock()
+                .unwrap()
+                .iter()
+                .filter(|event| {
+                    event
+                        .message
+                        .starts_with("Possible idTag brute force")
+                })
+                .count()
+        };
+        let now = Instant::now();
+        let max = max_auth_requests_per_minute();
+        allow_authorize_at(&quiet_station_id, now);
+        for _ in 0..max {
+            assert!(allow_authorize_at(&station_id, now));
+        }
+        assert_eq!(warnings(), 0);
+        // Logged at the first request over the limit only
+        for _ in 0..3 {
+            assert!(!allow_authorize_at(&station_id, now + AUTHORIZE_WINDOW / 2));
+        }
+        assert_eq!(warnings(), 1);
+        // The next window starts over, and evicts the ended window of the quiet charger
+        assert!(allow_authorize_at(&station_id, now + AUTHORIZE_WINDOW));
+        assert!(!AUTHORIZE_REQUESTS.contains_key(&quiet_station_id));
+        for _ in 1..max {
+            assert!(allow_authorize_at(&station_id, now + AUTHORIZE_WINDOW));
+        }
+        assert!(!allow_authorize_at(&station_id, now + AUTHORIZE_WINDOW));
+        assert_eq!(warnings(), 2);
+    }
+
+    #[test]
+    fn reconnect_delay_doubles_until_the_charger_pauses() {
+        let station_id = "RATE-LIMIT-RECONNECT".to_string();
+        let quiet_station_id = "RATE-LIMIT-RECONNECT-QUIET".to_string();
+        let start = Instant::now();
+        reconnect_delay(&quiet_station_id, start);
+        let mut now = start;
+        for _ in 0..MAX_RECONNECTS_PER_WINDOW {
+            assert_eq!(reconnect_delay(&station_id, now), None);
+            now += Duration::from_secs(1);
+        }
+        for expected_secs in [1, 2, 4, 8, 16, 32, 64, 64] {
+            let (delay, _) = reconnect_delay(&station_id, now).unwrap();
+            assert_eq!(delay, Duration::from_secs(expected_secs));
+            now += Duration::from_secs(1);
+        }
+        // The pause resets the backoff, and the charger is forgotten along with the quiet one
+        now += reconnect_backoff_reset();
+        assert_eq!(reconnect_delay(&station_id, now), None);
+        assert!(!RECONNECTS.contains_key(&quiet_station_id));
+        let reconnects = RECONNECTS.get(&station_id).unwrap();
+        assert_eq!((reconnects.recent.len(), reconnects.throttled), (1, 0));
+    }
+}
@@ -0,0 +1,54 @@
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use dashmap::{mapref::entry::Entry, DashMap};
+use dotenvy_macro::dotenv;
+
+use crate::{connectors::ConnectorId, StationId};
+
+/// When the pending RemoteStartTransaction of each connector was sent
+static PENDING_REMOTE_STARTS: LazyLock<DashMap<(StationId, ConnectorId), Instant>> =
+    LazyLock::new(Default::default);
+
+fn remote_start_debounce() -> Duration {
+    const REMOTE_START_DEBOUNCE_SECS: &str = dotenv!("REMOTE_START_DEBOUNCE_SECS");
+    Duration::from_secs(
+        REMOTE_START_DEBOUNCE_SECS
+            .parse()
+            .expect("REMOTE_START_DEBOUNCE_SECS must be a number of seconds"),
+    )
+}
+
+/// Pending RemoteStartTransaction of a connector, released when dropped
+#[derive(Debug)]
+pub struct RemoteStartGuard {
+    key: (StationId, ConnectorId),
+    sent_at: Instant,
+}
+
+impl Drop for RemoteStartGuard {
+    fn drop(&mut self) {
+        // A newer start took over the connector once this one was older than the debounce window
+        PENDING_REMOTE_STARTS.remove_if(&self.key, |_, sent_at| *sent_at == self.sent_at);
+    }
+}
+
+/// Mark a RemoteStartTransaction as pending on the connector, or `None` when another one was sent
+/// less than `REMOTE_START_DEBOUNCE_SECS` ago and is still waiting for the charger, as the charger
+/// could start two overlapping transactions
+pub fn acquire(station_id: &StationId, connector_id: ConnectorId) -> Option<RemoteStartGuard> {
+    let key = (station_id.clone(), connector_id);
+    let sent_at = Instant::now();
+    match PENDING_REMOTE_STARTS.entry(key.clone()) {
+        Entry::Occupied(entry) if entry.get().elapsed() < remote_start_debounce() => return None,
+        Entry::Occupied(mut entry) => {
+            entry.insert(sent_at);
+        },
+        Entry::Vacant(entry) => {
+            entry.insert(sent_at);
+        },
+    }
+    Some(RemoteStartGuard { key, sent_at })
+}
@@ -0,0 +1,67 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use rust_ocpp::v1_6::messages::remote_stop_transaction::{
+    RemoteStopTransactionRequest, RemoteStopTransactionResponse,
+};
+use tracing::{info, warn};
+
+use crate::{
+    commands::{self, OcppError},
+    OcppActionEnum, StationId,
+};
+
+/// Transactions to stop once their charger connects again, by station
+static QUEUED_REMOTE_STOPS: LazyLock<Mutex<HashMap<StationId, Vec<i32>>>> =
+    LazyLock::new(Default::default);
+
+/// Send a RemoteStopTransaction for the transaction when its charger connects again
+pub fn queue(station_id: &StationId, transaction_id: i32) {
+    let mut queued = QUEUED_REMOTE_STOPS.lock().unwrap();
+    let transaction_ids = queued
+        .entry(station_id.clone())
+        .or_default();
+    if !transaction_ids.contains(&transaction_id) {
+        transaction_ids.push(transaction_id);
+    }
+}
+
+/// Drop the queued RemoteStopTransaction of a transaction the charger stopped
+pub fn cancel(transaction_id: i32) {
+    QUEUED_REMOTE_STOPS
+        .lock()
+        .unwrap()
+        .retain(|_, transaction_ids| {
+            transaction_ids.retain(|queued_id| *queued_id != transaction_id);
+            !transaction_ids.is_empty()
+        });
+}
+
+/// Send the RemoteStopTransactions queued while the charger was offline. They are queued again
+/// when the charger disconnects before answering
+pub async fn send_queued(station_id: StationId) {
+    let Some(transaction_ids) = QUEUED_REMOTE_STOPS
+        .lock()
+        .unwrap()
+        .remove(&station_id)
+    else {
+        return;
+    };
+    for transaction_id in transaction_ids {
+        let request = RemoteStopTransactionRequest { transaction_id };
+        let response: Result<RemoteStopTransactionResponse, _> =
+            commands::send_call(&station_id, OcppActionEnum::RemoteStopTransaction, &request).await;
+        match response {
+            Ok(response) => info!(
+                "{station_id} answered {:?} to the queued stop of transaction {transaction_id}",
+                response.status
+            ),
+            Err(OcppError::NotConnected) => queue(&station_id, transaction_id),
+            Err(err) => {
+                warn!("Failed to send the queued stop of transaction {transaction_id}: {err}")
+            },
+        }
+    }
+}
@@ -0,0 +1,296 @@
+//! Routing of the paths of the server. The charger WebSocket, the REST API and the metrics must
+//! never shadow one another, nor an unknown path reach one of them
+
+use axum::{
+    body::{self, Body},
+    http::{header, Method, Request, StatusCode},
+    response::Response,
+};
+use dotenvy_macro::dotenv;
+use metrics_exporter_prometheus::PrometheusBuilder;
+use tower::ServiceExt;
+
+async fn send(method: Method, uri: &str) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        // Headers of a WebSocket handshake, an upgrade route answers them with a 426 below
+        .header(header::CONNECTION, "upgrade")
+        .header(header::UPGRADE, "websocket")
+        .header(header::SEC_WEBSOCKET_VERSION, "13")
+        .header(header::SEC_WEBSOCKET_KEY, "dGhlIHNhbXBsZSBub25jZQ==")
+        .body(Body::empty())
+        .unwrap();
+    let metrics_handle = PrometheusBuilder::new()
+        .build_recorder()
+        .handle();
+    crate::app(metrics_handle)
+        .oneshot(request)
+        .await
+        .unwrap()
+}
+
+/// Send a JSON body to the REST API
+async fn send_json(method: Method, uri: &str, body: &'static str) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .body(Body::from(body))
+        .unwrap();
+    let metrics_handle = PrometheusBuilder::new()
+        .build_recorder()
+        .handle();
+    crate::app(metrics_handle)
+        .oneshot(request)
+        .await
+        .unwrap()
+}
+
+/// Send a JSON body to an admin route of the REST API, with the token as a Bearer token
+async fn send_json_as_admin(
+    method: Method,
+    uri: &str,
+    body: &'static str,
+    token: &str,
+) -> Response {
+    let request = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(header::CONTENT_TYPE, "application/json")
+        .header(header::AUTHORIZATION, format!("Bearer {token}"))
+        .body(Body::from(body))
+        .unwrap();
+    let metrics_handle = PrometheusBuilder::new()
+        .build_recorder()
+        .handle();
+    crate::app(metrics_handle)
+        .oneshot(request)
+        .await
+        .unwrap()
+}
+
+/// Check the response is a 401 with the error body of the REST API
+async fn assert_unauthorized(response: Response) {
+    assert_eq!(response.status(), StatusCode::UNAUTHORIZED);
+    assert_eq!(response.headers()[header::WWW_AUTHENTICATE], "Bearer");
+    let body = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"], "unauthorized");
+}
+
+/// Check the response is a 400 with the error body of the REST API
+async fn assert_invalid_input(response: Response) {
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+    let body = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    assert_eq!(body["error"], "invalid_input");
+    assert!(
+        body["detail"]
+            .as_str()
+            .is_some_and(|detail| !detail.is_empty()),
+        "{body}"
+    );
+}
+
+#[tokio::test]
+async fn ocpp_path_is_the_websocket_upgrade() {
+    let response = send(Method::GET, "/ocpp16j/CP001").await;
+    // A request without a connection to upgrade, as there is none in a test. The charger Basic
+    // Auth, when enabled, answers before the upgrade
+    assert!(
+        matches!(
+            response.status(),
+            StatusCode::UPGRADE_REQUIRED | StatusCode::UNAUTHORIZED
+        ),
+        "{}",
+        response.status()
+    );
+}
+
+#[tokio::test]
+async fn ocpp_path_is_only_upgraded_with_get() {
+    assert_eq!(
+        send(Method::POST, "/ocpp16j/CP001")
+            .await
+            .status(),
+        StatusCode::METHOD_NOT_ALLOWED
+    );
+}
+
+#[tokio::test]
+async fn api_path_is_the_rest_api() {
+    let response = send(Method::GET, "/api/server/capabilities").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(response.headers()[header::CONTENT_TYPE], "application/json");
+}
+
+#[tokio::test]
+async fn metrics_path_is_prometheus() {
+    let response = send(Method::GET, "/metrics").await;
+    assert_eq!(response.status(), StatusCode::OK);
+    assert_eq!(
+        response.headers()[header::CONTENT_TYPE],
+        "text/plain; charset=utf-8"
+    );
+}
+
+#[tokio::test]
+async fn unknown_api_paths_are_not_found() {
+    for uri in ["/api/unknown", "/api/ocpp16j/CP001", "/api/metrics"] {
+        assert_eq!(
+            send(Method::GET, uri).await.status(),
+            StatusCode::NOT_FOUND,
+            "{uri}"
+        );
+    }
+}
+
+#[tokio::test]
+async fn malformed_json_body_is_invalid_input() {
+    for uri in [
+        "/api/chargers/CP001/remote-start",
+        "/api/groups/1/actions/clear-cache",
+    ] {
+        assert_invalid_input(send_json(Method::POST, uri, r#"{"id_tag": "#).await).await;
+    }
+}
+
+#[tokio::test]
+async fn invalid_path_parameter_is_invalid_input() {
+    assert_invalid_input(send(Method::GET, "/api/groups/first/configuration").await).await;
+}
+
+#[tokio::test]
+async fn invalid_query_string_is_invalid_input() {
+    assert_invalid_input(send(Method::GET, "/api/chargers/CP001/faults?resolved=maybe").await)
+        .await;
+}
+
+#[tokio::test]
+async fn admin_route_without_token_is_unauthorized() {
+    let response = send_json(
+        Method::PUT,
+        "/api/chargers/CP001/auth-key",
+        r#"{"auth_key": "0123456789abcdef"}"#,
+    )
+    .await;
+    assert_unauthorized(response).await;
+}
+
+#[tokio::test]
+async fn admin_route_with_wrong_token_is_unauthorized() {
+    let response = send_json_as_admin(
+        Method::PUT,
+        "/api/chargers/CP001/auth-key",
+        r#"{"auth_key": "0123456789abcdef"}"#,
+        "wrong-token",
+    )
+    .await;
+    assert_unauthorized(response).await;
+}
+
+#[tokio::test]
+async fn admin_route_with_admin_token_reaches_the_handler() {
+    // Too short an AuthorizationKey, rejected by the handler before it is stored
+    let response = send_json_as_admin(
+        Method::PUT,
+        "/api/chargers/CP001/auth-key",
+        r#"{"auth_key": "short"}"#,
+        dotenv!("ADMIN_API_TOKEN"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[tokio::test]
+async fn log_level_is_an_admin_route() {
+    let response = send_json(
+        Method::PUT,
+        "/api/admin/log-level",
+        r#"{"filter": "debug"}"#,
+    )
+    .await;
+    assert_unauthorized(response).await;
+}
+
+#[tokio::test]
+async fn blocked_ips_is_an_admin_route() {
+    assert_unauthorized(send(Method::GET, "/api/admin/blocked-ips").await).await;
+}
+
+#[tokio::test]
+async fn raw_message_injection_is_an_admin_route() {
+    let body = r#"{"action": "GetConfiguration", "payload": {}}"#;
+    assert_unauthorized(send_json(Method::POST, "/api/chargers/CP001/message", body).await).await;
+}
+
+#[tokio::test]
+async fn alert_rules_are_admin_routes() {
+    let body = r#"{"rule_type": "charger_faulted", "webhook_url": "https://93.184.216.34/hook"}"#;
+    assert_unauthorized(send_json(Method::POST, "/api/alert-rules", body).await).await;
+}
+
+#[tokio::test]
+async fn alert_rule_webhook_to_a_private_host_is_rejected() {
+    let response = send_json_as_admin(
+        Method::POST,
+        "/api/alert-rules",
+        r#"{"rule_type": "charger_faulted", "webhook_url": "http://169.254.169.254/latest"}"#,
+        dotenv!("ADMIN_API_TOKEN"),
+    )
+    .await;
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+}
+
+#[cfg(feature = "soap")]
+#[tokio::test]
+async fn soap_request_of_another_charge_box_is_rejected() {
+    let envelope = r#"<Envelope>
+        <Header><chargeBoxIdentity>CP002</chargeBoxIdentity></Header>
+        <Body><heartbeatRequest/></Body>
+    </Envelope>"#;
+    let request = Request::builder()
+        .method(Method::POST)
+        .uri("/ocpp15s/CP001")
+        .header(header::CONTENT_TYPE, "application/soap+xml")
+        .body(Body::from(envelope))
+        .unwrap();
+    let metrics_handle = PrometheusBuilder::new()
+        .build_recorder()
+        .handle();
+    let response = crate::app(metrics_handle)
+        .oneshot(request)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+    let body = body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    assert!(String::from_utf8_lossy(&body).contains("chargeBoxIdentity"));
+}
+
+// The dashboard serves its client-side routes on the paths the other routes do not match
+#[cfg(not(feature = "web-ui"))]
+#[tokio::test]
+async fn unknown_paths_are_not_found() {
+    for uri in [
+        "/unknown",
+        "/ocpp16j",
+        "/ocpp16j/CP001/extra",
+        "/ocpp16jCP001",
+        "/apichargers",
+        "/metrics/extra",
+    ] {
+        assert_eq!(
+            send(Method::GET, uri).await.status(),
+            StatusCode::NOT_FOUND,
+            "{uri}"
+        );
+    }
+}
@@ -0,0 +1,92 @@
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::{
+    messages::get_configuration::{GetConfigurationRequest, GetConfigurationResponse},
+    types::KeyValue,
+};
+use tracing::warn;
+
+use crate::{StationId, HEARTBEAT_INTERVAL_SECS};
+
+/// Parameters of the server, named like OCPP configuration keys. Durations are in seconds, as in
+/// OCPP. They are set in the environment at build time, so they are all read-only
+pub fn keys() -> Vec<KeyValue> {
+    [
+        ("HeartbeatInterval", HEARTBEAT_INTERVAL_SECS.to_string()),
+        ("CallTimeout", dotenv!("OCPP_CALL_TIMEOUT_SECS").to_string()),
+        ("MaxClockSkew", dotenv!("MAX_CLOCK_SKEW_SECS").to_string()),
+        (
+            "ConfigurationCacheTtl",
+            dotenv!("CONFIG_CACHE_TTL_SECS").to_string(),
+        ),
+        (
+            "MaxConnectionsPerIp",
+            dotenv!("MAX_CONNECTIONS_PER_IP").to_string(),
+        ),
+        (
+            "RemoteStartDebounce",
+            dotenv!("REMOTE_START_DEBOUNCE_SECS").to_string(),
+        ),
+        (
+            "AuthorizationCacheSyncInterval",
+            dotenv!("AUTH_CACHE_SYNC_INTERVAL_SECS").to_string(),
+        ),
+        (
+            "InactivityTimeout",
+            dotenv!("INACTIVITY_TIMEOUT_SECS").to_string(),
+        ),
+        (
+            "ClockAlignedDataInterval",
+            dotenv!("CLOCK_ALIGNED_DATA_INTERVAL_SECS").to_string(),
+        ),
+        (
+            "OutboundBufferSize",
+            dotenv!("OUTBOUND_BUFFER_SIZE").to_string(),
+        ),
+    ]
+    .into_iter()
+    .map(|(key, value)| KeyValue {
+        key: key.to_string(),
+        readonly: true,
+        value: Some(value),
+    })
+    .collect()
+}
+
+/// Answer a GetConfiguration sent by a charger to the server with the keys of the server. Some
+/// firmware asks the server for NumberOfConnectors, a key only the charger has, which gets an
+/// empty value
+pub fn get_configuration(
+    station_id: &StationId,
+    request: &GetConfigurationRequest,
+) -> GetConfigurationResponse {
+    let keys = keys();
+    let Some(requested_keys) = &request.key else {
+        return GetConfigurationResponse {
+            configuration_key: Some(keys),
+            unknown_key: None,
+        };
+    };
+    let mut configuration_key = Vec::new();
+    let mut unknown_key = Vec::new();
+    for requested_key in requested_keys {
+        if let Some(key_value) = keys
+            .iter()
+            .find(|key_value| &key_value.key == requested_key)
+        {
+            configuration_key.push(key_value.clone());
+        } else if requested_key == "NumberOfConnectors" {
+            warn!("Charger {station_id} asked the server for its own NumberOfConnectors key");
+            configuration_key.push(KeyValue {
+                key: requested_key.clone(),
+                readonly: true,
+                value: Some(String::new()),
+            });
+        } else {
+            unknown_key.push(requested_key.clone());
+        }
+    }
+    GetConfigurationResponse {
+        configuration_key: Some(configuration_key),
+        unknown_key: (!unknown_key.is_empty()).then_some(unknown_key),
+    }
+}
@@ -0,0 +1,376 @@
+//! OCPP 1.5 over SOAP/HTTP (OCPP-S), for legacy chargers that do not speak OCPP-J.
+//!
+//! The SOAP body is converted to the equivalent OCPP 1.6 JSON payload and processed by the same
+//! handlers as the WebSocket messages. Their response is converted back to a SOAP envelope.
+
+use std::{io, str::FromStr};
+
+use axum::{
+    extract::{ws::Message as AxumWSMessage, Path},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+};
+use futures::{channel::mpsc, StreamExt};
+use quick_xml::{
+    events::{BytesDecl, BytesEnd, BytesStart, BytesText, Event},
+    Reader, Writer,
+};
+use serde_json::Value;
+use tracing::{error, info, warn, Span};
+
+use crate::{MessageTypeId, OcppActionEnum, StationId};
+
+const SOAP_ENVELOPE_NS: &str = "http://www.w3.org/2003/05/soap-envelope";
+const WS_ADDRESSING_NS: &str = "http://www.w3.org/2005/08/addressing";
+const OCPP_CENTRAL_SYSTEM_NS: &str = "urn://Ocpp/Cs/2012/06/";
+
+/// Fields whose SOAP text holds a JSON number
+const NUMERIC_FIELDS: [&str; 5] = [
+    "connectorId",
+    "meterStart",
+    "meterStop",
+    "transactionId",
+    "reservationId",
+];
+/// Fields holding a JSON array, even when the SOAP body only has a single element of them
+const ARRAY_FIELDS: [&str; 2] = ["meterValue", "sampledValue"];
+/// OCPP 1.5 elements renamed in OCPP 1.6, as (parent, OCPP 1.5 name, OCPP 1.6 name)
+const RENAMED_FIELDS: [(&str, &str, &str); 2] = [
+    ("meterValuesRequest", "values", "meterValue"),
+    ("values", "value", "sampledValue"),
+];
+/// OCPP 1.6 response fields renamed back to their OCPP 1.5 name
+const RENAMED_RESPONSE_FIELDS: [(&str, &str); 1] = [("interval", "heartbeatInterval")];
+
+/// An XML element with the namespace prefixes stripped
+#[derive(Debug, Default)]
+struct Element {
+    name: String,
+    attributes: Vec<(String, String)>,
+    children: Vec<Element>,
+    text: String,
+}
+
+impl Element {
+    fn child(&self, name: &str) -> Option<&Element> {
+        self.children
+            .iter()
+            .find(|child| child.name == name)
+    }
+}
+
+/// Handle an OCPP 1.5 SOAP request of a charger
+pub async fn handle_soap_request(Path(station_id): Path<StationId>, body: String) -> Response {
+    Span::current().record("station_id", station_id.as_str());
+    let envelope = match parse_xml(&body) {
+        Ok(envelope) if envelope.name == "Envelope" => envelope,
+        Ok(_) => return fault(StatusCode::BAD_REQUEST, "Sender", "Not a SOAP envelope"),
+        Err(err) => return fault(StatusCode::BAD_REQUEST, "Sender", &err),
+    };
+    let header = envelope.child("Header");
+    let Some(request) = envelope
+        .child("Body")
+        .and_then(|body| body.children.first())
+    else {
+        return fault(StatusCode::BAD_REQUEST, "Sender", "Empty SOAP body");
+    };
+
+    if let Some(charge_box_identity) = header.and_then(|header| header.child("chargeBoxIdentity"))
+        && charge_box_identity.text != station_id
+    {
+        // The credentials, when required, are those of the station of the path
+        warn!(
+            "Rejected SOAP request: chargeBoxIdentity {} does not match the station {station_id}",
+            charge_box_identity.text
+        );
+        return fault(
+            StatusCode::BAD_REQUEST,
+            "Sender",
+            "chargeBoxIdentity does not match the station of the URL",
+        );
+    }
+    let message_id = header
+        .and_then(|header| header.child("MessageID"))
+        .map(|message_id| message_id.text.clone())
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
+
+    // bootNotificationRequest -> BootNotification
+    let action = request
+        .name
+        .strip_suffix("Request")
+        .map(|action| {
+            let mut chars = action.chars();
+            chars
+                .next()
+                .map(|first| {
+                    first
+                        .to_uppercase()
+                        .chain(chars)
+                        .collect::<String>()
+                })
+                .unwrap_or_default()
+        })
+        .unwrap_or_default();
+    let action = match OcppActionEnum::from_str(&action) {
+        Ok(action) => action,
+        Err(_) => {
+            let reason = format!("Unknown OCPP action {}", request.name);
+            return fault(StatusCode::BAD_REQUEST, "Sender", &reason);
+        },
+    };
+    info!("Received OCPP SOAP {action} request from {station_id}");
+
+    // The handler writes its CallResult to the channel instead of a WebSocket
+    let (mut sender, mut receiver) = mpsc::unbounded::<AxumWSMessage>();
+    // Without its CallResult the request is answered with a fault below
+    if let Err(err) = crate::handle_ocpp_call(
+        MessageTypeId::CALL,
+        message_id.clone(),
+        action.clone(),
+        to_json(request),
+        &mut sender,
+        &station_id,
+    )
+    .await
+    {
+        error!("Failed to answer OCPP SOAP {action} request of {station_id}: {err:?}");
+    }
+    drop(sender);
+    let response = match receiver.next().await {
+        Some(AxumWSMessage::Text(text)) => serde_json::from_str::<Value>(&text).ok(),
+        _ => None,
+    };
+    // The CallResult is either an object or an array
+    let Some(payload) = response.and_then(|response| match response {
+        Value::Object(mut result) => result.remove("Payload"),
+        Value::Array(mut result) if result.len() == 3 => Some(result.remove(2)),
+        _ => None,
+    }) else {
+        return fault(
+            StatusCode::INTERNAL_SERVER_ERROR,
+            "Receiver",
+            &format!("No response to the {action} request"),
+        );
+    };
+
+    let xml = write_response(&action, &message_id, &payload).expect("Writing to a Vec cannot fail");
+    soap_response(StatusCode::OK, xml)
+}
+
+fn soap_response(status: StatusCode, xml: Vec<u8>) -> Response {
+    (
+        status,
+        [(header::CONTENT_TYPE, "application/soap+xml; charset=utf-8")],
+        xml,
+    )
+        .into_response()
+}
+
+fn fault(status: StatusCode, code: &str, reason: &str) -> Response {
+    warn!("Replying with a SOAP fault: {reason}");
+    let xml = write_fault(code, reason).expect("Writing to a Vec cannot fail");
+    soap_response(status, xml)
+}
+
+fn element(start: &BytesStart) -> Result<Element, String> {
+    let mut attributes = Vec::new();
+    for attribute in start.attributes() {
+        let attribute = attribute.map_err(|err| err.to_string())?;
+        // Namespace declarations are not data
+        if attribute
+            .key
+            .as_namespace_binding()
+            .is_some()
+        {
+            continue;
+        }
+        let value = attribute
+            .unescape_value()
+            .map_err(|err| err.to_string())?;
+        attributes.push((
+            String::from_utf8_lossy(attribute.key.local_name().as_ref()).into_owned(),
+            value.into_owned(),
+        ));
+    }
+    Ok(Element {
+        name: String::from_utf8_lossy(start.local_name().as_ref()).into_owned(),
+        attributes,
+        ..Default::default()
+    })
+}
+
+/// Parse an XML document into its root element
+fn parse_xml(xml: &str) -> Result<Element, String> {
+    let mut reader = Reader::from_str(xml);
+    reader.config_mut().trim_text(true);
+    let mut stack: Vec<Element> = Vec::new();
+    loop {
+        let closed = match reader
+            .read_event()
+            .map_err(|err| err.to_string())?
+        {
+            Event::Start(start) => {
+                stack.push(element(&start)?);
+                continue;
+            },
+            Event::Empty(start) => element(&start)?,
+            Event::Text(text) => {
+                if let Some(current) = stack.last_mut() {
+                    let text = text
+                        .unescape()
+                        .map_err(|err| err.to_string())?;
+                    current.text.push_str(&text);
+                }
+                continue;
+            },
+            Event::CData(data) => {
+                if let Some(current) = stack.last_mut() {
+                    current
+                        .text
+                        .push_str(&String::from_utf8_lossy(&data));
+                }
+                continue;
+            },
+            Event::End(_) => stack
+                .pop()
+                .ok_or("Unexpected closing tag")?,
+            Event::Eof => return Err("Unexpected end of the XML document".to_string()),
+            _ => continue,
+        };
+        match stack.last_mut() {
+            Some(parent) => parent.children.push(closed),
+            None => return Ok(closed),
+        }
+    }
+}
+
+/// Convert an OCPP 1.5 SOAP element to the OCPP 1.6 JSON object
+fn to_json(element: &Element) -> Value {
+    let mut object = serde_json::Map::new();
+    for (name, value) in &element.attributes {
+        object.insert(name.clone(), Value::String(value.clone()));
+    }
+    // <value unit="Wh">42</value> -> {"unit": "Wh", "value": "42"}
+    if !element.attributes.is_empty() && !element.text.is_empty() {
+        object.insert("value".to_string(), Value::String(element.text.clone()));
+    }
+    for child in &element.children {
+        let name = RENAMED_FIELDS
+            .iter()
+            .find(|(parent, old_name, _)| *parent == element.name && *old_name == child.name)
+            .map_or(child.name.as_str(), |(_, _, new_name)| new_name);
+        let value = if !child.children.is_empty() || !child.attributes.is_empty() {
+            to_json(child)
+        } else if NUMERIC_FIELDS.contains(&name)
+            && let Ok(number) = child.text.parse::<i64>()
+        {
+            Value::from(number)
+        } else {
+            Value::String(child.text.clone())
+        };
+        match object.get_mut(name) {
+            Some(Value::Array(values)) => values.push(value),
+            Some(existing) => *existing = Value::Array(vec![existing.take(), value]),
+            None if ARRAY_FIELDS.contains(&name) => {
+                object.insert(name.to_string(), Value::Array(vec![value]));
+            },
+            None => {
+                object.insert(name.to_string(), value);
+            },
+        }
+    }
+    Value::Object(object)
+}
+
+fn start_envelope(writer: &mut Writer<Vec<u8>>) -> io::Result<()> {
+    writer.write_event(Event::Decl(BytesDecl::new("1.0", Some("UTF-8"), None)))?;
+    writer.write_event(Event::Start(
+        BytesStart::new("soap:Envelope").with_attributes([
+            ("xmlns:soap", SOAP_ENVELOPE_NS),
+            ("xmlns:wsa", WS_ADDRESSING_NS),
+            ("xmlns:cs", OCPP_CENTRAL_SYSTEM_NS),
+        ]),
+    ))
+}
+
+fn write_text(writer: &mut Writer<Vec<u8>>, name: &str, text: &str) -> io::Result<()> {
+    writer
+        .create_element(name)
+        .write_text_content(BytesText::new(text))?;
+    Ok(())
+}
+
+/// SOAP envelope of the response to a charger request
+fn write_response(
+    action: &OcppActionEnum,
+    message_id: &str,
+    payload: &Value,
+) -> io::Result<Vec<u8>> {
+    let mut writer = Writer::new(Vec::new());
+    start_envelope(&mut writer)?;
+    writer.write_event(Event::Start(BytesStart::new("soap:Header")))?;
+    write_text(&mut writer, "wsa:Action", &format!("/{action}Response"))?;
+    write_text(&mut writer, "wsa:RelatesTo", message_id)?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Header")))?;
+    writer.write_event(Event::Start(BytesStart::new("soap:Body")))?;
+    // BootNotification -> bootNotificationResponse
+    let action = action.to_string();
+    let mut chars = action.chars();
+    let response_name = chars
+        .next()
+        .map(|first| {
+            first
+                .to_lowercase()
+                .chain(chars)
+                .collect::<String>()
+        })
+        .unwrap_or_default();
+    write_json(&mut writer, &format!("{response_name}Response"), payload)?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Body")))?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Envelope")))?;
+    Ok(writer.into_inner())
+}
+
+/// Write an OCPP 1.6 JSON value as `cs:` elements
+fn write_json(writer: &mut Writer<Vec<u8>>, name: &str, value: &Value) -> io::Result<()> {
+    let element_name = format!("cs:{name}");
+    match value {
+        Value::Null => Ok(()),
+        Value::Array(values) => values
+            .iter()
+            .try_for_each(|value| write_json(writer, name, value)),
+        Value::Object(fields) => {
+            writer.write_event(Event::Start(BytesStart::new(element_name.as_str())))?;
+            for (field, value) in fields {
+                let field = RENAMED_RESPONSE_FIELDS
+                    .iter()
+                    .find(|(new_name, _)| new_name == field)
+                    .map_or(field.as_str(), |(_, old_name)| old_name);
+                write_json(writer, field, value)?;
+            }
+            writer.write_event(Event::End(BytesEnd::new(element_name.as_str())))
+        },
+        Value::String(text) => write_text(writer, &element_name, text),
+        Value::Bool(_) | Value::Number(_) => write_text(writer, &element_name, &value.to_string()),
+    }
+}
+
+fn write_fault(code: &str, reason: &str) -> io::Result<Vec<u8>> {
+    let mut writer = Writer::new(Vec::new());
+    start_envelope(&mut writer)?;
+    writer.write_event(Event::Start(BytesStart::new("soap:Body")))?;
+    writer.write_event(Event::Start(BytesStart::new("soap:Fault")))?;
+    writer.write_event(Event::Start(BytesStart::new("soap:Code")))?;
+    write_text(&mut writer, "soap:Value", &format!("soap:{code}"))?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Code")))?;
+    writer.write_event(Event::Start(BytesStart::new("soap:Reason")))?;
+    writer
+        .create_element("soap:Text")
+        .with_attribute(("xml:lang", "en"))
+        .write_text_content(BytesText::new(reason))?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Reason")))?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Fault")))?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Body")))?;
+    writer.write_event(Event::End(BytesEnd::new("soap:Envelope")))?;
+    Ok(writer.into_inner())
+}
@@ -0,0 +1,71 @@
+use std::{
+    sync::LazyLock,
+    time::{Duration, Instant},
+};
+
+use chrono::Utc;
+use dashmap::DashMap;
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::messages::status_notification::StatusNotificationRequest;
+use tracing::{debug, Instrument, Span};
+
+use crate::{
+    connectors::{ConnectorId, ConnectorStatus},
+    StationId,
+};
+
+/// Last StatusNotification of each connector and when it was received, until it is processed
+static LAST_STATUSES: LazyLock<DashMap<(StationId, ConnectorId), (ConnectorStatus, Instant)>> =
+    LazyLock::new(Default::default);
+
+fn status_debounce() -> Duration {
+    const STATUS_DEBOUNCE_MS: &str = dotenv!("STATUS_DEBOUNCE_MS");
+    Duration::from_millis(
+        STATUS_DEBOUNCE_MS
+            .parse()
+            .expect("STATUS_DEBOUNCE_MS must be a number of milliseconds"),
+    )
+}
+
+/// Store and alert on the StatusNotification once no other one came for the connector within
+/// `STATUS_DEBOUNCE_MS`. Some firmware flips a connector between two statuses many times per
+/// second, only the last status of such a burst is processed
+pub fn debounce(station_id: &StationId, mut status_notification: StatusNotificationRequest) {
+    let key = (station_id.clone(), status_notification.connector_id);
+    let received_at = Instant::now();
+    // Stored with the time it was received at, not the one it is processed at
+    status_notification
+        .timestamp
+        .get_or_insert_with(Utc::now);
+    LAST_STATUSES.insert(
+        key.clone(),
+        (
+            ConnectorStatus(status_notification.status.clone()),
+            received_at,
+        ),
+    );
+    let station_id = station_id.clone();
+    tokio::spawn(
+        async move {
+            tokio::time::sleep(status_debounce()).await;
+            if LAST_STATUSES
+                .remove_if(&key, |_, (_, last_received_at)| {
+                    *last_received_at == received_at
+                })
+                .is_none()
+            {
+                if let Some(last_status) = LAST_STATUSES.get(&key) {
+                    debug!(
+                        "Debounced {} of connector {} of {station_id}, followed by {}",
+                        ConnectorStatus(status_notification.status),
+                        status_notification.connector_id,
+                        last_status.0
+                    );
+                }
+                return;
+            }
+            crate::process_status_notification(&station_id, &status_notification).await;
+        }
+        .instrument(Span::current()),
+    );
+}
@@ -0,0 +1,47 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+
+use crate::db::{self, StopReasonCount};
+
+/// How long a report is served without querying the database again
+const CACHE_TTL: TimeDelta = TimeDelta::minutes(5);
+
+/// Reports by start of the period and station, grouping every transaction of the period is heavy
+static REPORT_CACHE: LazyLock<Mutex<HashMap<ReportKey, CachedReport>>> =
+    LazyLock::new(Default::default);
+
+type ReportKey = (DateTime<Utc>, Option<String>);
+
+struct CachedReport {
+    report: Vec<StopReasonCount>,
+    computed_at: DateTime<Utc>,
+}
+
+/// Transactions stopped since the given time for each reason, of a station or of all of them,
+/// recomputed when the cached ones are older than 5 minutes
+pub async fn report(
+    since: DateTime<Utc>,
+    station_id: Option<&str>,
+) -> Result<Vec<StopReasonCount>, sqlx::Error> {
+    let key = (since, station_id.map(str::to_string));
+    if let Some(cached) = REPORT_CACHE.lock().unwrap().get(&key)
+        && Utc::now() - cached.computed_at < CACHE_TTL
+    {
+        return Ok(cached.report.clone());
+    }
+    let report = db::stop_reason_counts(since, station_id).await?;
+    let mut cache = REPORT_CACHE.lock().unwrap();
+    cache.retain(|_, cached| Utc::now() - cached.computed_at < CACHE_TTL);
+    cache.insert(
+        key,
+        CachedReport {
+            report: report.clone(),
+            computed_at: Utc::now(),
+        },
+    );
+    Ok(report)
+}
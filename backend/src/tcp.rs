@@ -0,0 +1,124 @@
+use std::{io, net::SocketAddr};
+
+use axum::extract::ws::Message as AxumWSMessage;
+use futures::{future, SinkExt, StreamExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio_util::codec::{Framed, LinesCodec, LinesCodecError};
+use tracing::{error, field, info, info_span, warn, Instrument, Span};
+use uuid::Uuid;
+
+use crate::{charger_auth, ocpp_version::OcppVersion, rate_limit, StationId};
+
+/// Accept OCPP-J over raw TCP, for embedded chargers without a WebSocket stack. Every message is a
+/// line of JSON. The first line identifies the charger with its station ID, as there is no URL.
+/// Not served while `OCPP_BASIC_AUTH` is enabled
+pub async fn serve(addr: String) {
+    // A station ID on a line carries no credentials, any client could take over a charger
+    if charger_auth::basic_auth_enabled() {
+        error!("Raw TCP OCPP is disabled, its chargers cannot authenticate with OCPP_BASIC_AUTH");
+        return;
+    }
+    let listener = TcpListener::bind(&addr)
+        .await
+        .unwrap_or_else(|_| panic!("Failed to bind to raw TCP address: {addr}"));
+    info!("Raw TCP OCPP listening on {addr}");
+    loop {
+        let (stream, peer_addr) = match listener.accept().await {
+            Ok(accepted) => accepted,
+            Err(err) => {
+                error!("Failed to accept raw TCP connection: {err:?}");
+                continue;
+            },
+        };
+        let connection_id = Uuid::new_v4();
+        let span = info_span!(
+            "connection",
+            %connection_id,
+            client_ip = %peer_addr.ip(),
+            // Recorded once the charger identifies itself, as in the span of the WebSocket upgrade
+            station_id = field::Empty,
+        );
+        tokio::spawn(handle_connection(stream, peer_addr, connection_id).instrument(span));
+    }
+}
+
+async fn handle_connection(stream: TcpStream, peer_addr: SocketAddr, connection_id: Uuid) {
+    let client_ip = peer_addr.ip();
+    // Too many connections from a single IP could indicate an attack
+    let Some(_connection_guard) = rate_limit::acquire(client_ip) else {
+        warn!(
+            "Rejected raw TCP connection from {}: too many connections from this IP",
+            rate_limit::IpPrefix::from(client_ip)
+        );
+        return;
+    };
+    // A line is never buffered past the longest message
+    let mut framed = Framed::new(
+        stream,
+        LinesCodec::new_with_max_length(crate::MAX_MESSAGE_LENGTH),
+    );
+    let station_id: StationId = match framed.next().await {
+        Some(Ok(line)) if !line.trim().is_empty() => line.trim().to_string(),
+        Some(Ok(_)) => {
+            warn!("Raw TCP connection did not identify itself with a station ID");
+            return;
+        },
+        Some(Err(err)) => {
+            warn!("Failed to read station ID of raw TCP connection: {err:?}");
+            return;
+        },
+        None => return,
+    };
+    Span::current().record("station_id", station_id.as_str());
+    // Server-initiated Calls are queued on this channel and written to the stream below. No
+    // subprotocol is negotiated over raw TCP
+    let (connection, mut outbound_receiver) = crate::ChargerConnection::open(
+        "raw TCP",
+        &station_id,
+        connection_id,
+        client_ip,
+        OcppVersion::V16,
+        None,
+    )
+    .await;
+
+    loop {
+        tokio::select! {
+            line = framed.next() => match line {
+                Some(Ok(line)) if line.trim().is_empty() => (),
+                Some(Ok(line)) => {
+                    // The handlers answer with WebSocket messages, written to the stream as lines
+                    let mut sink = (&mut framed).with(into_line);
+                    if let Err(err) = crate::handle_ocpp_messages(line, &mut sink, &station_id).await {
+                        error!("Failed to answer OCPP message of {station_id}: {err:?}");
+                        break;
+                    }
+                },
+                Some(Err(err)) => {
+                    warn!("Failed to read from raw TCP connection of {station_id}: {err:?}");
+                    break;
+                },
+                None => {
+                    info!("Raw TCP connection closed");
+                    break;
+                },
+            },
+            Some(call) = outbound_receiver.recv() => {
+                if let Err(err) = framed.send(call).await {
+                    error!("Failed to send OCPP Call to {station_id}: {err:?}");
+                    break;
+                }
+            },
+        }
+    }
+    connection.close().await;
+}
+
+fn into_line(message: AxumWSMessage) -> future::Ready<Result<String, LinesCodecError>> {
+    future::ready(match message {
+        AxumWSMessage::Text(text) => Ok(text),
+        message => Err(LinesCodecError::Io(io::Error::other(format!(
+            "{message:?} cannot be sent over raw TCP"
+        )))),
+    })
+}
@@ -0,0 +1,171 @@
+//! Tracing context of the tasks spawned by the handlers. A task spawned while handling a message
+//! must log within the span of that message, or its logs could not be traced back to the charger
+
+use std::{
+    collections::HashMap,
+    fmt,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use serde_json::json;
+use tracing::{
+    field::{Field, Visit},
+    info_span, span, Event, Instrument, Subscriber,
+};
+use tracing_subscriber::{layer::Context, prelude::*, registry::LookupSpan, Layer, Registry};
+
+use crate::{
+    mock_charger::{CallResponse, MockCharger},
+    OcppActionEnum,
+};
+
+/// Fields of a span or event, formatted with `Debug`
+#[derive(Default)]
+struct Fields(Vec<(String, String)>);
+
+impl Visit for Fields {
+    fn record_debug(&mut self, field: &Field, value: &dyn fmt::Debug) {
+        self.0
+            .push((field.name().to_string(), format!("{value:?}")));
+    }
+}
+
+/// Event logged with the fields of the spans it was logged in
+#[derive(Debug)]
+pub(crate) struct CapturedEvent {
+    pub(crate) message: String,
+    span_fields: HashMap<String, String>,
+}
+
+/// Layer capturing every event instead of writing it out
+#[derive(Clone, Default)]
+pub(crate) struct CaptureLayer {
+    pub(crate) events: Arc<Mutex<Vec<CapturedEvent>>>,
+}
+
+impl<S> Layer<S> for CaptureLayer
+where
+    S: Subscriber + for<'a> LookupSpan<'a>,
+{
+    fn on_new_span(&self, attributes: &span::Attributes<'_>, id: &span::Id, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        attributes.record(&mut fields);
+        ctx.span(id)
+            .unwrap()
+            .extensions_mut()
+            .insert(fields);
+    }
+
+    fn on_record(&self, id: &span::Id, values: &span::Record<'_>, ctx: Context<'_, S>) {
+        let span = ctx.span(id).unwrap();
+        let mut extensions = span.extensions_mut();
+        if let Some(fields) = extensions.get_mut::<Fields>() {
+            values.record(fields);
+        }
+    }
+
+    fn on_event(&self, event: &Event<'_>, ctx: Context<'_, S>) {
+        let mut fields = Fields::default();
+        event.record(&mut fields);
+        let message = fields
+            .0
+            .into_iter()
+            .find(|(name, _)| name == "message")
+            .map(|(_, value)| value)
+            .unwrap_or_default();
+        let mut span_fields = HashMap::new();
+        for span in ctx
+            .event_scope(event)
+            .into_iter()
+            .flat_map(|scope| scope.from_root())
+        {
+            if let Some(fields) = span.extensions().get::<Fields>() {
+                span_fields.extend(fields.0.iter().cloned());
+            }
+        }
+        self.events
+            .lock()
+            .unwrap()
+            .push(CapturedEvent { message, span_fields });
+    }
+}
+
+#[tokio::test]
+async fn spawned_task_logs_in_the_span_of_the_handler() {
+    const STATION_ID: &str = "MOCK-SPAWNED-TASK-SPAN";
+    let capture = CaptureLayer::default();
+    // The test runs on a single thread, with the tasks it spawns
+    let _subscriber = tracing::subscriber::set_default(Registry::default().with(capture.clone()));
+    // The paused clock jumps to the next timer whenever the test waits, instead of sleeping
+    tokio::time::pause();
+    let charger = MockCharger::connect(STATION_ID);
+    // Like the span of the WebSocket upgrade request, which records the station ID
+    let connection_span = info_span!("http_request", station_id = STATION_ID);
+    // A StatusNotification superseded by another one within STATUS_DEBOUNCE_MS is dropped by its
+    // debounce task, which logs it. The last one would be stored, the test ends before it is
+    for (message_id, status) in [("status-1", "Preparing"), ("status-2", "Available")] {
+        let response = charger
+            .send_call_with_message_id(
+                message_id,
+                OcppActionEnum::StatusNotification,
+                json!({ "connectorId": 1, "errorCode": "NoError", "status": status }),
+            )
+            .instrument(connection_span.clone())
+            .await;
+        assert!(matches!(response, CallResponse::CallResult(_)));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    // STATUS_DEBOUNCE_MS is 500, only the debounce task of the first StatusNotification is due
+    tokio::time::sleep(Duration::from_millis(350)).await;
+    let events = capture.events.lock().unwrap();
+    let debounced = events
+        .iter()
+        .find(|event| {
+            event
+                .message
+                .starts_with("Debounced Preparing")
+        })
+        .unwrap_or_else(|| panic!("The debounce task did not log: {events:#?}"));
+    assert_eq!(debounced.span_fields["message_id"], "status-1");
+    assert_eq!(
+        debounced.span_fields["station_id"],
+        format!("{STATION_ID:?}")
+    );
+}
+
+#[cfg(feature = "soap")]
+#[tokio::test]
+async fn soap_request_logs_in_the_span_of_its_station() {
+    use axum::{body::Body, http::Request};
+    use metrics_exporter_prometheus::PrometheusBuilder;
+    use tower::ServiceExt;
+
+    let capture = CaptureLayer::default();
+    let _subscriber = tracing::subscriber::set_default(Registry::default().with(capture.clone()));
+    // Rejected once the station ID is recorded, without the database
+    let envelope = r#"<Envelope>
+        <Header><chargeBoxIdentity>CP002</chargeBoxIdentity></Header>
+        <Body><heartbeatRequest/></Body>
+    </Envelope>"#;
+    let request = Request::post("/ocpp15s/MOCK-SOAP-SPAN")
+        .body(Body::from(envelope))
+        .unwrap();
+    let metrics_handle = PrometheusBuilder::new()
+        .build_recorder()
+        .handle();
+    crate::app(metrics_handle)
+        .oneshot(request)
+        .await
+        .unwrap();
+    let events = capture.events.lock().unwrap();
+    let rejected = events
+        .iter()
+        .find(|event| {
+            event
+                .message
+                .starts_with("Rejected SOAP request")
+        })
+        .unwrap_or_else(|| panic!("The SOAP request was not rejected: {events:#?}"));
+    assert_eq!(rejected.span_fields["station_id"], "\"MOCK-SOAP-SPAN\"");
+}
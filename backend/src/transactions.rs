@@ -0,0 +1,479 @@
+use std::{
+    collections::HashMap,
+    sync::{LazyLock, Mutex},
+    time::Duration,
+};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use dotenvy_macro::dotenv;
+use rust_ocpp::v1_6::{
+    messages::{
+        meter_values::MeterValuesRequest,
+        remote_stop_transaction::{RemoteStopTransactionRequest, RemoteStopTransactionResponse},
+    },
+    types::{Measurand, MeterValue, RemoteStartStopStatus, UnitOfMeasure},
+};
+use tokio::time::MissedTickBehavior;
+use tracing::warn;
+
+use crate::{commands, connectors::ConnectorId, db, mask::mask_id_tag, OcppActionEnum, StationId};
+
+/// How often the transactions are checked for inactivity
+const INACTIVITY_CHECK_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Where the server learned of a running transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TransactionSource {
+    /// Started by a StartTransaction while the server ran
+    StartTransaction,
+    /// Still active in the database when the server started, so it misses the meter values the
+    /// charger sent while the server was down
+    Reconciled,
+}
+
+/// Transaction running on a connector, kept up to date by the OCPP messages of the charger
+#[derive(Debug, Clone, PartialEq)]
+struct ActiveTransaction {
+    transaction_id: i32,
+    id_tag: String,
+    start_time: DateTime<Utc>,
+    meter_start: i32,
+    /// Latest energy register reading, in Wh
+    meter_now: i32,
+    /// When the last MeterValues of the transaction was received, its start until then
+    last_meter_value_time: DateTime<Utc>,
+    /// Current of the last meter value reporting it, the highest of its phases
+    current_import_a: Option<f64>,
+    /// Whether the charger accepted a RemoteStopTransaction sent for inactivity
+    inactivity_stop_requested: bool,
+    source: TransactionSource,
+}
+
+static ACTIVE_TRANSACTIONS: LazyLock<Mutex<HashMap<(StationId, ConnectorId), ActiveTransaction>>> =
+    LazyLock::new(Default::default);
+
+/// Snapshot of the transaction running on a connector
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct ActiveTransactionSummary {
+    pub transaction_id: i32,
+    /// Masked like in the logs
+    pub id_tag: String,
+    pub start_time: DateTime<Utc>,
+    pub energy_so_far_wh: i32,
+    pub duration_secs: i64,
+}
+
+pub fn start(
+    station_id: &StationId,
+    connector_id: ConnectorId,
+    transaction_id: i32,
+    id_tag: &str,
+    start_time: DateTime<Utc>,
+    meter_start: i32,
+) {
+    ACTIVE_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .insert(
+            (station_id.clone(), connector_id),
+            ActiveTransaction {
+                transaction_id,
+                id_tag: id_tag.to_string(),
+                start_time,
+                meter_start,
+                meter_now: meter_start,
+                last_meter_value_time: Utc::now(),
+                current_import_a: None,
+                inactivity_stop_requested: false,
+                source: TransactionSource::StartTransaction,
+            },
+        );
+}
+
+/// Load the transactions still active in the database, e.g. running when the server last stopped,
+/// so they can be followed and stopped like the others. Returns how many were loaded. Of several
+/// active transactions on a connector, only the latest is running
+pub async fn reconcile() -> Result<usize, sqlx::Error> {
+    let open_transactions = db::open_transactions().await?;
+    let now = Utc::now();
+    let mut transactions = ACTIVE_TRANSACTIONS.lock().unwrap();
+    for open_transaction in open_transactions {
+        transactions.insert(
+            (
+                open_transaction.station_id,
+                open_transaction.connector_id as ConnectorId,
+            ),
+            ActiveTransaction {
+                transaction_id: open_transaction.id,
+                id_tag: open_transaction.id_tag,
+                start_time: open_transaction.start_time,
+                meter_start: open_transaction.meter_start,
+                meter_now: open_transaction
+                    .last_reading_wh
+                    .map_or(open_transaction.meter_start, |wh| wh.round() as i32),
+                // Not stale before the charger had the time to send new meter values
+                last_meter_value_time: now,
+                current_import_a: None,
+                inactivity_stop_requested: false,
+                source: TransactionSource::Reconciled,
+            },
+        );
+    }
+    Ok(transactions
+        .values()
+        .filter(|transaction| transaction.source == TransactionSource::Reconciled)
+        .count())
+}
+
+/// Update the energy of the transaction from the energy register readings of a MeterValues
+pub fn update_meter(station_id: &StationId, meter_values: &MeterValuesRequest) {
+    let mut transactions = ACTIVE_TRANSACTIONS.lock().unwrap();
+    let Some(transaction) = transactions.get_mut(&(station_id.clone(), meter_values.connector_id))
+    else {
+        return;
+    };
+    if meter_values
+        .transaction_id
+        .is_some_and(|transaction_id| transaction_id != transaction.transaction_id)
+    {
+        return;
+    }
+    if let Some(meter_now) = energy_register_wh(&meter_values.meter_value) {
+        transaction.meter_now = meter_now.round() as i32;
+    }
+    transaction.last_meter_value_time = Utc::now();
+    if let Some(current_import_a) = current_import_a(&meter_values.meter_value) {
+        transaction.current_import_a = Some(current_import_a);
+    }
+}
+
+/// Current of the latest meter value reporting it, in A. The highest of the phases, so that a
+/// single phase charging counts as current flowing
+fn current_import_a(meter_values: &[MeterValue]) -> Option<f64> {
+    meter_values
+        .iter()
+        .rev()
+        .find_map(|meter_value| {
+            meter_value
+                .sampled_value
+                .iter()
+                .filter(|sampled_value| sampled_value.measurand == Some(Measurand::CurrentImport))
+                .filter_map(|sampled_value| sampled_value.value.parse::<f64>().ok())
+                .reduce(f64::max)
+        })
+}
+
+/// Active energy reading converted to Wh, or `None` when the unit is not one of an active energy.
+/// Reactive energy, in varh or kvarh, is not delivered to the vehicle and never counts as Wh
+pub fn normalize_to_wh(value: f64, unit: &UnitOfMeasure) -> Option<f64> {
+    match unit {
+        UnitOfMeasure::Wh => Some(value),
+        UnitOfMeasure::KWh => Some(value * 1000.0),
+        _ => None,
+    }
+}
+
+/// Latest energy register reading among the meter values, in Wh
+pub fn energy_register_wh(meter_values: &[MeterValue]) -> Option<f64> {
+    register_wh(meter_values, Measurand::EnergyActiveImportRegister)
+}
+
+/// Latest reading of the register of the energy fed back to the grid by a bi-directional (V2G)
+/// charger, in Wh
+pub fn export_register_wh(meter_values: &[MeterValue]) -> Option<f64> {
+    register_wh(meter_values, Measurand::EnergyActiveExportRegister)
+}
+
+fn register_wh(meter_values: &[MeterValue], register: Measurand) -> Option<f64> {
+    meter_values
+        .iter()
+        .flat_map(|meter_value| &meter_value.sampled_value)
+        // The import register is the default measurand, and Wh its default unit
+        .filter(|sampled_value| {
+            sampled_value
+                .measurand
+                .as_ref()
+                .unwrap_or(&Measurand::EnergyActiveImportRegister)
+                == &register
+        })
+        .filter_map(|sampled_value| {
+            let value = sampled_value.value.parse().ok()?;
+            normalize_to_wh(
+                value,
+                sampled_value
+                    .unit
+                    .as_ref()
+                    .unwrap_or(&UnitOfMeasure::Wh),
+            )
+        })
+        .next_back()
+}
+
+/// Forget the transaction. Returns where the server learned of it, `None` when it was not running
+pub fn stop(transaction_id: i32) -> Option<TransactionSource> {
+    let mut source = None;
+    ACTIVE_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .retain(|_, transaction| {
+            if transaction.transaction_id != transaction_id {
+                return true;
+            }
+            source = Some(transaction.source);
+            false
+        });
+    source
+}
+
+/// Charger the transaction is running on, or `None` when it is not running
+pub fn station_of(transaction_id: i32) -> Option<StationId> {
+    ACTIVE_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .find(|(_, transaction)| transaction.transaction_id == transaction_id)
+        .map(|((station_id, _), _)| station_id.clone())
+}
+
+/// Transaction running on the connector, or `None` when the connector is idle
+pub fn active_transaction(
+    station_id: &StationId,
+    connector_id: ConnectorId,
+) -> Option<ActiveTransactionSummary> {
+    ACTIVE_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .get(&(station_id.clone(), connector_id))
+        .map(|transaction| ActiveTransactionSummary {
+            transaction_id: transaction.transaction_id,
+            id_tag: mask_id_tag(&transaction.id_tag),
+            start_time: transaction.start_time,
+            energy_so_far_wh: transaction.meter_now - transaction.meter_start,
+            duration_secs: (Utc::now() - transaction.start_time).num_seconds(),
+        })
+}
+
+/// Transaction left open by its charger without charging
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct StaleSession {
+    pub station_id: StationId,
+    pub connector_id: ConnectorId,
+    pub transaction_id: i32,
+    /// Masked like in the logs
+    pub id_tag: String,
+    pub start_time: DateTime<Utc>,
+    pub last_meter_value_time: DateTime<Utc>,
+    pub inactive_secs: i64,
+}
+
+fn inactivity_timeout() -> TimeDelta {
+    const INACTIVITY_TIMEOUT_SECS: &str = dotenv!("INACTIVITY_TIMEOUT_SECS");
+    TimeDelta::seconds(
+        INACTIVITY_TIMEOUT_SECS
+            .parse()
+            .expect("INACTIVITY_TIMEOUT_SECS must be a number of seconds"),
+    )
+}
+
+/// Transactions without MeterValues for more than `INACTIVITY_TIMEOUT_SECS` whose last reported
+/// current is 0 A, e.g. a connector left plugged in once the vehicle is full. A transaction that
+/// never reported its current is not stale, as it might be charging
+pub fn stale_sessions() -> Vec<StaleSession> {
+    let now = Utc::now();
+    let inactivity_timeout = inactivity_timeout();
+    ACTIVE_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .iter()
+        .filter(|(_, transaction)| {
+            now - transaction.last_meter_value_time > inactivity_timeout
+                && transaction.current_import_a == Some(0.0)
+        })
+        .map(|((station_id, connector_id), transaction)| StaleSession {
+            station_id: station_id.clone(),
+            connector_id: *connector_id,
+            transaction_id: transaction.transaction_id,
+            id_tag: mask_id_tag(&transaction.id_tag),
+            start_time: transaction.start_time,
+            last_meter_value_time: transaction.last_meter_value_time,
+            inactive_secs: (now - transaction.last_meter_value_time).num_seconds(),
+        })
+        .collect()
+}
+
+/// Every minute, send a RemoteStopTransaction for the stale sessions. A charger that does not
+/// accept it, or is not connected, is asked again the next minute
+pub async fn stop_inactive() {
+    let mut interval = tokio::time::interval(INACTIVITY_CHECK_INTERVAL);
+    interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+    loop {
+        interval.tick().await;
+        for session in stale_sessions() {
+            if inactivity_stop_requested(session.transaction_id) {
+                continue;
+            }
+            let transaction_id = session.transaction_id;
+            let station_id = &session.station_id;
+            warn!(
+                "Transaction {transaction_id} of {station_id} has no meter values and no current \
+                 for {}s, stopping it",
+                session.inactive_secs
+            );
+            let request = RemoteStopTransactionRequest { transaction_id };
+            let response: RemoteStopTransactionResponse = match commands::send_call(
+                station_id,
+                OcppActionEnum::RemoteStopTransaction,
+                &request,
+            )
+            .await
+            {
+                Ok(response) => response,
+                Err(err) => {
+                    warn!("Failed to stop inactive transaction {transaction_id}: {err}");
+                    continue;
+                },
+            };
+            match response.status {
+                RemoteStartStopStatus::Accepted => {
+                    set_inactivity_stop_requested(transaction_id);
+                },
+                RemoteStartStopStatus::Rejected => {
+                    warn!(
+                        "{station_id} rejected the stop of inactive transaction {transaction_id}"
+                    );
+                },
+            }
+        }
+    }
+}
+
+fn inactivity_stop_requested(transaction_id: i32) -> bool {
+    ACTIVE_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .values()
+        .any(|transaction| {
+            transaction.transaction_id == transaction_id && transaction.inactivity_stop_requested
+        })
+}
+
+fn set_inactivity_stop_requested(transaction_id: i32) {
+    for transaction in ACTIVE_TRANSACTIONS
+        .lock()
+        .unwrap()
+        .values_mut()
+        .filter(|transaction| transaction.transaction_id == transaction_id)
+    {
+        transaction.inactivity_stop_requested = true;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use rust_ocpp::v1_6::types::SampledValue;
+
+    use super::*;
+
+    fn energy_register(value: &str, unit: Option<UnitOfMeasure>) -> Vec<MeterValue> {
+        vec![MeterValue {
+            timestamp: Utc::now(),
+            sampled_value: vec![SampledValue {
+                value: value.to_string(),
+                unit,
+                ..Default::default()
+            }],
+        }]
+    }
+
+    #[test]
+    fn wh_is_kept() {
+        assert_eq!(normalize_to_wh(1500.0, &UnitOfMeasure::Wh), Some(1500.0));
+    }
+
+    #[test]
+    fn kwh_is_converted_to_wh() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::KWh), Some(1500.0));
+    }
+
+    #[test]
+    fn varh_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Varh), None);
+    }
+
+    #[test]
+    fn kvarh_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Kvarh), None);
+    }
+
+    #[test]
+    fn w_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::W), None);
+    }
+
+    #[test]
+    fn kw_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Kw), None);
+    }
+
+    #[test]
+    fn va_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Va), None);
+    }
+
+    #[test]
+    fn kva_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Kva), None);
+    }
+
+    #[test]
+    fn var_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Var), None);
+    }
+
+    #[test]
+    fn kvar_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Kvar), None);
+    }
+
+    #[test]
+    fn a_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::A), None);
+    }
+
+    #[test]
+    fn v_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::V), None);
+    }
+
+    #[test]
+    fn celsius_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Celsius), None);
+    }
+
+    #[test]
+    fn fahrenheit_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Fahrenheit), None);
+    }
+
+    #[test]
+    fn k_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::K), None);
+    }
+
+    #[test]
+    fn percent_is_not_active_energy() {
+        assert_eq!(normalize_to_wh(1.5, &UnitOfMeasure::Percent), None);
+    }
+
+    #[test]
+    fn missing_unit_is_wh() {
+        assert_eq!(
+            energy_register_wh(&energy_register("1500", None)),
+            Some(1500.0)
+        );
+        assert_eq!(
+            energy_register_wh(&energy_register("1.5", Some(UnitOfMeasure::KWh))),
+            Some(1500.0)
+        );
+    }
+}
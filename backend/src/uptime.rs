@@ -0,0 +1,56 @@
+use chrono::{DateTime, Utc};
+
+use crate::{commands, db, StationId};
+
+#[derive(serde::Serialize, Debug, Clone, PartialEq)]
+pub struct Uptime {
+    /// Share of the period the charger was connected, to one decimal
+    pub uptime_percentage: f64,
+    pub total_downtime_secs: i64,
+    /// Times the charger was disconnected during the period, the ongoing one included
+    pub outage_count: usize,
+}
+
+/// How long the charger was connected since the given time, from its WebSocket sessions. The
+/// period starts at the first connection of the charger when it is more recent, so a new charger is
+/// not counted as down before it was installed. `None` when the charger never connected
+pub async fn uptime(
+    station_id: &StationId,
+    since: DateTime<Utc>,
+) -> Result<Option<Uptime>, sqlx::Error> {
+    let Some(first_connected_at) = db::first_connected_at(station_id).await? else {
+        return Ok(None);
+    };
+    let since = since.max(first_connected_at);
+    let until = Utc::now();
+    let connected = commands::is_connected(station_id);
+    let intervals = db::online_intervals(station_id, since, until, connected).await?;
+
+    let period_ms = (until - since).num_milliseconds();
+    let online_ms: i64 = intervals
+        .iter()
+        .map(|interval| (interval.online_to - interval.online_from).num_milliseconds())
+        .sum();
+    // The intervals are merged, so there is an outage before each one that does not start the
+    // period, and after the last one unless the charger is still connected
+    let mut outage_count = intervals
+        .iter()
+        .filter(|interval| interval.online_from > since)
+        .count();
+    if intervals
+        .last()
+        .is_none_or(|interval| interval.online_to < until)
+    {
+        outage_count += 1;
+    }
+    let uptime_percentage = if period_ms > 0 {
+        (online_ms as f64 * 1000.0 / period_ms as f64).round() / 10.0
+    } else {
+        100.0
+    };
+    Ok(Some(Uptime {
+        uptime_percentage,
+        total_downtime_secs: (period_ms - online_ms) / 1000,
+        outage_count,
+    }))
+}
@@ -0,0 +1,66 @@
+use axum::{
+    extract::Path,
+    http::{header, HeaderValue, StatusCode},
+    response::{IntoResponse, Response},
+    routing::get,
+    Router,
+};
+use rust_embed::RustEmbed;
+
+/// Dashboard built by `vite build`, embedded in release builds and read from the disk in debug ones.
+/// Without a build the server still compiles, and answers the dashboard paths with a 404
+#[derive(RustEmbed)]
+#[folder = "../dist"]
+#[allow_missing = true]
+struct Dashboard;
+
+/// Files of the dashboard, on `/` and the paths the other routes do not match
+pub fn router() -> Router {
+    Router::new()
+        .route("/", get(index))
+        .route("/*path", get(static_file))
+}
+
+async fn index() -> Response { file("index.html") }
+
+/// Embedded file at the path, or the dashboard for the paths of its client-side routes
+async fn static_file(Path(path): Path<String>) -> Response {
+    // A mistyped API path is not a page of the dashboard
+    if path.starts_with("api/") {
+        return StatusCode::NOT_FOUND.into_response();
+    }
+    if Dashboard::get(&path).is_some() {
+        file(&path)
+    } else {
+        file("index.html")
+    }
+}
+
+fn file(path: &str) -> Response {
+    let Some(file) = Dashboard::get(path) else {
+        return (
+            StatusCode::NOT_FOUND,
+            "The dashboard is not built, run `vite build` before building the server",
+        )
+            .into_response();
+    };
+    // The bundles in assets/ have the hash of their content in their name, the other files must be
+    // revalidated so that a new deployment is picked up
+    let cache_control =
+        if path.starts_with("assets/") { "public, max-age=31536000" } else { "no-cache" };
+    (
+        [
+            (
+                header::CONTENT_TYPE,
+                HeaderValue::from_str(file.metadata.mimetype())
+                    .unwrap_or(HeaderValue::from_static("application/octet-stream")),
+            ),
+            (
+                header::CACHE_CONTROL,
+                HeaderValue::from_static(cache_control),
+            ),
+        ],
+        file.data,
+    )
+        .into_response()
+}
@@ -0,0 +1,208 @@
+//! Wire format of the OCPP 1.6 payloads, checked against the examples of the specification. Guards
+//! the serde renames of `OcppPayload` and of the `rust_ocpp` messages across upgrades, and the
+//! dateTime format of the server
+
+use chrono::{TimeDelta, TimeZone, Utc};
+use serde_json::json;
+
+use crate::{ocpp_datetime, OcppActionEnum, OcppPayload};
+
+/// Parse the payload of a Call of the action, and check it serializes back to the same JSON
+fn assert_round_trip(action: OcppActionEnum, payload: serde_json::Value) {
+    let parsed = OcppPayload::deserialize_request(&action, payload.clone())
+        .unwrap_or_else(|err| panic!("Failed to parse {action:?} payload: {err}"));
+    assert_eq!(serde_json::to_value(&parsed).unwrap(), payload);
+}
+
+#[test]
+fn authorize() { assert_round_trip(OcppActionEnum::Authorize, json!({ "idTag": "B4F62CEF" })); }
+
+#[test]
+fn boot_notification() {
+    assert_round_trip(
+        OcppActionEnum::BootNotification,
+        json!({
+            "chargePointVendor": "VendorX",
+            "chargePointModel": "SingleSocketCharger",
+            "chargePointSerialNumber": "CP-0001",
+            "firmwareVersion": "1.2.3",
+            "meterType": "AC",
+        }),
+    );
+}
+
+#[test]
+fn change_availability() {
+    assert_round_trip(
+        OcppActionEnum::ChangeAvailability,
+        json!({ "connectorId": 1, "type": "Inoperative" }),
+    );
+}
+
+#[test]
+fn change_configuration() {
+    assert_round_trip(
+        OcppActionEnum::ChangeConfiguration,
+        json!({ "key": "HeartbeatInterval", "value": "300" }),
+    );
+}
+
+#[test]
+fn clear_cache() { assert_round_trip(OcppActionEnum::ClearCache, json!({})); }
+
+#[test]
+fn data_transfer() {
+    assert_round_trip(
+        OcppActionEnum::DataTransfer,
+        json!({
+            "vendorId": "com.vendorx",
+            "messageId": "DisplayMessage",
+            "data": "Welcome",
+        }),
+    );
+}
+
+#[test]
+fn get_configuration() {
+    assert_round_trip(
+        OcppActionEnum::GetConfiguration,
+        json!({ "key": ["HeartbeatInterval", "MeterValueSampleInterval"] }),
+    );
+}
+
+#[test]
+fn heartbeat() { assert_round_trip(OcppActionEnum::Heartbeat, json!({})); }
+
+#[test]
+fn meter_values() {
+    assert_round_trip(
+        OcppActionEnum::MeterValues,
+        json!({
+            "connectorId": 1,
+            "transactionId": 170,
+            "meterValue": [{
+                "timestamp": "2013-02-01T20:53:32.486Z",
+                "sampledValue": [
+                    {
+                        "value": "1337",
+                        "context": "Sample.Periodic",
+                        "measurand": "Energy.Active.Import.Register",
+                        "unit": "Wh",
+                    },
+                    {
+                        "value": "16.0",
+                        "measurand": "Current.Import",
+                        "phase": "L1",
+                        "unit": "A",
+                    },
+                ],
+            }],
+        }),
+    );
+}
+
+#[test]
+fn remote_start_transaction() {
+    assert_round_trip(
+        OcppActionEnum::RemoteStartTransaction,
+        json!({ "connectorId": 1, "idTag": "B4F62CEF" }),
+    );
+}
+
+#[test]
+fn remote_stop_transaction() {
+    assert_round_trip(
+        OcppActionEnum::RemoteStopTransaction,
+        json!({ "transactionId": 170 }),
+    );
+}
+
+#[test]
+fn reset() { assert_round_trip(OcppActionEnum::Reset, json!({ "type": "Soft" })); }
+
+#[test]
+fn start_transaction() {
+    assert_round_trip(
+        OcppActionEnum::StartTransaction,
+        json!({
+            "connectorId": 1,
+            "idTag": "B4F62CEF",
+            "meterStart": 0,
+            "timestamp": "2013-02-01T15:09:18Z",
+        }),
+    );
+}
+
+#[test]
+fn status_notification() {
+    assert_round_trip(
+        OcppActionEnum::StatusNotification,
+        json!({
+            "connectorId": 1,
+            "errorCode": "NoError",
+            "status": "Charging",
+            "timestamp": "2013-02-01T15:09:18Z",
+        }),
+    );
+}
+
+#[test]
+fn stop_transaction() {
+    assert_round_trip(
+        OcppActionEnum::StopTransaction,
+        json!({
+            "transactionId": 170,
+            "idTag": "B4F62CEF",
+            "meterStop": 2050,
+            "timestamp": "2013-02-01T17:42:07Z",
+            "reason": "EVDisconnected",
+        }),
+    );
+}
+
+#[test]
+fn unlock_connector() {
+    assert_round_trip(OcppActionEnum::UnlockConnector, json!({ "connectorId": 1 }));
+}
+
+#[test]
+fn payload_of_another_action() {
+    let err = OcppPayload::deserialize_request(
+        &OcppActionEnum::BootNotification,
+        json!({
+            "chargePointVendor": "VendorX",
+            "chargePointModel": "SingleSocketCharger",
+            "meterStop": 2050,
+        }),
+    )
+    .unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Expected BootNotificationRequest but got unknown field 'meterStop'"
+    );
+}
+
+#[test]
+fn current_time_in_utc() {
+    let current_time = Utc
+        .with_ymd_and_hms(2024, 1, 1, 10, 0, 0)
+        .unwrap()
+        + TimeDelta::nanoseconds(123_456_789);
+    assert_eq!(ocpp_datetime(current_time), "2024-01-01T10:00:00.123Z");
+}
+
+#[test]
+fn current_time_on_the_second() {
+    let current_time = Utc
+        .with_ymd_and_hms(2024, 1, 1, 10, 0, 0)
+        .unwrap();
+    assert_eq!(ocpp_datetime(current_time), "2024-01-01T10:00:00.000Z");
+}
+
+#[test]
+fn current_time_in_the_timezone_of_the_charger() {
+    let current_time = chrono_tz::America::Sao_Paulo
+        .with_ymd_and_hms(2024, 1, 1, 7, 0, 0)
+        .unwrap();
+    assert_eq!(ocpp_datetime(current_time), "2024-01-01T07:00:00.000-03:00");
+}